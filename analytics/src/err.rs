@@ -0,0 +1,24 @@
+#[derive(Debug)]
+pub enum AnalyticsErr {
+    InvalidSerialInterval,
+    InvalidWindow,
+    NegativeIncidence,
+    NotEnoughData(usize),
+    InvalidSmoothingParam,
+    NotConverged,
+}
+
+impl std::error::Error for AnalyticsErr {}
+
+impl std::fmt::Display for AnalyticsErr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AnalyticsErr::InvalidSerialInterval => write!(f, "The mean and the standard deviation of the serial interval should be positive"),
+            AnalyticsErr::InvalidWindow => write!(f, "The window should contain at least one day"),
+            AnalyticsErr::NegativeIncidence => write!(f, "The incidence can't be negative"),
+            AnalyticsErr::NotEnoughData(min) => write!(f, "The series should contain at least {min} values"),
+            AnalyticsErr::InvalidSmoothingParam => write!(f, "The smoothing parameters should be between 0 and 1"),
+            AnalyticsErr::NotConverged => write!(f, "The incomplete gamma function did not converge"),
+        }
+    }
+}
@@ -0,0 +1,184 @@
+// Short-term forecasting with the Holt linear trend method (double exponential smoothing).
+// For a series y the level l and the trend b are updated for each value with
+//     - l(t) = alpha * y(t) + (1 - alpha) * (l(t - 1) + b(t - 1))
+//     - b(t) = beta * (l(t) - l(t - 1)) + (1 - beta) * b(t - 1)
+// and the forecast of h days after the last value is l + h * b.
+//
+// The smoothing parameters are fitted by a grid search minimizing the sum of the squared
+// one-step-ahead errors. The prediction interval uses the variance of the equivalent
+// ETS(A,A,N) model (Hyndman & Athanasopoulos, Forecasting: Principles and Practice)
+//     var(h) = sigma² * (1 + Σ_{j=1}^{h-1} (alpha * (1 + j * beta))²)
+use super::err::AnalyticsErr;
+
+// The level is initialized with the first value & the trend with the first difference.
+// Two more values are needed to get at least two one-step-ahead errors
+const MIN_VALUES: usize = 4;
+const GRID_STEP: f64 = 0.05;
+// z-score of the 95% prediction interval
+const INTERVAL_ZSCORE: f64 = 1.96;
+
+/// Holt model fitted on a series
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Holt {
+    pub alpha: f64,
+    pub beta: f64,
+    pub level: f64,
+    pub trend: f64,
+    // standard deviation of the one-step-ahead errors
+    pub sigma: f64
+}
+
+/// Forecast of a day with its 95% prediction interval
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Forecast {
+    pub value: f64,
+    pub lower: f64,
+    pub upper: f64
+}
+
+impl Holt {
+    /// Fit a Holt model with the given smoothing parameters
+    ///
+    /// # Arguments
+    /// * `series` - &[f64]
+    /// * `alpha` - f64
+    /// * `beta` - f64
+    pub fn fit_with(series: &[f64], alpha: f64, beta: f64) -> Result<Holt, AnalyticsErr> {
+        if series.len() < MIN_VALUES {
+            return Err(AnalyticsErr::NotEnoughData(MIN_VALUES));
+        }
+
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return Err(AnalyticsErr::InvalidSmoothingParam);
+        }
+
+        let mut level = series[0];
+        let mut trend = series[1] - series[0];
+        let mut sse = 0.0;
+        for (idx, value) in series.iter().enumerate().skip(1) {
+            // the error of the second value is always 0 as the trend is initialized with it
+            if idx > 1 {
+                sse += (value - (level + trend)).powi(2);
+            }
+
+            let previous = level;
+            level = alpha * value + (1.0 - alpha) * (level + trend);
+            trend = beta * (level - previous) + (1.0 - beta) * trend;
+        }
+
+        Ok(Holt {
+            alpha,
+            beta,
+            level,
+            trend,
+            sigma: (sse / (series.len() - 2) as f64).sqrt()
+        })
+    }
+
+    /// Fit a Holt model by searching the smoothing parameters which minimize
+    /// the one-step-ahead errors
+    ///
+    /// # Arguments
+    /// * `series` - &[f64]
+    pub fn fit(series: &[f64]) -> Result<Holt, AnalyticsErr> {
+        let steps = (1.0 / GRID_STEP).round() as usize;
+        let grid: Vec<f64> = (1..steps).map(|s| s as f64 * GRID_STEP).collect();
+
+        let mut best: Option<Holt> = None;
+        for alpha in &grid {
+            for beta in &grid {
+                let model = Holt::fit_with(series, *alpha, *beta)?;
+                if best.is_none_or(|b| model.sigma < b.sigma) {
+                    best = Some(model);
+                }
+            }
+        }
+
+        best.ok_or(AnalyticsErr::NotEnoughData(MIN_VALUES))
+    }
+
+    /// Forecast the days following the last value of the series. When the series can't
+    /// be negative (e.g. an occupancy) the lower bound of the interval is clamped to 0
+    ///
+    /// # Arguments
+    /// * `&self` - Self
+    /// * `horizon` - usize
+    /// * `non_negative` - bool
+    pub fn forecast(&self, horizon: usize, non_negative: bool) -> Vec<Forecast> {
+        let mut variance = 0.0;
+        (1..=horizon)
+            .map(|h| {
+                // sum of the squared coefficients of the errors up to the day
+                variance += if h == 1 { 1.0 } else { (self.alpha * (1.0 + (h - 1) as f64 * self.beta)).powi(2) };
+
+                let value = self.level + h as f64 * self.trend;
+                let margin = INTERVAL_ZSCORE * self.sigma * variance.sqrt();
+                let (value, lower) = match non_negative {
+                    true => (value.max(0.0), (value - margin).max(0.0)),
+                    false => (value, value - margin)
+                };
+
+                Forecast {
+                    value,
+                    lower,
+                    upper: value + margin
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Occupancy of a department with a growing trend & some noise
+    const NOISY: [f64; 21] = [
+        410.0, 418.0, 431.0, 429.0, 445.0, 452.0, 449.0, 466.0, 478.0, 475.0, 491.0,
+        502.0, 498.0, 515.0, 527.0, 524.0, 540.0, 551.0, 548.0, 566.0, 574.0
+    ];
+
+    #[test]
+    fn expect_linear_series_to_be_extended() {
+        let series: Vec<f64> = (0..14).map(|d| 100.0 + 5.0 * d as f64).collect();
+        let model = Holt::fit(&series).unwrap();
+        let forecast = model.forecast(7, true);
+
+        assert_eq!(forecast.len(), 7);
+        assert!((forecast[0].value - 170.0).abs() < 1e-9);
+        assert!((forecast[6].value - 200.0).abs() < 1e-9);
+        assert!((forecast[6].upper - forecast[6].lower).abs() < 1e-9);
+    }
+
+    #[test]
+    fn expect_constant_series_to_stay_flat() {
+        let model = Holt::fit(&[80.0; 10]).unwrap();
+
+        assert!(model.forecast(14, true).iter().all(|f| (f.value - 80.0).abs() < 1e-9));
+    }
+
+    #[test]
+    fn expect_interval_to_widen_with_horizon() {
+        let model = Holt::fit(&NOISY).unwrap();
+        let forecast = model.forecast(14, true);
+
+        assert!(model.trend > 0.0);
+        assert!(forecast[0].value > 574.0 - 3.0 * model.sigma);
+        assert!(forecast.windows(2).all(|w| w[1].upper - w[1].lower > w[0].upper - w[0].lower));
+        assert!(forecast.iter().all(|f| f.lower <= f.value && f.value <= f.upper));
+    }
+
+    #[test]
+    fn expect_lower_bound_to_be_clamped() {
+        let series: Vec<f64> = (0..10).map(|d| 30.0 - 4.0 * d as f64 + (d % 2) as f64 * 3.0).collect();
+        let model = Holt::fit(&series).unwrap();
+
+        assert!(model.forecast(14, true).iter().all(|f| f.lower >= 0.0 && f.value >= 0.0));
+    }
+
+    #[test]
+    fn expect_invalid_input_to_return_error() {
+        assert!(Holt::fit(&[1.0, 2.0, 3.0]).is_err());
+        assert!(Holt::fit_with(&NOISY, 1.5, 0.2).is_err());
+    }
+}
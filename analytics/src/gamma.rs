@@ -0,0 +1,219 @@
+// Numerical helpers for the gamma distribution. The implementations follow
+// the algorithms described in Numerical Recipes (Lanczos approximation, series
+// and continued fraction of the incomplete gamma function). The series and the
+// continued fraction need about sqrt(a) iterations, so P(a, x) is approximated with
+// the Wilson-Hilferty transformation for the large shapes of the national incidences
+
+const LANCZOS: [f64; 6] = [
+    76.180_091_729_471_46,
+    -86.505_320_329_416_77,
+    24.014_098_240_830_91,
+    -1.231_739_572_450_155,
+    0.120_865_097_386_617_9e-2,
+    -0.539_523_938_495_3e-5
+];
+const MAX_ITERATIONS: usize = 500;
+const EPSILON: f64 = 1e-12;
+// Shape above which the Wilson-Hilferty approximation is used
+const LARGE_SHAPE: f64 = 1e3;
+
+use crate::err::AnalyticsErr;
+
+/// Return the natural logarithm of the gamma function
+///
+/// # Arguments
+/// * `x` - f64
+pub fn ln_gamma(x: f64) -> f64 {
+    let tmp = x + 5.5;
+    let tmp = tmp - (x + 0.5) * tmp.ln();
+    let series = LANCZOS
+        .iter()
+        .enumerate()
+        .fold(1.000_000_000_190_015, |acc, (i, c)| acc + c / (x + 1.0 + i as f64));
+
+    -tmp + (2.506_628_274_631_000_5 * series / x).ln()
+}
+
+/// Return the regularized lower incomplete gamma function P(a, x)
+///
+/// # Arguments
+/// * `a` - f64
+/// * `x` - f64
+pub fn regularized_lower(a: f64, x: f64) -> Result<f64, AnalyticsErr> {
+    if x <= 0.0 {
+        return Ok(0.0);
+    }
+
+    if a >= LARGE_SHAPE {
+        wilson_hilferty(a, x)
+    } else if x < a + 1.0 {
+        lower_series(a, x)
+    } else {
+        upper_continued_fraction(a, x).map(|q| 1.0 - q)
+    }
+}
+
+/// Cumulative distribution function of a gamma distribution
+///
+/// # Arguments
+/// * `shape` - f64
+/// * `scale` - f64
+/// * `x` - f64
+pub fn cdf(shape: f64, scale: f64, x: f64) -> Result<f64, AnalyticsErr> {
+    regularized_lower(shape, x / scale)
+}
+
+/// Quantile function of a gamma distribution. The quantile is found by bisection
+///
+/// # Arguments
+/// * `shape` - f64
+/// * `scale` - f64
+/// * `p` - f64
+pub fn quantile(shape: f64, scale: f64, p: f64) -> Result<f64, AnalyticsErr> {
+    let mut low = 0.0;
+    let mut high = (shape * scale).max(1.0);
+    while cdf(shape, scale, high)? < p {
+        high *= 2.0;
+    }
+
+    for _ in 0..MAX_ITERATIONS {
+        let mid = (low + high) / 2.0;
+        if cdf(shape, scale, mid)? < p {
+            low = mid;
+        } else {
+            high = mid;
+        }
+
+        if high - low < EPSILON * high.max(1.0) {
+            break;
+        }
+    }
+
+    Ok((low + high) / 2.0)
+}
+
+/// Wilson-Hilferty approximation of P(a, x). (x / a)^(1/3) is close to a normal distribution
+/// of mean 1 - 1/(9a) and variance 1/(9a) when a is large
+///
+/// # Arguments
+/// * `a` - f64
+/// * `x` - f64
+fn wilson_hilferty(a: f64, x: f64) -> Result<f64, AnalyticsErr> {
+    let variance = 1.0 / (9.0 * a);
+    let z = ((x / a).cbrt() - (1.0 - variance)) / variance.sqrt();
+
+    // standard normal cdf, erf(z / sqrt(2)) = P(1/2, z^2 / 2)
+    let erf = regularized_lower(0.5, z * z / 2.0)?;
+    Ok(0.5 * (1.0 + erf.copysign(z)))
+}
+
+/// Series representation of P(a, x). Converge quickly for x < a + 1
+///
+/// # Arguments
+/// * `a` - f64
+/// * `x` - f64
+fn lower_series(a: f64, x: f64) -> Result<f64, AnalyticsErr> {
+    let mut ap = a;
+    let mut del = 1.0 / a;
+    let mut sum = del;
+    for _ in 0..MAX_ITERATIONS {
+        ap += 1.0;
+        del *= x / ap;
+        sum += del;
+        if del.abs() < sum.abs() * EPSILON {
+            return Ok(sum * (-x + a * x.ln() - ln_gamma(a)).exp());
+        }
+    }
+
+    Err(AnalyticsErr::NotConverged)
+}
+
+/// Continued fraction representation of Q(a, x) = 1 - P(a, x). Converge quickly for x >= a + 1
+///
+/// # Arguments
+/// * `a` - f64
+/// * `x` - f64
+fn upper_continued_fraction(a: f64, x: f64) -> Result<f64, AnalyticsErr> {
+    let tiny = f64::MIN_POSITIVE / EPSILON;
+    let mut b = x + 1.0 - a;
+    let mut c = 1.0 / tiny;
+    let mut d = 1.0 / b;
+    let mut h = d;
+    for i in 1..MAX_ITERATIONS {
+        let an = -(i as f64) * (i as f64 - a);
+        b += 2.0;
+        d = an * d + b;
+        if d.abs() < tiny {
+            d = tiny;
+        }
+        c = b + an / c;
+        if c.abs() < tiny {
+            c = tiny;
+        }
+        d = 1.0 / d;
+        let del = d * c;
+        h *= del;
+        if (del - 1.0).abs() < EPSILON {
+            return Ok((-x + a * x.ln() - ln_gamma(a)).exp() * h);
+        }
+    }
+
+    Err(AnalyticsErr::NotConverged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expect_ln_gamma_to_match_factorial() {
+        // gamma(5) = 4! = 24
+        assert!((ln_gamma(5.0) - 24_f64.ln()).abs() < 1e-10);
+        assert!((ln_gamma(0.5) - std::f64::consts::PI.sqrt().ln()).abs() < 1e-10);
+    }
+
+    #[test]
+    fn expect_cdf_of_exponential_distribution() {
+        // gamma with a shape of 1 is an exponential distribution
+        for x in [0.1, 1.0, 2.5, 10.0] {
+            assert!((cdf(1.0, 2.0, x).unwrap() - (1.0 - (-x / 2.0_f64).exp())).abs() < 1e-10);
+        }
+    }
+
+    #[test]
+    fn expect_quantile_to_invert_cdf() {
+        for (shape, scale) in [(2.0, 1.5), (50.0, 0.02), (0.8, 3.0)] {
+            for p in [0.025, 0.5, 0.975] {
+                let q = quantile(shape, scale, p).unwrap();
+                assert!((cdf(shape, scale, q).unwrap() - p).abs() < 1e-8);
+            }
+        }
+    }
+
+    #[test]
+    fn expect_approximation_to_match_series_at_the_threshold() {
+        for x in [0.98, 0.99, 1.0, 1.01, 1.02] {
+            let series = lower_series(LARGE_SHAPE, x * LARGE_SHAPE).unwrap();
+            let approx = wilson_hilferty(LARGE_SHAPE, x * LARGE_SHAPE).unwrap();
+            assert!((series - approx).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn expect_quantile_of_large_count() {
+        // a week of ~70000 daily cases with a R of 1, close to a normal distribution
+        let shape: f64 = 5e5;
+        let scale = 1.0 / shape;
+        let sd = shape.sqrt() * scale;
+
+        let lower = quantile(shape, scale, 0.025).unwrap();
+        let upper = quantile(shape, scale, 0.975).unwrap();
+        assert!((lower - (1.0 - 1.959_964 * sd)).abs() < 1e-5);
+        assert!((upper - (1.0 + 1.959_964 * sd)).abs() < 1e-5);
+    }
+
+    #[test]
+    fn expect_series_to_not_silently_truncate() {
+        assert!(matches!(lower_series(1e6, 1e6 - 1.0), Err(AnalyticsErr::NotConverged)));
+    }
+}
@@ -0,0 +1,4 @@
+pub mod err;
+pub mod forecast;
+pub mod gamma;
+pub mod reproduction;
@@ -0,0 +1,227 @@
+// Estimation of the effective reproduction number (Rt) with the method of Cori et al. (2013)
+// "A New Framework and Software to Estimate Time-Varying Reproduction Numbers During Epidemics".
+//
+// The number of new cases of a day is modelled by a Poisson distribution of mean Rt * Λt where
+// Λt = Σ I(t - s) * w(s) is the infectivity of the previous cases weighted by the serial interval
+// distribution w. Assuming Rt is constant over a sliding window and a gamma prior, the posterior
+// of Rt is a gamma distribution of
+//     - shape = a + Σ I(k)
+//     - scale = 1 / (1 / b + Σ Λ(k))
+// for the days k of the window
+use super::gamma;
+use super::err::AnalyticsErr;
+
+// Serial interval of the covid (Nishiura et al., 2020)
+const DEFAULT_SERIAL_INTERVAL_MEAN: f64 = 4.7;
+const DEFAULT_SERIAL_INTERVAL_SD: f64 = 2.9;
+const DEFAULT_WINDOW: usize = 7;
+// Prior used by EpiEstim: gamma with a mean of 5 and a standard deviation of 5
+const PRIOR_SHAPE: f64 = 1.0;
+const PRIOR_SCALE: f64 = 5.0;
+// The serial interval distribution is truncated once it covers 99.9% of the distribution
+const SERIAL_INTERVAL_COVERAGE: f64 = 0.999;
+const SERIAL_INTERVAL_MAX_DAYS: usize = 60;
+// Bounds of the 95% credible interval
+const LOWER_QUANTILE: f64 = 0.025;
+const UPPER_QUANTILE: f64 = 0.975;
+
+/// Serial interval distribution modelled by a gamma distribution
+#[derive(Debug, Clone, Copy)]
+pub struct SerialInterval {
+    pub mean: f64,
+    pub sd: f64
+}
+
+impl Default for SerialInterval {
+    fn default() -> Self {
+        SerialInterval {
+            mean: DEFAULT_SERIAL_INTERVAL_MEAN,
+            sd: DEFAULT_SERIAL_INTERVAL_SD
+        }
+    }
+}
+
+impl SerialInterval {
+    /// Discretize the serial interval distribution by day. The first item is the
+    /// probability of a serial interval of 0 day which is always 0
+    ///
+    /// # Arguments
+    /// * `&self` - Self
+    pub fn discretize(&self) -> Result<Vec<f64>, AnalyticsErr> {
+        if !(self.mean > 0.0 && self.sd > 0.0) {
+            return Err(AnalyticsErr::InvalidSerialInterval);
+        }
+
+        let shape = (self.mean / self.sd).powi(2);
+        let scale = self.sd.powi(2) / self.mean;
+
+        let mut weights = vec![0.0];
+        for day in 1..=SERIAL_INTERVAL_MAX_DAYS {
+            let upper = gamma::cdf(shape, scale, day as f64)?;
+            weights.push(upper - gamma::cdf(shape, scale, (day - 1) as f64)?);
+
+            if upper >= SERIAL_INTERVAL_COVERAGE {
+                break;
+            }
+        }
+
+        let total: f64 = weights.iter().sum();
+        Ok(weights.into_iter().map(|w| w / total).collect())
+    }
+}
+
+/// Configuration of the estimation
+#[derive(Debug, Clone, Copy)]
+pub struct Config {
+    pub serial_interval: SerialInterval,
+    // number of days over which Rt is assumed to be constant
+    pub window: usize
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            serial_interval: SerialInterval::default(),
+            window: DEFAULT_WINDOW
+        }
+    }
+}
+
+/// Estimation of Rt for a day with its 95% credible interval
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Estimate {
+    pub mean: f64,
+    pub lower: f64,
+    pub upper: f64
+}
+
+/// Estimate Rt for each day of a daily incidence series. The estimate of a day uses
+/// the window ending on this day. None is returned for the days where the window is
+/// incomplete or where there isn't any previous case
+///
+/// # Arguments
+/// * `incidence` - &[f64]
+/// * `config` - &Config
+pub fn estimate(incidence: &[f64], config: &Config) -> Result<Vec<Option<Estimate>>, AnalyticsErr> {
+    if config.window == 0 {
+        return Err(AnalyticsErr::InvalidWindow);
+    }
+
+    if incidence.iter().any(|i| *i < 0.0) {
+        return Err(AnalyticsErr::NegativeIncidence);
+    }
+
+    let weights = config.serial_interval.discretize()?;
+    let infectivity: Vec<f64> = (0..incidence.len())
+        .map(|t| {
+            (1..weights.len().min(t + 1))
+                .map(|s| incidence[t - s] * weights[s])
+                .sum()
+        })
+        .collect();
+
+    let estimates = (0..incidence.len())
+        .map(|t| {
+            // the first day does not have any infectivity
+            if t < config.window {
+                return Ok(None);
+            }
+
+            let start = t + 1 - config.window;
+            let cases: f64 = incidence[start..=t].iter().sum();
+            let lambda: f64 = infectivity[start..=t].iter().sum();
+            if lambda <= 0.0 {
+                return Ok(None);
+            }
+
+            let shape = PRIOR_SHAPE + cases;
+            let scale = 1.0 / (1.0 / PRIOR_SCALE + lambda);
+
+            Ok(Some(Estimate {
+                mean: shape * scale,
+                lower: gamma::quantile(shape, scale, LOWER_QUANTILE)?,
+                upper: gamma::quantile(shape, scale, UPPER_QUANTILE)?
+            }))
+        })
+        .collect();
+
+    estimates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Generate a synthetic series with the renewal equation I(t) = R(t) * Λ(t)
+    fn simulate(r: impl Fn(usize) -> f64, days: usize) -> Vec<f64> {
+        let weights = SerialInterval::default().discretize().unwrap();
+        let mut incidence = vec![100.0];
+        for t in 1..days {
+            let lambda: f64 = (1..weights.len().min(t + 1))
+                .map(|s| incidence[t - s] * weights[s])
+                .sum();
+            incidence.push(r(t) * lambda);
+        }
+
+        incidence
+    }
+
+    #[test]
+    fn expect_serial_interval_to_be_a_distribution() {
+        let weights = SerialInterval::default().discretize().unwrap();
+
+        assert_eq!(weights[0], 0.0);
+        assert!((weights.iter().sum::<f64>() - 1.0).abs() < 1e-12);
+        assert!(SerialInterval { mean: 0.0, sd: 1.0 }.discretize().is_err());
+    }
+
+    #[test]
+    fn expect_to_estimate_constant_r() {
+        let incidence = simulate(|_| 1.5, 60);
+        let estimates = estimate(&incidence, &Config::default()).unwrap();
+
+        assert!(estimates[..7].iter().all(|e| e.is_none()));
+        let last = estimates.last().unwrap().unwrap();
+        assert!((last.mean - 1.5).abs() < 0.05);
+        assert!(last.lower < last.mean && last.mean < last.upper);
+    }
+
+    #[test]
+    fn expect_to_follow_change_of_r() {
+        let incidence = simulate(|t| if t < 40 { 2.0 } else { 0.8 }, 80);
+        let estimates = estimate(&incidence, &Config::default()).unwrap();
+
+        assert!((estimates[35].unwrap().mean - 2.0).abs() < 0.1);
+        assert!((estimates[79].unwrap().mean - 0.8).abs() < 0.05);
+    }
+
+    #[test]
+    fn expect_wider_interval_with_fewer_cases() {
+        let large = simulate(|_| 1.2, 40);
+        let small: Vec<f64> = large.iter().map(|i| i / 50.0).collect();
+
+        let large = estimate(&large, &Config::default()).unwrap()[39].unwrap();
+        let small = estimate(&small, &Config::default()).unwrap()[39].unwrap();
+
+        assert!(small.upper - small.lower > large.upper - large.lower);
+    }
+
+    #[test]
+    fn expect_interval_of_national_incidence() {
+        // ~100000 daily cases, the 95% interval is about 2 * 1.96 * R / sqrt(cases of the window)
+        let incidence = vec![100_000.0; 60];
+        let last = estimate(&incidence, &Config::default()).unwrap()[59].unwrap();
+        let cases = 100_000.0 * Config::default().window as f64;
+
+        assert!((last.mean - 1.0).abs() < 1e-3);
+        assert!(((last.upper - last.lower) - 2.0 * 1.959_964 * last.mean / cases.sqrt()).abs() < 1e-5);
+    }
+
+    #[test]
+    fn expect_invalid_input_to_return_error() {
+        let config = Config { window: 0, ..Config::default() };
+
+        assert!(estimate(&[1.0, 2.0], &config).is_err());
+        assert!(estimate(&[1.0, -2.0], &Config::default()).is_err());
+    }
+}
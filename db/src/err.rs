@@ -4,6 +4,7 @@ pub enum DBError {
     MissingEnv(String),
     IO(String),
     Connection(String),
+    Unavailable(String),
     Exec,
 }
 
@@ -15,6 +16,7 @@ impl std::fmt::Display for DBError {
             DBError::MissingEnv(msg) => write!(f, "Unable to build database uri. Error: {}", msg),
             DBError::IO(msg) => write!(f, "Unable to perform IO operation, {}", msg),
             DBError::Connection(msg) => write!(f, "Unable to connect to the database, reason: {}", msg),
+            DBError::Unavailable(msg) => write!(f, "The database is unavailable, reason: {}", msg),
             DBError::Exec => write!(f, "Error while parsing result")
         }
     }
@@ -32,9 +34,33 @@ impl From<std::io::Error> for DBError {
     }
 }
 
+/// Check whether an error is caused by the database being unreachable (e.g. the connection
+/// is lost or the pool is exhausted). These errors are transient and the query can be retried
+///
+/// # Arguments
+/// * `err` - &sqlx::Error
+pub fn is_unavailable(err: &sqlx::Error) -> bool {
+    match err {
+        sqlx::Error::Io(_)
+        | sqlx::Error::Tls(_)
+        | sqlx::Error::PoolTimedOut
+        | sqlx::Error::PoolClosed
+        | sqlx::Error::WorkerCrashed => true,
+        // connection exception (08) & operator intervention (57P) classes of postgres
+        sqlx::Error::Database(err) => err
+            .code()
+            .map(|c| c.starts_with("08") || c.starts_with("57P"))
+            .unwrap_or(false),
+        _ => false
+    }
+}
+
 impl From<sqlx::Error> for DBError {
     fn from(err: sqlx::Error) -> Self {
-        DBError::Connection(err.to_string())
+        match is_unavailable(&err) {
+            true => DBError::Unavailable(err.to_string()),
+            false => DBError::Connection(err.to_string())
+        }
     }
 }
 
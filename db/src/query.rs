@@ -1,5 +1,5 @@
-use futures::TryStreamExt;
-use sqlx::postgres::PgRow;
+use futures::{Stream, TryStreamExt, stream::TryChunksError};
+use sqlx::{Postgres, Row, postgres::{PgArguments, PgRow}, query::Query};
 use super::err::DBError;
 
 /// Generic helper method which helps to query the database
@@ -29,7 +29,7 @@ where
         .bind(other)
         .fetch(pool);
 
-    while let Some(row) = stream.try_next().await.unwrap() {
+    while let Some(row) = stream.try_next().await? {
         let value = T::try_from(row)
             .map_err(|_| DBError::Exec)?;
 
@@ -59,7 +59,7 @@ where
         .bind(date)
         .fetch(pool);
 
-    while let Some(row) = stream.try_next().await.unwrap() {
+    while let Some(row) = stream.try_next().await? {
         let value = T::try_from(row)
             .map_err(|_| DBError::Exec)?;
 
@@ -68,3 +68,414 @@ where
 
     Ok(vec)
 }
+
+/// Stream the rows of a query by chunks of rows. The rows are fetched as the chunks
+/// are consumed. Hence the whole result is never loaded in memory
+///
+/// # Arguments
+/// * `pool` - &'q PGPool
+/// * `query` - Query<'q, Postgres, PgArguments>
+/// * `size` - usize
+pub fn stream_chunks<'q>(
+    pool: &'q super::PGPool,
+    query: Query<'q, Postgres, PgArguments>,
+    size: usize
+) -> impl Stream<Item = Result<Vec<PgRow>, DBError>> + 'q {
+    query
+        .fetch(pool)
+        .try_chunks(size)
+        .map_err(|TryChunksError(_, err)| DBError::from(err))
+}
+
+/// Return the value of a column of a row or the default value when the column has not been
+/// selected (e.g. a field which is not requested by a field mask)
+///
+/// # Arguments
+/// * `row` - &'r PgRow
+/// * `column` - &str
+pub fn get_or_default<'r, T>(row: &'r PgRow, column: &str) -> Result<T, sqlx::Error>
+where
+    T: Default + sqlx::Decode<'r, Postgres> + sqlx::Type<Postgres>
+{
+    match row.try_get(column) {
+        Err(sqlx::Error::ColumnNotFound(_)) => Ok(T::default()),
+        res => res
+    }
+}
+
+/// Change of a metric compared to the previous day and to the same day the previous week
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Trend {
+    pub day_change: Option<f64>,
+    pub day_change_rate: Option<f64>,
+    pub week_change: Option<f64>,
+    pub week_change_rate: Option<f64>
+}
+
+/// Build a query which select the rows of a table along with the change of a metric compared
+/// to the previous day and to the same day the previous week. The rows of the previous days are
+/// joined by calendar day, hence the change is unknown when the previous day is missing. Only the
+/// rows of the requested period & of the 7 days before are read.
+/// The query expects the following parameters
+///     - $1 the date used with a LIKE
+///     - other parameters used by the filter
+///
+/// # Arguments
+/// * `table` - &str
+/// * `metric` - &str
+/// * `partition` - &str
+/// * `filter` - &str
+pub fn build_trend_query(table: &str, metric: &str, partition: &str, filter: &str) -> String {
+    let cur = format!("SELECT * FROM {table} WHERE jour LIKE $1 AND {filter}");
+
+    trend_query(table, metric, partition, filter, &cur)
+}
+
+/// Build a query which return a page of the rows of a table along with their trend (see
+/// build_trend_query & build_page_query). The page is selected before the trend is computed,
+/// hence only the days of the page & the 7 days before are read.
+/// The query expects the parameters of build_trend_query followed by the ones of build_page_query
+///
+/// # Arguments
+/// * `table` - &str
+/// * `metric` - &str
+/// * `partition` - &str
+/// * `filter` - &str
+/// * `keys` - &str
+/// * `first` - usize
+pub fn build_trend_page_query(table: &str, metric: &str, partition: &str, filter: &str, keys: &str, first: usize) -> String {
+    let cur = build_page_query(&format!("SELECT * FROM {table} WHERE jour LIKE $1 AND {filter}"), keys, first);
+
+    build_page_query(&trend_query(table, metric, partition, filter, &cur), keys, first)
+}
+
+/// Build the trend query of the rows returned by the `cur` query
+///
+/// # Arguments
+/// * `table` - &str
+/// * `metric` - &str
+/// * `partition` - &str
+/// * `filter` - &str
+/// * `cur` - &str
+fn trend_query(table: &str, metric: &str, partition: &str, filter: &str, cur: &str) -> String {
+    let columns: Vec<&str> = partition.split(',').map(str::trim).collect();
+    let join = |alias: &str| columns
+        .iter()
+        .map(|c| format!("{alias}.{c} = cur.{c}"))
+        .collect::<Vec<_>>()
+        .join(" AND ");
+    let order = columns
+        .iter()
+        .map(|c| format!("cur.{c}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let (day, week) = (join("d"), join("w"));
+
+    format!(
+        "WITH cur AS (
+            {cur}
+        ), prev AS (
+            SELECT {partition}, jour, {metric} FROM {table}
+            WHERE {filter}
+                AND jour >= (SELECT to_char(MIN(jour)::date - 7, 'YYYY-MM-DD') FROM cur)
+                AND jour < (SELECT MAX(jour) FROM cur)
+        )
+        SELECT cur.*,
+            (cur.{metric} - d.{metric})::float8 AS day_change,
+            (cur.{metric} - d.{metric})::float8 / NULLIF(d.{metric}, 0) AS day_change_rate,
+            (cur.{metric} - w.{metric})::float8 AS week_change,
+            (cur.{metric} - w.{metric})::float8 / NULLIF(w.{metric}, 0) AS week_change_rate
+        FROM cur
+        LEFT JOIN prev d ON d.jour = to_char(cur.jour::date - 1, 'YYYY-MM-DD') AND {day}
+        LEFT JOIN prev w ON w.jour = to_char(cur.jour::date - 7, 'YYYY-MM-DD') AND {week}
+        ORDER BY {order}, cur.jour"
+    )
+}
+
+/// Return the trend of a row. The trend is only available when the query has been built
+/// with the trend columns (see build_trend_query)
+///
+/// # Arguments
+/// * `value` - &PgRow
+pub fn get_trend(value: &PgRow) -> Option<Trend> {
+    let day_change: Option<f64> = value.try_get("day_change").ok()?;
+
+    Some(Trend {
+        day_change,
+        day_change_rate: value.try_get("day_change_rate").ok().flatten(),
+        week_change: value.try_get("week_change").ok().flatten(),
+        week_change_rate: value.try_get("week_change_rate").ok().flatten()
+    })
+}
+
+/// Wrap a query in order to return a page of its rows. The rows are ordered by the keys which are
+/// the day, the area (as text) & the age of a row. Hence the pages stay stable when rows of new days
+/// are imported. The query expects the following parameters after the ones of the wrapped query
+///     - $first, $first + 1 & $first + 2 the day, area & age of the last row of the previous page.
+///       The first page is returned when they are NULL
+///     - $first + 3 the maximum number of rows to return
+///
+/// # Arguments
+/// * `query` - &str
+/// * `keys` - &str
+/// * `first` - usize
+pub fn build_page_query(query: &str, keys: &str, first: usize) -> String {
+    let (day, area, age, limit) = (first, first + 1, first + 2, first + 3);
+
+    format!(
+        "SELECT * FROM ({query}) page
+        WHERE ${day}::text IS NULL OR ({keys}) > (${day}::text, ${area}::text, ${age}::bigint)
+        ORDER BY {keys} LIMIT ${limit}"
+    )
+}
+
+/// Build a query which rank the departments of a table by a value aggregated over a period
+/// (e.g. AVG(hosp)). The query expects the following parameters
+///     - $1 the days of the period
+///     - $2 the days of the previous period which is used to compute the change
+///     - $3 the maximum number of departments to return. All departments are returned if NULL
+///
+/// # Arguments
+/// * `table` - &str
+/// * `value` - &str
+/// * `filter` - Option<&str>
+/// * `ascending` - bool
+pub fn build_ranking_query(table: &str, value: &str, filter: Option<&str>, ascending: bool) -> String {
+    let filter = filter.map(|f| format!("AND {f}")).unwrap_or_default();
+    let order = if ascending { "ASC" } else { "DESC" };
+
+    format!(
+        "WITH cur AS (
+            SELECT dep, ({value})::float8 AS value FROM {table} WHERE jour = ANY($1) {filter} GROUP BY dep
+        ), prev AS (
+            SELECT dep, ({value})::float8 AS value FROM {table} WHERE jour = ANY($2) {filter} GROUP BY dep
+        )
+        SELECT cur.dep, cur.value, cur.value - prev.value AS week_change,
+            RANK() OVER (ORDER BY cur.value {order}) AS rank
+        FROM cur LEFT JOIN prev ON prev.dep = cur.dep
+        ORDER BY rank, cur.dep
+        LIMIT $3"
+    )
+}
+
+/// Generic helper method which helps to query a ranking built with the
+/// `build_ranking_query` method
+///
+/// # Arguments
+/// * `pool` - &PGPool
+/// * `query` - &str
+/// * `days` - Vec<String>
+/// * `previous_days` - Vec<String>
+/// * `limit` - Option<i64>
+pub async fn get_ranking<T>(
+    pool: &super::PGPool,
+    query: &str,
+    days: Vec<String>,
+    previous_days: Vec<String>,
+    limit: Option<i64>
+) -> Result<Vec<T>, DBError>
+where
+    T: TryFrom<PgRow>
+{
+    let mut vec = Vec::new();
+    let mut stream = sqlx::query(query)
+        .bind(days)
+        .bind(previous_days)
+        .bind(limit)
+        .fetch(pool);
+
+    while let Some(row) = stream.try_next().await? {
+        let value = T::try_from(row)
+            .map_err(|_| DBError::Exec)?;
+
+        vec.push(value);
+    }
+
+    Ok(vec)
+}
+
+/// Generic helper method which helps to query all the data between two dates
+/// and a third parameter. Parameters are bind to the query in order ($1, $2, $3)
+/// 
+/// # Arguments
+/// * `pool` - &PGPool
+/// * `query` - &'q str
+/// * `from` - &'q str
+/// * `to` - &'q str
+/// * `other` - I
+pub async fn get_all_by_date_range_and_gen_field<'q, T, I>(
+    pool: &super::PGPool,
+    query: &'q str,
+    from: &'q str,
+    to: &'q str,
+    other: I
+) -> Result<Vec<T>, DBError>
+where
+    T: TryFrom<PgRow>,
+    I: 'q + sqlx::Encode<'q, sqlx::Postgres> + sqlx::Type<sqlx::Postgres> + std::marker::Send
+{
+    let mut vec = Vec::new();
+    let mut stream = sqlx::query(query)
+        .bind(from)
+        .bind(to)
+        .bind(other)
+        .fetch(pool);
+
+    while let Some(row) = stream.try_next().await? {
+        let value = T::try_from(row)
+            .map_err(|_| DBError::Exec)?;
+
+        vec.push(value);
+    }
+
+    Ok(vec)
+}
+
+/// Generic helper method which helps to query all the data between
+/// two dates. Both dates are bind to the query in order ($1, $2)
+/// 
+/// # Arguments
+/// * `pool` - &PGPool
+/// * `query` - &str
+/// * `from` - &str
+/// * `to` - &str
+pub async fn get_all_by_date_range<T>(
+    pool: &super::PGPool,
+    query: &str,
+    from: &str,
+    to: &str
+) -> Result<Vec<T>, DBError>
+where
+    T: TryFrom<PgRow>
+{
+    let mut vec = Vec::new();
+    let mut stream = sqlx::query(query)
+        .bind(from)
+        .bind(to)
+        .fetch(pool);
+
+    while let Some(row) = stream.try_next().await? {
+        let value = T::try_from(row)
+            .map_err(|_| DBError::Exec)?;
+
+        vec.push(value);
+    }
+
+    Ok(vec)
+}
+
+/// Return a page of the rows of a query built by `build_page_query`. The parameters of the
+/// wrapped query are expected to be bound already
+///
+/// # Arguments
+/// * `pool` - &PGPool
+/// * `query` - Query<'q, Postgres, PgArguments>
+/// * `after` - Option<(&'q str, &'q str, i64)>
+/// * `limit` - i64
+pub async fn get_page<'q, T>(
+    pool: &super::PGPool,
+    query: Query<'q, Postgres, PgArguments>,
+    after: Option<(&'q str, &'q str, i64)>,
+    limit: i64
+) -> Result<Vec<T>, DBError>
+where
+    T: TryFrom<PgRow>
+{
+    let mut vec = Vec::new();
+    let mut stream = query
+        .bind(after.map(|(day, _, _)| day))
+        .bind(after.map(|(_, area, _)| area))
+        .bind(after.map(|(_, _, age)| age))
+        .bind(limit)
+        .fetch(pool);
+
+    while let Some(row) = stream.try_next().await? {
+        let value = T::try_from(row)
+            .map_err(|_| DBError::Exec)?;
+
+        vec.push(value);
+    }
+
+    Ok(vec)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn expect_trend_to_be_computed_by_calendar_day() {
+        let pool = super::super::connect("../config.toml").await.unwrap();
+        let mut tx = pool.begin().await.unwrap();
+
+        sqlx::query("CREATE TEMP TABLE trend_test (dep text, jour text, hosp bigint) ON COMMIT DROP")
+            .execute(&mut tx)
+            .await
+            .unwrap();
+
+        // 2021-12-09 is missing
+        sqlx::query("INSERT INTO trend_test VALUES
+            ('75', '2021-12-01', 10), ('75', '2021-12-03', 20), ('75', '2021-12-08', 30), ('75', '2021-12-10', 40),
+            ('13', '2021-12-09', 5), ('13', '2021-12-10', 8)")
+            .execute(&mut tx)
+            .await
+            .unwrap();
+
+        let rows = sqlx::query(&build_trend_query("trend_test", "hosp", "dep", "dep = ANY($2)"))
+            .bind("2021-12-10")
+            .bind(vec!["75", "13"])
+            .fetch_all(&mut tx)
+            .await
+            .unwrap();
+
+        let trends: Vec<(String, Trend)> = rows
+            .iter()
+            .map(|r| (r.get("dep"), get_trend(r).unwrap()))
+            .collect();
+
+        assert_eq!(trends.len(), 2);
+        assert_eq!(trends[0].0, "13");
+        assert_eq!(trends[0].1.day_change, Some(3.0));
+        // the previous day is missing & the previous week is the 2021-12-03
+        assert_eq!(trends[1].1.day_change, None);
+        assert_eq!(trends[1].1.week_change, Some(20.0));
+        assert_eq!(trends[1].1.week_change_rate, Some(1.0));
+    }
+
+    #[tokio::test]
+    async fn expect_trend_of_a_page_to_use_the_days_of_the_page() {
+        let pool = super::super::connect("../config.toml").await.unwrap();
+        let mut tx = pool.begin().await.unwrap();
+
+        sqlx::query("CREATE TEMP TABLE trend_page_test (dep text, jour text, hosp bigint) ON COMMIT DROP")
+            .execute(&mut tx)
+            .await
+            .unwrap();
+
+        sqlx::query("INSERT INTO trend_page_test VALUES
+            ('75', '2021-12-01', 10), ('75', '2021-12-07', 15), ('75', '2021-12-08', 30),
+            ('13', '2021-12-07', 5), ('13', '2021-12-08', 8)")
+            .execute(&mut tx)
+            .await
+            .unwrap();
+
+        let sql = build_trend_page_query("trend_page_test", "hosp", "dep", "dep = ANY($2)", "jour, dep, 0::bigint", 3);
+        let rows = sqlx::query(&sql)
+            .bind("2021-12-%")
+            .bind(vec!["75", "13"])
+            .bind(Some("2021-12-08"))
+            .bind(Some("13"))
+            .bind(Some(0_i64))
+            .bind(1_i64)
+            .fetch_all(&mut tx)
+            .await
+            .unwrap();
+
+        // the page only contains the row after ('2021-12-08', '13') whose history is read
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].get::<String, _>("dep"), "75");
+        let trend = get_trend(&rows[0]).unwrap();
+        assert_eq!(trend.day_change, Some(15.0));
+        assert_eq!(trend.week_change, Some(20.0));
+    }
+}
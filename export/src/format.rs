@@ -0,0 +1,317 @@
+use std::str::FromStr;
+use std::sync::Arc;
+use arrow_array::{ArrayRef, Float64Array, Int64Array, RecordBatch, StringArray};
+use arrow_ipc::writer::StreamWriter;
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use parquet::arrow::ArrowWriter;
+use parquet::file::properties::WriterProperties;
+use registry::{ColumnType, Dataset, row::{DatasetRow, Value}};
+use utils::err::MaskErr;
+
+// Maximum number of rows of a row group of parquet. The rows of the row group
+// in progress are kept in memory by the writer
+const ROW_GROUP_SIZE: usize = 65_536;
+
+/// Format of an export
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Csv,
+    Arrow,
+    Parquet
+}
+
+impl Format {
+    /// Return the media type of the format
+    ///
+    /// # Arguments
+    /// * `&self` - Self
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Format::Csv => "text/csv",
+            Format::Arrow => "application/vnd.apache.arrow.stream",
+            Format::Parquet => "application/vnd.apache.parquet"
+        }
+    }
+
+    /// Return the extension of the files of the format
+    ///
+    /// # Arguments
+    /// * `&self` - Self
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Csv => "csv",
+            Format::Arrow => "arrows",
+            Format::Parquet => "parquet"
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = MaskErr;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(Format::Csv),
+            "arrow" => Ok(Format::Arrow),
+            "parquet" => Ok(Format::Parquet),
+            _ => Err(MaskErr::InvalidParam(format!("the format {s} is unknown")))
+        }
+    }
+}
+
+enum Writer {
+    Csv(Vec<u8>),
+    Arrow(StreamWriter<Vec<u8>>),
+    Parquet(ArrowWriter<Vec<u8>>)
+}
+
+/// Encode the rows of a dataset by chunks. The bytes returned by each call are the
+/// continuation of the bytes of the previous calls
+pub struct Encoder {
+    schema: SchemaRef,
+    writer: Writer
+}
+
+/// Convert an error of arrow or parquet
+///
+/// # Arguments
+/// * `err` - E
+fn encode_err<E: std::fmt::Display>(err: E) -> MaskErr {
+    MaskErr::IO(format!("unable to encode the export: {err}"))
+}
+
+/// Build the arrow schema of a dataset. Every column is nullable
+///
+/// # Arguments
+/// * `dataset` - &Dataset
+fn build_schema(dataset: &Dataset) -> SchemaRef {
+    let fields = dataset.columns
+        .iter()
+        .map(|c| {
+            let kind = match c.kind {
+                ColumnType::Int => DataType::Int64,
+                ColumnType::Float => DataType::Float64,
+                ColumnType::String => DataType::Utf8
+            };
+
+            Field::new(&c.name, kind, true)
+        })
+        .collect::<Vec<_>>();
+
+    Arc::new(Schema::new(fields))
+}
+
+/// Build the record batch of the rows with a column per field of the schema
+///
+/// # Arguments
+/// * `schema` - &SchemaRef
+/// * `rows` - &[DatasetRow]
+fn build_batch(schema: &SchemaRef, rows: &[DatasetRow]) -> Result<RecordBatch, MaskErr> {
+    let columns = schema.fields()
+        .iter()
+        .enumerate()
+        .map(|(idx, field)| {
+            let values = rows.iter().map(|r| r.values.get(idx).and_then(Option::as_ref));
+            let array: ArrayRef = match field.data_type() {
+                DataType::Int64 => Arc::new(values
+                    .map(|v| match v { Some(Value::Int(v)) => Some(*v), _ => None })
+                    .collect::<Int64Array>()),
+                DataType::Float64 => Arc::new(values
+                    .map(|v| match v { Some(Value::Float(v)) => Some(*v), _ => None })
+                    .collect::<Float64Array>()),
+                _ => Arc::new(values
+                    .map(|v| match v { Some(Value::Text(v)) => Some(v.as_str()), _ => None })
+                    .collect::<StringArray>())
+            };
+
+            array
+        })
+        .collect::<Vec<_>>();
+
+    RecordBatch::try_new(Arc::clone(schema), columns).map_err(encode_err)
+}
+
+/// Escape a field of a CSV. The field is quoted when it contains a separator, a quote or a new line
+///
+/// # Arguments
+/// * `value` - &str
+fn escape_csv(value: &str) -> String {
+    match value.contains([',', '"', '\n', '\r']) {
+        true => format!("\"{}\"", value.replace('"', "\"\"")),
+        false => value.to_owned()
+    }
+}
+
+/// Write the rows to a CSV. The null values are empty fields
+///
+/// # Arguments
+/// * `buffer` - &mut Vec<u8>
+/// * `rows` - &[DatasetRow]
+fn write_csv(buffer: &mut Vec<u8>, rows: &[DatasetRow]) {
+    for row in rows {
+        let line = row.values
+            .iter()
+            .map(|v| match v {
+                Some(Value::Int(v)) => v.to_string(),
+                Some(Value::Float(v)) => v.to_string(),
+                Some(Value::Text(v)) => escape_csv(v),
+                None => String::new()
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+
+        buffer.extend_from_slice(line.as_bytes());
+        buffer.push(b'\n');
+    }
+}
+
+impl Encoder {
+    /// Create an encoder for the rows of a dataset. The header of the format (e.g. the
+    /// names of the columns of the CSV) is returned with the first chunk
+    ///
+    /// # Arguments
+    /// * `format` - Format
+    /// * `dataset` - &Dataset
+    pub fn new(format: Format, dataset: &Dataset) -> Result<Self, MaskErr> {
+        let schema = build_schema(dataset);
+        let writer = match format {
+            Format::Csv => {
+                let header = dataset.columns
+                    .iter()
+                    .map(|c| escape_csv(&c.name))
+                    .collect::<Vec<_>>()
+                    .join(",");
+
+                Writer::Csv(format!("{header}\n").into_bytes())
+            },
+            Format::Arrow => {
+                let writer = StreamWriter::try_new(Vec::new(), &schema).map_err(encode_err)?;
+                Writer::Arrow(writer)
+            },
+            Format::Parquet => {
+                let props = WriterProperties::builder()
+                    .set_max_row_group_size(ROW_GROUP_SIZE)
+                    .build();
+
+                let writer = ArrowWriter::try_new(Vec::new(), Arc::clone(&schema), Some(props)).map_err(encode_err)?;
+                Writer::Parquet(writer)
+            }
+        };
+
+        Ok(Encoder { schema, writer })
+    }
+
+    /// Encode the rows and return the bytes written so far. The bytes may be empty when
+    /// the writer keeps the rows in memory (e.g. the row group of parquet in progress)
+    ///
+    /// # Arguments
+    /// * `&mut self` - Self
+    /// * `rows` - &[DatasetRow]
+    pub fn encode(&mut self, rows: &[DatasetRow]) -> Result<Vec<u8>, MaskErr> {
+        let bytes = match &mut self.writer {
+            Writer::Csv(buffer) => {
+                write_csv(buffer, rows);
+                std::mem::take(buffer)
+            },
+            Writer::Arrow(writer) => {
+                writer.write(&build_batch(&self.schema, rows)?).map_err(encode_err)?;
+                std::mem::take(writer.get_mut())
+            },
+            Writer::Parquet(writer) => {
+                writer.write(&build_batch(&self.schema, rows)?).map_err(encode_err)?;
+                std::mem::take(writer.inner_mut())
+            }
+        };
+
+        Ok(bytes)
+    }
+
+    /// Return the last bytes of the export (e.g. the footer of parquet)
+    ///
+    /// # Arguments
+    /// * `self` - Self
+    pub fn finish(self) -> Result<Vec<u8>, MaskErr> {
+        match self.writer {
+            Writer::Csv(buffer) => Ok(buffer),
+            Writer::Arrow(writer) => writer.into_inner().map_err(encode_err),
+            Writer::Parquet(writer) => writer.into_inner().map_err(encode_err)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow_ipc::reader::StreamReader;
+    use bytes::Bytes;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    fn get_rows() -> Vec<DatasetRow> {
+        (0..10)
+            .map(|idx| DatasetRow {
+                values: vec![
+                    Some(Value::Int(11)),
+                    Some(Value::Int(0)),
+                    Some(Value::Text(format!("2021-10-{:02}", idx + 1))),
+                    (idx != 3).then_some(Value::Int(idx)),
+                    Some(Value::Int(idx * 2)),
+                    Some(Value::Float(1.5)),
+                    Some(Value::Float(0.0)),
+                    None,
+                    Some(Value::Int(0)),
+                    Some(Value::Int(0))
+                ]
+            })
+            .collect()
+    }
+
+    fn encode(format: Format) -> Vec<u8> {
+        let dataset = registry::get_dataset("hospitalization").unwrap();
+        let rows = get_rows();
+
+        let mut encoder = Encoder::new(format, dataset).unwrap();
+        let mut bytes = encoder.encode(&rows[..4]).unwrap();
+        bytes.extend(encoder.encode(&rows[4..]).unwrap());
+        bytes.extend(encoder.finish().unwrap());
+
+        bytes
+    }
+
+    #[test]
+    fn expect_to_parse_format() {
+        assert_eq!("parquet".parse::<Format>().unwrap(), Format::Parquet);
+        assert!("xlsx".parse::<Format>().is_err());
+    }
+
+    #[test]
+    fn expect_to_encode_csv() {
+        let csv = String::from_utf8(encode(Format::Csv)).unwrap();
+        let lines = csv.lines().collect::<Vec<_>>();
+
+        assert_eq!(lines.len(), 11);
+        assert!(lines[0].starts_with("reg,cl_age90,jour"));
+        assert_eq!(lines[4], "11,0,2021-10-04,,6,1.5,0,,0,0");
+        assert_eq!(escape_csv("a \"b\", c"), "\"a \"\"b\"\", c\"");
+    }
+
+    #[test]
+    fn expect_to_encode_arrow() {
+        let bytes = encode(Format::Arrow);
+        let reader = StreamReader::try_new(bytes.as_slice(), None).unwrap();
+        let batches = reader.collect::<Result<Vec<_>, _>>().unwrap();
+
+        assert_eq!(batches.len(), 2);
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 10);
+        assert_eq!(batches[0].column(3).null_count(), 1);
+    }
+
+    #[test]
+    fn expect_to_encode_parquet() {
+        let bytes = Bytes::from(encode(Format::Parquet));
+        let reader = ParquetRecordBatchReaderBuilder::try_new(bytes).unwrap().build().unwrap();
+        let batches = reader.collect::<Result<Vec<_>, _>>().unwrap();
+
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 10);
+        assert_eq!(batches[0].schema().field(2).name(), "jour");
+    }
+}
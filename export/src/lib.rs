@@ -0,0 +1,145 @@
+use std::sync::Arc;
+use db::PGPool;
+use futures::TryStreamExt;
+use registry::{Dataset, row};
+use tokio::sync::mpsc;
+use utils::err::MaskErr;
+
+#[macro_use]
+extern crate log;
+
+mod format;
+
+pub use format::{Encoder, Format};
+
+// Number of rows fetched & encoded at once
+const CHUNK_SIZE: usize = 1000;
+// Number of encoded chunks waiting for the client
+const CHANNEL_SIZE: usize = 4;
+
+/// Rows of a dataset to export. The filters are the ones of the dataset service
+pub struct Export {
+    pub dataset: &'static Dataset,
+    pub format: Format,
+    // day or month used with a LIKE
+    pub day: Option<String>,
+    pub areas: Option<Vec<String>>
+}
+
+/// Fetch the rows by chunks, encode them and send the bytes of each chunk to the sender.
+/// Stop when the receiver is dropped
+///
+/// # Arguments
+/// * `pool` - &PGPool
+/// * `export` - &Export
+/// * `sender` - &mpsc::Sender<Result<Vec<u8>, MaskErr>>
+async fn send_chunks(pool: &PGPool, export: &Export, sender: &mpsc::Sender<Result<Vec<u8>, MaskErr>>) -> Result<(), MaskErr> {
+    let dataset = export.dataset;
+    let (day, areas) = dataset.filters(export.day.as_deref(), export.areas.as_deref());
+    let query = dataset.build_query(day.is_some(), areas.is_some());
+
+    let mut encoder = Encoder::new(export.format, dataset)?;
+    let mut chunks = Box::pin(db::query::stream_chunks(pool, row::bind_filters(&query, day, areas), CHUNK_SIZE));
+    while let Some(rows) = chunks.try_next().await? {
+        let rows = rows
+            .iter()
+            .map(|r| dataset.decode(r))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let bytes = encoder.encode(&rows)?;
+        // the client closed the stream
+        if !bytes.is_empty() && sender.send(Ok(bytes)).await.is_err() {
+            return Ok(());
+        }
+    }
+
+    let _ = sender.send(Ok(encoder.finish()?)).await;
+
+    Ok(())
+}
+
+/// Stream the rows of a dataset encoded in the format of the export. The rows are fetched
+/// & encoded as the chunks are consumed by the receiver, hence the whole result is never
+/// loaded in memory. An error stops the stream
+///
+/// # Arguments
+/// * `pool` - Arc<PGPool>
+/// * `export` - Export
+pub fn stream(pool: Arc<PGPool>, export: Export) -> mpsc::Receiver<Result<Vec<u8>, MaskErr>> {
+    let (sender, receiver) = mpsc::channel(CHANNEL_SIZE);
+    tokio::spawn(async move {
+        if let Err(err) = send_chunks(&pool, &export, &sender).await {
+            error!("export of {} {:?}", export.dataset.name, err);
+            let _ = sender.send(Err(err)).await;
+        }
+    });
+
+    receiver
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow_ipc::reader::StreamReader;
+
+    async fn collect(export: Export) -> Vec<u8> {
+        let pool = db::connect("../config.toml").await.unwrap();
+        let mut receiver = stream(Arc::new(pool), export);
+
+        let mut bytes = Vec::new();
+        while let Some(chunk) = receiver.recv().await {
+            bytes.extend(chunk.unwrap());
+        }
+
+        bytes
+    }
+
+    #[tokio::test]
+    async fn expect_to_stream_csv_of_a_month() {
+        let export = Export {
+            dataset: registry::get_dataset("cases").unwrap(),
+            format: Format::Csv,
+            day: Some("2021-10%".to_owned()),
+            areas: Some(vec!["75".to_owned(), "13".to_owned()])
+        };
+
+        let csv = String::from_utf8(collect(export).await).unwrap();
+        let lines = csv.lines().collect::<Vec<_>>();
+
+        assert_eq!(lines[0], "dep,jour,incid_hosp,incid_rea,incid_dc,incid_rad");
+        assert_eq!(lines.len(), 63);
+        assert!(lines[1].starts_with("13,2021-10-01,"));
+    }
+
+    #[tokio::test]
+    async fn expect_to_stream_arrow_by_chunks() {
+        let export = Export {
+            dataset: registry::get_dataset("cases").unwrap(),
+            format: Format::Arrow,
+            day: None,
+            areas: None
+        };
+
+        let bytes = collect(export).await;
+        let reader = StreamReader::try_new(bytes.as_slice(), None).unwrap();
+        let batches = reader.collect::<Result<Vec<_>, _>>().unwrap();
+
+        // a batch per chunk of rows
+        assert!(batches.len() > 1);
+        assert!(batches.iter().map(|b| b.num_rows()).sum::<usize>() > CHUNK_SIZE);
+        assert!(batches.iter().all(|b| b.num_rows() <= CHUNK_SIZE));
+    }
+
+    #[tokio::test]
+    async fn expect_empty_export_to_have_header() {
+        let export = Export {
+            dataset: registry::get_dataset("cases").unwrap(),
+            format: Format::Csv,
+            day: Some("1990-01-01".to_owned()),
+            areas: None
+        };
+
+        let csv = String::from_utf8(collect(export).await).unwrap();
+        assert_eq!(csv.lines().count(), 1);
+    }
+}
@@ -0,0 +1,149 @@
+/// Department reference data
+#[derive(Debug)]
+pub struct Department {
+    pub code: &'static str,
+    pub name: &'static str,
+    pub region: i32,
+    pub population: i64
+}
+
+/// Region reference data. The population of a region is the sum of its departments
+#[derive(Debug)]
+pub struct Region {
+    pub code: i32,
+    pub name: &'static str
+}
+
+// INSEE codes of the regions. Overseas collectivities (975, 977, 978) use the region
+// code under which they're published by Santé publique France
+pub const REGIONS: &[Region] = &[
+    Region { code: 1, name: "Guadeloupe" },
+    Region { code: 2, name: "Martinique" },
+    Region { code: 3, name: "Guyane" },
+    Region { code: 4, name: "La Réunion" },
+    Region { code: 5, name: "Saint-Pierre-et-Miquelon" },
+    Region { code: 6, name: "Mayotte" },
+    Region { code: 7, name: "Saint-Barthélemy" },
+    Region { code: 8, name: "Saint-Martin" },
+    Region { code: 11, name: "Île-de-France" },
+    Region { code: 24, name: "Centre-Val de Loire" },
+    Region { code: 27, name: "Bourgogne-Franche-Comté" },
+    Region { code: 28, name: "Normandie" },
+    Region { code: 32, name: "Hauts-de-France" },
+    Region { code: 44, name: "Grand Est" },
+    Region { code: 52, name: "Pays de la Loire" },
+    Region { code: 53, name: "Bretagne" },
+    Region { code: 75, name: "Nouvelle-Aquitaine" },
+    Region { code: 76, name: "Occitanie" },
+    Region { code: 84, name: "Auvergne-Rhône-Alpes" },
+    Region { code: 93, name: "Provence-Alpes-Côte d'Azur" },
+    Region { code: 94, name: "Corse" },
+];
+
+// Population of each department (INSEE, population municipale 2019)
+pub const DEPARTMENTS: &[Department] = &[
+    Department { code: "01", name: "Ain", region: 84, population: 652432 },
+    Department { code: "02", name: "Aisne", region: 32, population: 531345 },
+    Department { code: "03", name: "Allier", region: 84, population: 335975 },
+    Department { code: "04", name: "Alpes-de-Haute-Provence", region: 93, population: 164308 },
+    Department { code: "05", name: "Hautes-Alpes", region: 93, population: 141220 },
+    Department { code: "06", name: "Alpes-Maritimes", region: 93, population: 1083310 },
+    Department { code: "07", name: "Ardèche", region: 84, population: 328278 },
+    Department { code: "08", name: "Ardennes", region: 44, population: 270582 },
+    Department { code: "09", name: "Ariège", region: 76, population: 153153 },
+    Department { code: "10", name: "Aube", region: 44, population: 310242 },
+    Department { code: "11", name: "Aude", region: 76, population: 374070 },
+    Department { code: "12", name: "Aveyron", region: 76, population: 279595 },
+    Department { code: "13", name: "Bouches-du-Rhône", region: 93, population: 2043110 },
+    Department { code: "14", name: "Calvados", region: 28, population: 694905 },
+    Department { code: "15", name: "Cantal", region: 84, population: 144692 },
+    Department { code: "16", name: "Charente", region: 75, population: 352015 },
+    Department { code: "17", name: "Charente-Maritime", region: 75, population: 651358 },
+    Department { code: "18", name: "Cher", region: 24, population: 302306 },
+    Department { code: "19", name: "Corrèze", region: 75, population: 240073 },
+    Department { code: "21", name: "Côte-d'Or", region: 27, population: 534124 },
+    Department { code: "22", name: "Côtes-d'Armor", region: 53, population: 600582 },
+    Department { code: "23", name: "Creuse", region: 75, population: 116617 },
+    Department { code: "24", name: "Dordogne", region: 75, population: 413606 },
+    Department { code: "25", name: "Doubs", region: 27, population: 543974 },
+    Department { code: "26", name: "Drôme", region: 84, population: 516762 },
+    Department { code: "27", name: "Eure", region: 28, population: 599507 },
+    Department { code: "28", name: "Eure-et-Loir", region: 24, population: 431575 },
+    Department { code: "29", name: "Finistère", region: 53, population: 915090 },
+    Department { code: "2A", name: "Corse-du-Sud", region: 94, population: 158507 },
+    Department { code: "2B", name: "Haute-Corse", region: 94, population: 181933 },
+    Department { code: "30", name: "Gard", region: 76, population: 748437 },
+    Department { code: "31", name: "Haute-Garonne", region: 76, population: 1400039 },
+    Department { code: "32", name: "Gers", region: 76, population: 191377 },
+    Department { code: "33", name: "Gironde", region: 75, population: 1623749 },
+    Department { code: "34", name: "Hérault", region: 76, population: 1175623 },
+    Department { code: "35", name: "Ille-et-Vilaine", region: 53, population: 1079498 },
+    Department { code: "36", name: "Indre", region: 24, population: 219316 },
+    Department { code: "37", name: "Indre-et-Loire", region: 24, population: 610079 },
+    Department { code: "38", name: "Isère", region: 84, population: 1271166 },
+    Department { code: "39", name: "Jura", region: 27, population: 259199 },
+    Department { code: "40", name: "Landes", region: 75, population: 413690 },
+    Department { code: "41", name: "Loir-et-Cher", region: 24, population: 329470 },
+    Department { code: "42", name: "Loire", region: 84, population: 765634 },
+    Department { code: "43", name: "Haute-Loire", region: 84, population: 227570 },
+    Department { code: "44", name: "Loire-Atlantique", region: 52, population: 1429272 },
+    Department { code: "45", name: "Loiret", region: 24, population: 680434 },
+    Department { code: "46", name: "Lot", region: 76, population: 174094 },
+    Department { code: "47", name: "Lot-et-Garonne", region: 75, population: 331271 },
+    Department { code: "48", name: "Lozère", region: 76, population: 76604 },
+    Department { code: "49", name: "Maine-et-Loire", region: 52, population: 818273 },
+    Department { code: "50", name: "Manche", region: 28, population: 495045 },
+    Department { code: "51", name: "Marne", region: 44, population: 566855 },
+    Department { code: "52", name: "Haute-Marne", region: 44, population: 172512 },
+    Department { code: "53", name: "Mayenne", region: 52, population: 307062 },
+    Department { code: "54", name: "Meurthe-et-Moselle", region: 44, population: 733760 },
+    Department { code: "55", name: "Meuse", region: 44, population: 184083 },
+    Department { code: "56", name: "Morbihan", region: 53, population: 759684 },
+    Department { code: "57", name: "Moselle", region: 44, population: 1043522 },
+    Department { code: "58", name: "Nièvre", region: 27, population: 204452 },
+    Department { code: "59", name: "Nord", region: 32, population: 2611293 },
+    Department { code: "60", name: "Oise", region: 32, population: 829419 },
+    Department { code: "61", name: "Orne", region: 28, population: 279942 },
+    Department { code: "62", name: "Pas-de-Calais", region: 32, population: 1465278 },
+    Department { code: "63", name: "Puy-de-Dôme", region: 84, population: 662152 },
+    Department { code: "64", name: "Pyrénées-Atlantiques", region: 75, population: 682621 },
+    Department { code: "65", name: "Hautes-Pyrénées", region: 76, population: 229567 },
+    Department { code: "66", name: "Pyrénées-Orientales", region: 76, population: 479979 },
+    Department { code: "67", name: "Bas-Rhin", region: 44, population: 1132607 },
+    Department { code: "68", name: "Haut-Rhin", region: 44, population: 767086 },
+    Department { code: "69", name: "Rhône", region: 84, population: 1875747 },
+    Department { code: "70", name: "Haute-Saône", region: 27, population: 235313 },
+    Department { code: "71", name: "Saône-et-Loire", region: 27, population: 551493 },
+    Department { code: "72", name: "Sarthe", region: 52, population: 566412 },
+    Department { code: "73", name: "Savoie", region: 84, population: 436434 },
+    Department { code: "74", name: "Haute-Savoie", region: 84, population: 826094 },
+    Department { code: "75", name: "Paris", region: 11, population: 2165423 },
+    Department { code: "76", name: "Seine-Maritime", region: 28, population: 1254378 },
+    Department { code: "77", name: "Seine-et-Marne", region: 11, population: 1421197 },
+    Department { code: "78", name: "Yvelines", region: 11, population: 1448207 },
+    Department { code: "79", name: "Deux-Sèvres", region: 75, population: 374878 },
+    Department { code: "80", name: "Somme", region: 32, population: 570559 },
+    Department { code: "81", name: "Tarn", region: 76, population: 389844 },
+    Department { code: "82", name: "Tarn-et-Garonne", region: 76, population: 260669 },
+    Department { code: "83", name: "Var", region: 93, population: 1076711 },
+    Department { code: "84", name: "Vaucluse", region: 93, population: 561469 },
+    Department { code: "85", name: "Vendée", region: 52, population: 685442 },
+    Department { code: "86", name: "Vienne", region: 75, population: 438435 },
+    Department { code: "87", name: "Haute-Vienne", region: 75, population: 372359 },
+    Department { code: "88", name: "Vosges", region: 44, population: 364499 },
+    Department { code: "89", name: "Yonne", region: 27, population: 335707 },
+    Department { code: "90", name: "Territoire de Belfort", region: 27, population: 141318 },
+    Department { code: "91", name: "Essonne", region: 11, population: 1301659 },
+    Department { code: "92", name: "Hauts-de-Seine", region: 11, population: 1624357 },
+    Department { code: "93", name: "Seine-Saint-Denis", region: 11, population: 1644903 },
+    Department { code: "94", name: "Val-de-Marne", region: 11, population: 1407124 },
+    Department { code: "95", name: "Val-d'Oise", region: 11, population: 1249674 },
+    Department { code: "971", name: "Guadeloupe", region: 1, population: 384239 },
+    Department { code: "972", name: "Martinique", region: 2, population: 364508 },
+    Department { code: "973", name: "Guyane", region: 3, population: 281678 },
+    Department { code: "974", name: "La Réunion", region: 4, population: 861210 },
+    Department { code: "975", name: "Saint-Pierre-et-Miquelon", region: 5, population: 5974 },
+    Department { code: "976", name: "Mayotte", region: 6, population: 279471 },
+    Department { code: "977", name: "Saint-Barthélemy", region: 7, population: 10124 },
+    Department { code: "978", name: "Saint-Martin", region: 8, population: 32489 },
+];
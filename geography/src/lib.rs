@@ -0,0 +1,299 @@
+use proto::geography_service_server::GeographyService;
+use tonic::{Request, Response, Status};
+use utils::err::MaskErr;
+
+pub mod data;
+
+pub mod proto {
+    tonic::include_proto!("geography");
+}
+
+pub use proto::geography_service_server::GeographyServiceServer;
+
+/// Return a department from its code (e.g. "75", "2A", "971")
+///
+/// # Arguments
+/// * `code` - &str
+pub fn get_department(code: &str) -> Option<&'static data::Department> {
+    data::DEPARTMENTS.iter().find(|d| d.code == code)
+}
+
+/// Return a region from its INSEE code
+///
+/// # Arguments
+/// * `code` - i32
+pub fn get_region(code: i32) -> Option<&'static data::Region> {
+    data::REGIONS.iter().find(|r| r.code == code)
+}
+
+/// Return the list of departments of a region
+///
+/// # Arguments
+/// * `region` - i32
+pub fn get_departments_by_region(region: i32) -> Vec<&'static data::Department> {
+    data::DEPARTMENTS
+        .iter()
+        .filter(|d| d.region == region)
+        .collect()
+}
+
+/// Return the codes of the departments of a region or of the whole country when
+/// no region is given. Return a MaskErr if the region does not exist
+///
+/// # Arguments
+/// * `region` - Option<i32>
+pub fn get_department_codes(region: Option<i32>) -> Result<Vec<String>, MaskErr> {
+    let departments = match region {
+        Some(code) => {
+            validate_region(code)?;
+            get_departments_by_region(code)
+        },
+        None => data::DEPARTMENTS.iter().collect()
+    };
+
+    Ok(departments.into_iter().map(|d| d.code.to_owned()).collect())
+}
+
+/// Return the list of departments to query from the department inputs of an RPC.
+/// The second value is true when multiple departments have been asked with
+/// either the list of departments or the flag to query all the departments
+///
+/// # Arguments
+/// * `department` - &str
+/// * `departments` - &[String]
+/// * `all` - bool
+pub fn resolve_departments(department: &str, departments: &[String], all: bool) -> Result<(Vec<String>, bool), MaskErr> {
+    if all {
+        return Ok((get_department_codes(None)?, true));
+    }
+
+    if departments.is_empty() {
+        validate_department(department)?;
+        return Ok((vec![department.to_owned()], false));
+    }
+
+    let mut codes: Vec<String> = Vec::new();
+    for code in departments {
+        validate_department(code)?;
+        if !codes.contains(code) {
+            codes.push(code.to_owned());
+        }
+    }
+
+    Ok((codes, true))
+}
+
+/// Group rows by department. The order of the departments is preserved
+///
+/// # Arguments
+/// * `rows` - Vec<T>
+/// * `get_department` - F
+pub fn group_by_department<T, F>(rows: Vec<T>, get_department: F) -> Vec<(String, Vec<T>)>
+where
+    F: Fn(&T) -> String
+{
+    let mut groups: Vec<(String, Vec<T>)> = Vec::new();
+    for row in rows {
+        let department = get_department(&row);
+        match groups.iter_mut().find(|(code, _)| *code == department) {
+            Some((_, group)) => group.push(row),
+            None => groups.push((department, vec![row]))
+        }
+    }
+
+    groups
+}
+
+/// Return the population of a region which is the sum of the population of its departments
+///
+/// # Arguments
+/// * `region` - i32
+pub fn get_region_population(region: i32) -> i64 {
+    get_departments_by_region(region)
+        .iter()
+        .map(|d| d.population)
+        .sum()
+}
+
+/// Check that the department exist. Return a MaskErr otherwise
+///
+/// # Arguments
+/// * `code` - &str
+pub fn validate_department(code: &str) -> Result<&'static data::Department, MaskErr> {
+    get_department(code).ok_or_else(|| MaskErr::UnknownGeography { field: "department".to_owned(), code: code.to_owned() })
+}
+
+/// Check that the region exist. Return a MaskErr otherwise
+///
+/// # Arguments
+/// * `code` - i32
+pub fn validate_region(code: i32) -> Result<&'static data::Region, MaskErr> {
+    get_region(code).ok_or_else(|| MaskErr::UnknownGeography { field: "region".to_owned(), code: code.to_string() })
+}
+
+impl From<&data::Department> for proto::Department {
+    fn from(d: &data::Department) -> Self {
+        proto::Department {
+            code: d.code.to_owned(),
+            name: d.name.to_owned(),
+            region: d.region,
+            population: d.population
+        }
+    }
+}
+
+impl From<&data::Region> for proto::Region {
+    fn from(r: &data::Region) -> Self {
+        proto::Region {
+            code: r.code,
+            name: r.name.to_owned(),
+            population: get_region_population(r.code)
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct GeographyHandler {}
+
+#[tonic::async_trait]
+impl GeographyService for GeographyHandler {
+    /// List every region
+    ///
+    /// # Arguments
+    /// * `&self`
+    /// * `_` - Request<RegionsInput>
+    async fn list_regions(
+        &self,
+        _: Request<proto::RegionsInput>
+    ) -> Result<Response<proto::RegionList>, Status> {
+        let regions = data::REGIONS.iter().map(proto::Region::from).collect();
+
+        Ok(Response::new(proto::RegionList { regions }))
+    }
+
+    /// List the departments. The departments can be filtered by region
+    ///
+    /// # Arguments
+    /// * `&self`
+    /// * `request` - Request<DepartmentsInput>
+    async fn list_departments(
+        &self,
+        request: Request<proto::DepartmentsInput>
+    ) -> Result<Response<proto::DepartmentList>, Status> {
+        let input = request.into_inner();
+        let departments = match input.region {
+            Some(region) => {
+                validate_region(region)?;
+                get_departments_by_region(region)
+                    .into_iter()
+                    .map(proto::Department::from)
+                    .collect()
+            },
+            None => data::DEPARTMENTS.iter().map(proto::Department::from).collect()
+        };
+
+        Ok(Response::new(proto::DepartmentList { departments }))
+    }
+
+    /// Resolve a region from its code
+    ///
+    /// # Arguments
+    /// * `&self`
+    /// * `request` - Request<RegionInput>
+    async fn get_region(
+        &self,
+        request: Request<proto::RegionInput>
+    ) -> Result<Response<proto::Region>, Status> {
+        let input = request.into_inner();
+        let region = validate_region(input.code)?;
+
+        Ok(Response::new(region.into()))
+    }
+
+    /// Resolve a department from its code
+    ///
+    /// # Arguments
+    /// * `&self`
+    /// * `request` - Request<DepartmentInput>
+    async fn get_department(
+        &self,
+        request: Request<proto::DepartmentInput>
+    ) -> Result<Response<proto::Department>, Status> {
+        let input = request.into_inner();
+        let department = validate_department(&input.code)?;
+
+        Ok(Response::new(department.into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expect_every_department_to_have_a_region() {
+        for department in data::DEPARTMENTS {
+            assert!(get_region(department.region).is_some(), "{} has no region", department.code);
+        }
+    }
+
+    #[test]
+    fn expect_to_resolve_department() {
+        let department = get_department("2A").unwrap();
+
+        assert_eq!(department.name, "Corse-du-Sud");
+        assert_eq!(department.region, 94);
+        assert!(validate_department("20").is_err());
+    }
+
+    #[test]
+    fn expect_to_list_departments_of_region() {
+        let departments = get_departments_by_region(11);
+
+        assert_eq!(departments.len(), 8);
+        assert!(validate_region(12).is_err());
+    }
+
+    #[test]
+    fn expect_to_get_department_codes() {
+        let codes = get_department_codes(Some(94)).unwrap();
+
+        assert_eq!(codes, vec!["2A".to_owned(), "2B".to_owned()]);
+        assert_eq!(get_department_codes(None).unwrap().len(), data::DEPARTMENTS.len());
+        assert!(get_department_codes(Some(12)).is_err());
+    }
+
+    #[test]
+    fn expect_to_resolve_departments() {
+        let (codes, multiple) = resolve_departments("75", &[], false).unwrap();
+        assert_eq!(codes, vec!["75".to_owned()]);
+        assert!(!multiple);
+
+        let (codes, multiple) = resolve_departments("", &["13".to_owned(), "2A".to_owned(), "13".to_owned()], false).unwrap();
+        assert_eq!(codes, vec!["13".to_owned(), "2A".to_owned()]);
+        assert!(multiple);
+
+        assert!(resolve_departments("", &[], false).is_err());
+        assert!(resolve_departments("", &["13".to_owned(), "20".to_owned()], false).is_err());
+        assert_eq!(resolve_departments("", &[], true).unwrap().0.len(), data::DEPARTMENTS.len());
+    }
+
+    #[test]
+    fn expect_to_group_by_department() {
+        let rows = vec![("75", 1), ("13", 2), ("75", 3)];
+        let groups = group_by_department(rows, |r| r.0.to_owned());
+
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].0, "75");
+        assert_eq!(groups[0].1, vec![("75", 1), ("75", 3)]);
+    }
+
+    #[tokio::test]
+    async fn expect_grpc_to_return_error_for_unknown_department() {
+        let handler = GeographyHandler::default();
+        let request = Request::new(proto::DepartmentInput { code: "100".to_owned() });
+        let res = handler.get_department(request).await;
+
+        assert_eq!(res.unwrap_err().code(), tonic::Code::InvalidArgument);
+    }
+}
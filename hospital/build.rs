@@ -9,6 +9,13 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 "../proto/newcase.proto",
                 "../proto/mix.proto",
                 "../proto/icu.proto",
+                "../proto/quality.proto",
+                "../proto/forecast.proto",
+                "../proto/alert.proto",
+                "../proto/admin.proto",
+                "../proto/dataset.proto",
+                "../proto/vaccination.proto",
+                "../proto/mortality.proto",
             ], 
             &["../proto"]
         )?;
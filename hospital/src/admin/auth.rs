@@ -0,0 +1,93 @@
+use tonic::{Request, Status, service::Interceptor};
+
+// Environment variable of the token of the admin service
+const ENV_ADMIN_TOKEN: &str = "admin_token";
+
+/// Check the token of the calls of the admin service. Every call is rejected
+/// when the token is not configured
+#[derive(Debug, Clone, Default)]
+pub struct TokenInterceptor {
+    token: Option<String>
+}
+
+impl TokenInterceptor {
+    /// Create an interceptor with the token of the admin_token environment variable
+    pub fn from_env() -> Self {
+        let token = std::env::var(ENV_ADMIN_TOKEN)
+            .ok()
+            .filter(|t| !t.is_empty());
+
+        if token.is_none() {
+            warn!("The {ENV_ADMIN_TOKEN} variable is not set, the admin service is disabled");
+        }
+
+        TokenInterceptor { token }
+    }
+}
+
+/// Compare two tokens in a constant time
+///
+/// # Arguments
+/// * `expected` - &[u8]
+/// * `given` - &[u8]
+fn is_same_token(expected: &[u8], given: &[u8]) -> bool {
+    if expected.len() != given.len() {
+        return false;
+    }
+
+    expected
+        .iter()
+        .zip(given)
+        .fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+impl Interceptor for TokenInterceptor {
+    fn call(&mut self, request: Request<()>) -> Result<Request<()>, Status> {
+        let expected = match &self.token {
+            Some(token) => token,
+            None => return Err(Status::unauthenticated("the admin service is disabled"))
+        };
+
+        let given = request
+            .metadata()
+            .get("authorization")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "));
+
+        match given {
+            Some(given) if is_same_token(expected.as_bytes(), given.as_bytes()) => Ok(request),
+            _ => Err(Status::unauthenticated("the admin token is missing or invalid"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(authorization: Option<&str>) -> Request<()> {
+        let mut request = Request::new(());
+        if let Some(value) = authorization {
+            request.metadata_mut().insert("authorization", value.parse().unwrap());
+        }
+
+        request
+    }
+
+    #[test]
+    fn expect_token_to_be_checked() {
+        let mut interceptor = TokenInterceptor { token: Some("secret".to_owned()) };
+
+        assert!(interceptor.call(request(Some("Bearer secret"))).is_ok());
+        assert!(interceptor.call(request(Some("Bearer secreT"))).is_err());
+        assert!(interceptor.call(request(Some("secret"))).is_err());
+        assert!(interceptor.call(request(None)).is_err());
+    }
+
+    #[test]
+    fn expect_calls_to_be_rejected_without_token() {
+        let mut interceptor = TokenInterceptor::default();
+
+        assert!(interceptor.call(request(Some("Bearer "))).is_err());
+    }
+}
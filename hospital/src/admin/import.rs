@@ -0,0 +1,119 @@
+use std::sync::Arc;
+use tonic::{Request, Response, Status};
+use utils::err::MaskErr;
+use super::{store, runner::Runner};
+use super::proto_admin::admin_service_server::AdminService;
+use super::proto_admin::{TriggerInput, ImportJob, ImportJobList, ListImportsInput, JobId, JobStatus};
+
+// Number of jobs returned when the limit is not set
+const DEFAULT_LIMIT: u32 = 20;
+const MAX_LIMIT: u32 = 100;
+
+pub struct AdminHandler {
+    pub runner: Arc<Runner>
+}
+
+#[tonic::async_trait]
+impl AdminService for AdminHandler {
+    /// Start an import of the datasets. The job is returned before the import is done
+    ///
+    /// # Arguments
+    /// * `&self`
+    /// * `request` - Request<TriggerInput>
+    async fn trigger_import(&self, request: Request<TriggerInput>) -> Result<Response<ImportJob>, Status> {
+        let input = request.into_inner();
+        match self.runner.trigger(input.datasets, input.source, false).await {
+            Ok(job) => Ok(Response::new(job)),
+            Err(MaskErr::InvalidParam(msg)) => Err(MaskErr::InvalidParam(msg).into()),
+            Err(err) => Err(MaskErr::query("import job", err).into())
+        }
+    }
+
+    /// List the most recent import jobs
+    ///
+    /// # Arguments
+    /// * `&self`
+    /// * `request` - Request<ListImportsInput>
+    async fn list_imports(&self, request: Request<ListImportsInput>) -> Result<Response<ImportJobList>, Status> {
+        let input = request.into_inner();
+        let status = match input.status {
+            Some(status) => Some(JobStatus::from_i32(status)
+                .ok_or_else(|| MaskErr::InvalidParam("the status is unknown".to_owned()))?),
+            None => None
+        };
+
+        let limit = match input.limit {
+            0 => DEFAULT_LIMIT,
+            l => l.min(MAX_LIMIT)
+        };
+
+        match store::get_jobs(&self.runner.pool, status, limit as i64).await {
+            Ok(jobs) => Ok(Response::new(ImportJobList { jobs })),
+            Err(err) => Err(MaskErr::query("import jobs", err).into())
+        }
+    }
+
+    /// Return the status of an import job
+    ///
+    /// # Arguments
+    /// * `&self`
+    /// * `request` - Request<JobId>
+    async fn import_status(&self, request: Request<JobId>) -> Result<Response<ImportJob>, Status> {
+        let id = request.into_inner().id;
+        match store::get_job(&self.runner.pool, id).await {
+            Ok(Some(job)) => Ok(Response::new(job)),
+            Ok(None) => Err(Status::not_found(format!("the import job {id} does not exist"))),
+            Err(err) => Err(MaskErr::query("import job", err).into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::runner::ImportConfig;
+
+    async fn get_handler() -> AdminHandler {
+        let pool = db::connect("../config.toml").await.unwrap();
+        store::create_tables(&pool).await.unwrap();
+
+        let config = ImportConfig {
+            program: "true".to_owned(),
+            script: "import.py".to_owned(),
+            workdir: "..".to_owned(),
+            source: None,
+            root: "..".to_owned(),
+            instance: "admin-test".to_owned()
+        };
+
+        AdminHandler { runner: Arc::new(Runner::new(Arc::new(pool), config)) }
+    }
+
+    #[tokio::test]
+    async fn expect_grpc_to_trigger_and_return_job() {
+        let handler = get_handler().await;
+        let input = TriggerInput { datasets: vec!["vaxx".to_owned()], source: Some("fixtures".to_owned()) };
+
+        let job = handler.trigger_import(Request::new(input)).await.unwrap().into_inner();
+        let status = handler.import_status(Request::new(JobId { id: job.id })).await.unwrap().into_inner();
+        assert_eq!(status.datasets, vec!["vaxx".to_owned()]);
+
+        let list = handler.list_imports(Request::new(ListImportsInput { status: None, limit: 5 })).await.unwrap();
+        assert!(list.get_ref().jobs.len() <= 5);
+    }
+
+    #[tokio::test]
+    async fn expect_grpc_to_return_error() {
+        let handler = get_handler().await;
+        let input = TriggerInput { datasets: vec!["unknown".to_owned()], source: None };
+        let res = handler.trigger_import(Request::new(input)).await;
+        assert_eq!(res.unwrap_err().code(), tonic::Code::InvalidArgument);
+
+        let input = TriggerInput { datasets: Vec::new(), source: Some("../..".to_owned()) };
+        let res = handler.trigger_import(Request::new(input)).await;
+        assert_eq!(res.unwrap_err().code(), tonic::Code::InvalidArgument);
+
+        let res = handler.import_status(Request::new(JobId { id: -1 })).await;
+        assert_eq!(res.unwrap_err().code(), tonic::Code::NotFound);
+    }
+}
@@ -0,0 +1,9 @@
+pub mod auth;
+pub mod store;
+pub mod runner;
+pub mod scheduler;
+pub mod import;
+
+pub mod proto_admin {
+    tonic::include_proto!("admin");
+}
@@ -0,0 +1,262 @@
+use std::path::Path;
+use std::sync::Arc;
+use db::PGPool;
+use tokio::process::Command;
+use tokio::sync::Mutex;
+use utils::err::MaskErr;
+use super::store;
+use super::proto_admin::ImportJob;
+
+// Environment variables of the import
+const ENV_IMPORT_PROGRAM: &str = "import_program";
+const ENV_IMPORT_SCRIPT: &str = "import_script";
+const ENV_IMPORT_WORKDIR: &str = "import_workdir";
+const ENV_IMPORT_SOURCE: &str = "import_source";
+const ENV_IMPORT_ROOT: &str = "import_root";
+const ENV_IMPORT_INSTANCE: &str = "import_instance";
+// set by docker & kubernetes with the name of the container
+const ENV_HOSTNAME: &str = "HOSTNAME";
+// Number of characters of the output kept when an import fails
+const MAX_ERROR_LEN: usize = 2000;
+
+/// Command used to run the imports
+#[derive(Debug, Clone)]
+pub struct ImportConfig {
+    pub program: String,
+    pub script: String,
+    // directory from where the script is run
+    pub workdir: String,
+    // directory of the files to import. The files are downloaded when None
+    pub source: Option<String>,
+    // directory containing the sources which can be requested by a job
+    pub root: String,
+    // name of the service instance running the jobs
+    pub instance: String
+}
+
+impl ImportConfig {
+    /// Create the config from the environment variables. By default the import.py script
+    /// at the root of the project is run with python3
+    pub fn from_env() -> Self {
+        let var = |key: &str, default: &str| std::env::var(key).unwrap_or_else(|_| default.to_owned());
+
+        let workdir = var(ENV_IMPORT_WORKDIR, "..");
+        ImportConfig {
+            program: var(ENV_IMPORT_PROGRAM, "python3"),
+            script: var(ENV_IMPORT_SCRIPT, "import.py"),
+            root: var(ENV_IMPORT_ROOT, &workdir),
+            workdir,
+            source: std::env::var(ENV_IMPORT_SOURCE).ok().filter(|s| !s.is_empty()),
+            instance: std::env::var(ENV_IMPORT_INSTANCE)
+                .or_else(|_| std::env::var(ENV_HOSTNAME))
+                .unwrap_or_else(|_| "default".to_owned())
+        }
+    }
+
+    /// Check that a source requested by a job is a directory of the import root. The source
+    /// is relative to the directory from where the script is run
+    ///
+    /// # Arguments
+    /// * `&self` - Self
+    /// * `source` - &str
+    pub fn validate_source(&self, source: &str) -> Result<(), MaskErr> {
+        let root = Path::new(&self.root).canonicalize()?;
+        let path = Path::new(&self.workdir)
+            .join(source)
+            .canonicalize()
+            .map_err(|_| MaskErr::InvalidParam(format!("the source {source} does not exist")))?;
+
+        match path.is_dir() && path.starts_with(&root) {
+            true => Ok(()),
+            false => Err(MaskErr::InvalidParam(format!("the source {source} is not a directory of the import root")))
+        }
+    }
+}
+
+/// Run the import jobs one at a time
+pub struct Runner {
+    pub pool: Arc<PGPool>,
+    pub config: ImportConfig,
+    lock: Mutex<()>
+}
+
+impl Runner {
+    /// Create a new runner
+    ///
+    /// # Arguments
+    /// * `pool` - Arc<PGPool>
+    /// * `config` - ImportConfig
+    pub fn new(pool: Arc<PGPool>, config: ImportConfig) -> Self {
+        Runner { pool, config, lock: Mutex::new(()) }
+    }
+
+    /// Save a job and run it in the background. The job waits for the previous jobs to finish
+    ///
+    /// # Arguments
+    /// * `self` - &Arc<Self>
+    /// * `datasets` - Vec<String>
+    /// * `source` - Option<String>
+    /// * `scheduled` - bool
+    pub async fn trigger(self: &Arc<Self>, datasets: Vec<String>, source: Option<String>, scheduled: bool) -> Result<ImportJob, MaskErr> {
+        // the import.py script imports the datasets of the registry
+        for dataset in &datasets {
+            registry::validate_dataset(dataset)?;
+        }
+
+        // the default source is set by the configuration of the service
+        let source = match source {
+            Some(source) => {
+                self.config.validate_source(&source)?;
+                Some(source)
+            },
+            None => self.config.source.clone()
+        };
+        let job = store::insert_job(&self.pool, &datasets, source.as_deref(), scheduled, &self.config.instance).await?;
+
+        let runner = Arc::clone(self);
+        let id = job.id;
+        tokio::spawn(async move {
+            if let Err(err) = runner.run(id, datasets, source).await {
+                error!("unable to update the import job {id}: {err}");
+            }
+        });
+
+        Ok(job)
+    }
+
+    /// Run the import script of a job and save its result
+    ///
+    /// # Arguments
+    /// * `&self` - Self
+    /// * `id` - i64
+    /// * `datasets` - Vec<String>
+    /// * `source` - Option<String>
+    async fn run(&self, id: i64, datasets: Vec<String>, source: Option<String>) -> Result<(), MaskErr> {
+        let _guard = self.lock.lock().await;
+        store::set_running(&self.pool, id).await?;
+        info!("Running the import job {id}");
+
+        let mut command = Command::new(&self.config.program);
+        command
+            .arg(&self.config.script)
+            .current_dir(&self.config.workdir)
+            .kill_on_drop(true);
+
+        for dataset in datasets {
+            command.arg("--dataset").arg(dataset);
+        }
+
+        if let Some(source) = source {
+            command.arg("--source").arg(source);
+        }
+
+        let error = match command.output().await {
+            Ok(output) if output.status.success() => None,
+            Ok(output) => {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                Some(format!("{}: {}", output.status, tail(stderr.trim(), MAX_ERROR_LEN)))
+            },
+            Err(err) => Some(format!("unable to run the import: {err}"))
+        };
+
+        if let Some(err) = &error {
+            error!("The import job {id} failed {err}");
+        }
+
+        store::set_finished(&self.pool, id, error).await
+    }
+}
+
+/// Return the last characters of a text
+///
+/// # Arguments
+/// * `text` - &str
+/// * `len` - usize
+fn tail(text: &str, len: usize) -> String {
+    let skip = text.chars().count().saturating_sub(len);
+
+    text.chars().skip(skip).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::proto_admin::JobStatus;
+    use std::time::Duration;
+
+    async fn wait_for_job(pool: &PGPool, id: i64) -> ImportJob {
+        for _ in 0..50 {
+            let job = store::get_job(pool, id).await.unwrap().unwrap();
+            if job.finished_at.is_some() {
+                return job;
+            }
+
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+
+        panic!("the job {id} did not finish");
+    }
+
+    async fn get_runner(program: &str) -> Arc<Runner> {
+        let pool = db::connect("../config.toml").await.unwrap();
+        store::create_tables(&pool).await.unwrap();
+
+        // the script is an argument of the program which is ignored by true & false
+        let config = ImportConfig {
+            program: program.to_owned(),
+            script: "import.py".to_owned(),
+            workdir: "..".to_owned(),
+            source: Some("data".to_owned()),
+            root: "..".to_owned(),
+            instance: "runner-test".to_owned()
+        };
+
+        Arc::new(Runner::new(Arc::new(pool), config))
+    }
+
+    #[test]
+    fn expect_to_keep_end_of_output() {
+        assert_eq!(tail("abcdef", 3), "def");
+        assert_eq!(tail("ab", 3), "ab");
+    }
+
+    #[tokio::test]
+    async fn expect_job_to_succeed() {
+        let runner = get_runner("true").await;
+        let job = runner.trigger(vec!["cases".to_owned()], None, false).await.unwrap();
+        assert_eq!(job.status(), JobStatus::Pending);
+        assert_eq!(job.source.as_deref(), Some("data"));
+
+        let job = wait_for_job(&runner.pool, job.id).await;
+        assert_eq!(job.status(), JobStatus::Succeeded);
+        assert!(job.started_at.is_some());
+    }
+
+    #[tokio::test]
+    async fn expect_job_to_fail() {
+        let runner = get_runner("false").await;
+        let job = runner.trigger(Vec::new(), None, true).await.unwrap();
+
+        let job = wait_for_job(&runner.pool, job.id).await;
+        assert_eq!(job.status(), JobStatus::Failed);
+        assert!(job.error.is_some());
+    }
+
+    #[tokio::test]
+    async fn expect_source_outside_root_to_return_error() {
+        let runner = get_runner("true").await;
+
+        assert!(runner.config.validate_source("fixtures").is_ok());
+        for source in ["../..", "/etc", "fixtures/../../", "missing"] {
+            let res = runner.trigger(vec!["cases".to_owned()], Some(source.to_owned()), false).await;
+            assert!(matches!(res, Err(MaskErr::InvalidParam(_))));
+        }
+    }
+
+    #[tokio::test]
+    async fn expect_unknown_dataset_to_return_error() {
+        let runner = get_runner("true").await;
+
+        assert!(runner.trigger(vec!["unknown".to_owned()], None, false).await.is_err());
+    }
+}
@@ -0,0 +1,90 @@
+use std::sync::Arc;
+use std::time::Duration;
+use super::runner::Runner;
+
+// Interval between the imports of every dataset (e.g. 30m, 6h, 1d). The scheduler is
+// disabled when not set. The interval of a dataset can be overridden with the
+// import_interval_<dataset> variable (e.g. import_interval_data_mix=7d)
+const ENV_IMPORT_INTERVAL: &str = "import_interval";
+
+/// Parse an interval made of a number and a unit (s, m, h or d)
+///
+/// # Arguments
+/// * `value` - &str
+fn parse_interval(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    let (split, _) = value.char_indices().last()?;
+    let (count, unit) = value.split_at(split);
+    let count: u64 = count.parse().ok().filter(|c| *c > 0)?;
+
+    let seconds = match unit {
+        "s" => Some(count),
+        "m" => count.checked_mul(60),
+        "h" => count.checked_mul(3600),
+        "d" => count.checked_mul(86400),
+        _ => None
+    }?;
+
+    Some(Duration::from_secs(seconds))
+}
+
+/// Return the interval of each dataset from the environment variables
+fn get_intervals() -> Vec<(&'static str, Duration)> {
+    let default = std::env::var(ENV_IMPORT_INTERVAL).ok();
+
+    registry::get_datasets()
+        .iter()
+        .map(|dataset| dataset.name.as_str())
+        .filter_map(|dataset| {
+            let value = std::env::var(format!("{ENV_IMPORT_INTERVAL}_{dataset}"))
+                .ok()
+                .or_else(|| default.clone())?;
+
+            match parse_interval(&value) {
+                Some(interval) => Some((dataset, interval)),
+                None => {
+                    error!("The import interval {value} of {dataset} is invalid");
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+/// Start a task per dataset importing the dataset at its interval. The imports are made
+/// from the import_source of the runner
+///
+/// # Arguments
+/// * `runner` - Arc<Runner>
+pub fn start(runner: Arc<Runner>) {
+    for (dataset, interval) in get_intervals() {
+        info!("Importing {dataset} every {}s", interval.as_secs());
+
+        let runner = Arc::clone(&runner);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval_at(tokio::time::Instant::now() + interval, interval);
+            loop {
+                ticker.tick().await;
+                if let Err(err) = runner.trigger(vec![dataset.to_owned()], None, true).await {
+                    error!("unable to schedule the import of {dataset}: {err}");
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expect_to_parse_interval() {
+        assert_eq!(parse_interval("30m"), Some(Duration::from_secs(1800)));
+        assert_eq!(parse_interval("6h"), Some(Duration::from_secs(21600)));
+        assert_eq!(parse_interval("1d"), Some(Duration::from_secs(86400)));
+        assert_eq!(parse_interval("0h"), None);
+        assert_eq!(parse_interval("6"), None);
+        assert_eq!(parse_interval(""), None);
+        assert_eq!(parse_interval("6é"), None);
+    }
+}
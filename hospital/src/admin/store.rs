@@ -0,0 +1,200 @@
+use db::PGPool;
+use sqlx::{postgres::PgRow, Row};
+use utils::err::MaskErr;
+use super::proto_admin::{ImportJob, JobStatus};
+
+// The status is saved with the value of its protobuf enum
+const CREATE_JOB_TABLE: &str = "CREATE TABLE IF NOT EXISTS import_job (
+    id BIGSERIAL PRIMARY KEY,
+    datasets TEXT[] NOT NULL,
+    source TEXT,
+    status INTEGER NOT NULL,
+    scheduled BOOLEAN NOT NULL DEFAULT false,
+    created_at TIMESTAMP NOT NULL DEFAULT now(),
+    started_at TIMESTAMP,
+    finished_at TIMESTAMP,
+    error TEXT,
+    instance TEXT
+)";
+
+// tables created before the jobs were owned by an instance
+const ADD_INSTANCE_COLUMN: &str = "ALTER TABLE import_job ADD COLUMN IF NOT EXISTS instance TEXT";
+
+const SELECT_JOB: &str = "SELECT id, datasets, source, status, scheduled, error,
+    to_char(created_at, 'YYYY-MM-DD\"T\"HH24:MI:SS') AS created_at,
+    to_char(started_at, 'YYYY-MM-DD\"T\"HH24:MI:SS') AS started_at,
+    to_char(finished_at, 'YYYY-MM-DD\"T\"HH24:MI:SS') AS finished_at
+    FROM import_job";
+
+impl TryFrom<PgRow> for ImportJob {
+    type Error = sqlx::Error;
+
+    fn try_from(value: PgRow) -> Result<Self, Self::Error> {
+        let res = Self {
+            id: value.try_get("id")?,
+            datasets: value.try_get("datasets")?,
+            source: value.try_get("source")?,
+            status: value.try_get("status")?,
+            scheduled: value.try_get("scheduled")?,
+            created_at: value.try_get("created_at")?,
+            started_at: value.try_get("started_at")?,
+            finished_at: value.try_get("finished_at")?,
+            error: value.try_get("error")?
+        };
+
+        Ok(res)
+    }
+}
+
+/// Create the table of the import jobs if it doesn't exist
+///
+/// # Arguments
+/// * `pool` - &PGPool
+pub async fn create_tables(pool: &PGPool) -> Result<(), MaskErr> {
+    sqlx::query(CREATE_JOB_TABLE).execute(pool).await?;
+    sqlx::query(ADD_INSTANCE_COLUMN).execute(pool).await?;
+
+    Ok(())
+}
+
+/// Save a new pending job
+///
+/// # Arguments
+/// * `pool` - &PGPool
+/// * `datasets` - &[String]
+/// * `source` - Option<&str>
+/// * `scheduled` - bool
+/// * `instance` - &str
+pub async fn insert_job(
+    pool: &PGPool,
+    datasets: &[String],
+    source: Option<&str>,
+    scheduled: bool,
+    instance: &str
+) -> Result<ImportJob, MaskErr> {
+    let row = sqlx::query("INSERT INTO import_job (datasets, source, status, scheduled, instance) VALUES ($1, $2, $3, $4, $5) RETURNING id")
+        .bind(datasets)
+        .bind(source)
+        .bind(JobStatus::Pending as i32)
+        .bind(scheduled)
+        .bind(instance)
+        .fetch_one(pool)
+        .await?;
+
+    let id: i64 = row.try_get("id")?;
+    get_job(pool, id)
+        .await?
+        .ok_or_else(|| MaskErr::QueryError("the job has not been saved".to_owned()))
+}
+
+/// Return a job. Return None if the job does not exist
+///
+/// # Arguments
+/// * `pool` - &PGPool
+/// * `id` - i64
+pub async fn get_job(pool: &PGPool, id: i64) -> Result<Option<ImportJob>, MaskErr> {
+    let row = sqlx::query(&format!("{SELECT_JOB} WHERE id = $1"))
+        .bind(id)
+        .fetch_optional(pool)
+        .await?;
+
+    Ok(row.map(ImportJob::try_from).transpose()?)
+}
+
+/// Return the most recent jobs, optionally filtered by status
+///
+/// # Arguments
+/// * `pool` - &PGPool
+/// * `status` - Option<JobStatus>
+/// * `limit` - i64
+pub async fn get_jobs(pool: &PGPool, status: Option<JobStatus>, limit: i64) -> Result<Vec<ImportJob>, MaskErr> {
+    let rows = sqlx::query(&format!("{SELECT_JOB} WHERE ($1::integer IS NULL OR status = $1) ORDER BY id DESC LIMIT $2"))
+        .bind(status.map(|s| s as i32))
+        .bind(limit)
+        .fetch_all(pool)
+        .await?;
+
+    let jobs = rows
+        .into_iter()
+        .map(ImportJob::try_from)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(jobs)
+}
+
+/// Mark a job as running
+///
+/// # Arguments
+/// * `pool` - &PGPool
+/// * `id` - i64
+pub async fn set_running(pool: &PGPool, id: i64) -> Result<(), MaskErr> {
+    sqlx::query("UPDATE import_job SET status = $2, started_at = now() WHERE id = $1")
+        .bind(id)
+        .bind(JobStatus::Running as i32)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+/// Mark a job as finished. The error is only set when the job failed
+///
+/// # Arguments
+/// * `pool` - &PGPool
+/// * `id` - i64
+/// * `error` - Option<String>
+pub async fn set_finished(pool: &PGPool, id: i64, error: Option<String>) -> Result<(), MaskErr> {
+    let status = match error {
+        Some(_) => JobStatus::Failed,
+        None => JobStatus::Succeeded
+    };
+
+    sqlx::query("UPDATE import_job SET status = $2, finished_at = now(), error = $3 WHERE id = $1")
+        .bind(id)
+        .bind(status as i32)
+        .bind(error)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+/// Mark the jobs of an instance which were not finished when the instance stopped as failed.
+/// The jobs of the other instances are still running
+///
+/// # Arguments
+/// * `pool` - &PGPool
+/// * `instance` - &str
+pub async fn fail_interrupted(pool: &PGPool, instance: &str) -> Result<(), MaskErr> {
+    sqlx::query("UPDATE import_job SET status = $1, finished_at = now(), error = 'the service stopped during the import'
+        WHERE status = ANY($2) AND instance = $3")
+        .bind(JobStatus::Failed as i32)
+        .bind(vec![JobStatus::Pending as i32, JobStatus::Running as i32])
+        .bind(instance)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn expect_to_only_fail_jobs_of_the_instance() {
+        let pool = db::connect("../config.toml").await.unwrap();
+        create_tables(&pool).await.unwrap();
+
+        let own = insert_job(&pool, &[], None, false, "store-test-a").await.unwrap();
+        let other = insert_job(&pool, &[], None, false, "store-test-b").await.unwrap();
+        fail_interrupted(&pool, "store-test-a").await.unwrap();
+
+        let own = get_job(&pool, own.id).await.unwrap().unwrap();
+        let other = get_job(&pool, other.id).await.unwrap().unwrap();
+        set_finished(&pool, other.id, None).await.unwrap();
+
+        assert_eq!(own.status(), JobStatus::Failed);
+        assert_eq!(other.status(), JobStatus::Pending);
+    }
+}
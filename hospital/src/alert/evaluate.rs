@@ -0,0 +1,263 @@
+use db::PGPool;
+use sqlx::{postgres::PgRow, Row};
+use utils::err::MaskErr;
+use super::store;
+use super::proto_alert::{Rule, Metric, Operator, AlertEvent};
+
+/// Value of a metric for the last imported day
+#[derive(Debug)]
+pub struct Reading {
+    pub day: String,
+    pub value: f64
+}
+
+impl Reading {
+    /// Decode the reading of a row. Return None when the area does not have any data
+    /// as the aggregates return NULL
+    ///
+    /// # Arguments
+    /// * `value` - PgRow
+    fn from_row(value: PgRow) -> Result<Option<Self>, sqlx::Error> {
+        let day: Option<String> = value.try_get("jour")?;
+        let metric: Option<f64> = value.try_get("value")?;
+
+        Ok(day.zip(metric).map(|(day, value)| Self { day, value }))
+    }
+}
+
+/// Return the query of the last value of a metric. The query expects the department
+/// or the region as $1
+///
+/// # Arguments
+/// * `metric` - Metric
+/// * `by_department` - bool
+fn get_metric_query(metric: Metric, by_department: bool) -> Option<&'static str> {
+    let query = match (metric, by_department) {
+        (Metric::WeeklyIncidence, true) => "SELECT MAX(jour) AS jour, (SUM(p) * 100000.0 / NULLIF(MAX(pop), 0))::float8 AS value
+            FROM positivity_rate_per_dep_by_day
+            WHERE dep = $1 AND jour > (
+                SELECT to_char(MAX(jour)::date - 7, 'YYYY-MM-DD') FROM positivity_rate_per_dep_by_day WHERE dep = $1
+            )",
+        (Metric::WeeklyIncidence, false) => return None,
+        (Metric::IcuOccupancy, true) => "SELECT h.jour, h.rea::float8 * 100 / NULLIF(c.beds, 0) AS value
+            FROM hospital_dep h JOIN icu_capacity c ON c.dep = h.dep
+            WHERE h.dep = $1 AND h.sexe = 0 ORDER BY h.jour DESC LIMIT 1",
+        (Metric::IcuOccupancy, false) => "SELECT h.jour, h.rea::float8 * 100 / NULLIF(c.beds, 0) AS value
+            FROM hospitalization h JOIN (
+                SELECT reg, SUM(beds)::bigint AS beds FROM icu_capacity GROUP BY reg
+            ) c ON c.reg = h.reg
+            WHERE h.reg = $1 AND h.cl_age90 = 0 ORDER BY h.jour DESC LIMIT 1",
+        (Metric::Hospitalization, true) => "SELECT jour, hosp::float8 AS value FROM hospital_dep
+            WHERE dep = $1 AND sexe = 0 ORDER BY jour DESC LIMIT 1",
+        (Metric::Hospitalization, false) => "SELECT jour, hosp::float8 AS value FROM hospitalization
+            WHERE reg = $1 AND cl_age90 = 0 ORDER BY jour DESC LIMIT 1",
+        (Metric::PcrPositive, true) => "SELECT jour, p::float8 AS value FROM pcr_test_department
+            WHERE dep = $1 AND cl_age90 = 0 ORDER BY jour DESC LIMIT 1",
+        (Metric::PcrPositive, false) => "SELECT jour, p::float8 AS value FROM pcr_test_region
+            WHERE reg = $1 AND cl_age90 = 0 ORDER BY jour DESC LIMIT 1"
+    };
+
+    Some(query)
+}
+
+/// Check that the metric of a rule is available for its area
+///
+/// # Arguments
+/// * `metric` - Metric
+/// * `department` - Option<&str>
+/// * `region` - Option<i32>
+pub fn validate_area(metric: Metric, department: Option<&str>, region: Option<i32>) -> Result<(), MaskErr> {
+    match (department, region) {
+        (Some(department), None) => geography::validate_department(department).map(|_| ())?,
+        (None, Some(region)) => geography::validate_region(region).map(|_| ())?,
+        (Some(_), Some(_)) => return Err(MaskErr::InvalidParam("a rule can't target both a department and a region".to_owned())),
+        (None, None) => return Err(MaskErr::MissingParam("department or region".to_owned()))
+    };
+
+    if get_metric_query(metric, department.is_some()).is_none() {
+        return Err(MaskErr::InvalidParam("the metric is not available for a region".to_owned()));
+    }
+
+    Ok(())
+}
+
+/// Return the last value of the metric of a rule. Return None if the area does not have any value
+///
+/// # Arguments
+/// * `pool` - &PGPool
+/// * `rule` - &Rule
+pub async fn get_reading(pool: &PGPool, rule: &Rule) -> Result<Option<Reading>, MaskErr> {
+    let query = get_metric_query(rule.metric(), rule.department.is_some())
+        .ok_or_else(|| MaskErr::InvalidParam("the metric is not available for a region".to_owned()))?;
+
+    let row = match (&rule.department, rule.region) {
+        (Some(department), _) => sqlx::query(query).bind(department).fetch_optional(pool).await?,
+        (None, Some(region)) => sqlx::query(query).bind(region).fetch_optional(pool).await?,
+        (None, None) => return Ok(None)
+    };
+
+    Ok(row.map(Reading::from_row).transpose()?.flatten())
+}
+
+/// Check whether a value crossed the threshold of a rule
+///
+/// # Arguments
+/// * `rule` - &Rule
+/// * `value` - f64
+pub fn is_crossed(rule: &Rule, value: f64) -> bool {
+    match rule.operator() {
+        Operator::Above => value > rule.threshold,
+        Operator::Below => value < rule.threshold
+    }
+}
+
+/// Evaluate every rule against the last imported values. An event is returned & saved
+/// for the rules whose value cross their threshold since the previous evaluation.
+/// The rule is locked while its state is updated so concurrent evaluations emit a single event
+///
+/// # Arguments
+/// * `pool` - &PGPool
+pub async fn evaluate_rules(pool: &PGPool) -> Result<Vec<AlertEvent>, MaskErr> {
+    let mut events = Vec::new();
+    for rule in store::get_rules(pool).await? {
+        let reading = match get_reading(pool, &rule).await? {
+            Some(reading) => reading,
+            None => continue
+        };
+
+        let crossed = is_crossed(&rule, reading.value);
+        if crossed == rule.triggered {
+            continue;
+        }
+
+        let mut tx = pool.begin().await?;
+        let triggered = match store::lock_rule(&mut tx, rule.id).await? {
+            Some(triggered) => triggered,
+            // the rule has been deleted since it was read
+            None => continue
+        };
+
+        if crossed && !triggered {
+            let event = AlertEvent {
+                rule_id: rule.id,
+                metric: rule.metric,
+                department: rule.department.clone(),
+                region: rule.region,
+                operator: rule.operator,
+                threshold: rule.threshold,
+                day: reading.day,
+                value: reading.value
+            };
+
+            store::insert_event(&mut tx, &event).await?;
+            events.push(event);
+        }
+
+        if crossed != triggered {
+            store::set_triggered(&mut tx, rule.id, crossed).await?;
+        }
+
+        tx.commit().await?;
+    }
+
+    Ok(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(operator: Operator, threshold: f64) -> Rule {
+        Rule {
+            id: 1,
+            metric: Metric::Hospitalization as i32,
+            department: Some("75".to_owned()),
+            region: None,
+            operator: operator as i32,
+            threshold,
+            triggered: false
+        }
+    }
+
+    #[test]
+    fn expect_to_check_threshold() {
+        assert!(is_crossed(&rule(Operator::Above, 250.0), 251.0));
+        assert!(!is_crossed(&rule(Operator::Above, 250.0), 250.0));
+        assert!(is_crossed(&rule(Operator::Below, 10.0), 9.5));
+    }
+
+    #[test]
+    fn expect_to_validate_area() {
+        assert!(validate_area(Metric::WeeklyIncidence, Some("13"), None).is_ok());
+        assert!(validate_area(Metric::WeeklyIncidence, None, Some(93)).is_err());
+        assert!(validate_area(Metric::IcuOccupancy, None, Some(93)).is_ok());
+        assert!(validate_area(Metric::IcuOccupancy, None, None).is_err());
+        assert!(validate_area(Metric::PcrPositive, Some("20"), None).is_err());
+    }
+
+    #[tokio::test]
+    async fn expect_to_emit_event_once_when_threshold_is_crossed() {
+        let pool = db::connect("../config.toml").await.unwrap();
+        store::create_tables(&pool).await.unwrap();
+
+        let rule = store::insert_rule(&pool, super::super::proto_alert::RuleInput {
+            metric: Metric::Hospitalization as i32,
+            department: Some("75".to_owned()),
+            region: None,
+            operator: Operator::Above as i32,
+            threshold: -1.0
+        }).await.unwrap();
+
+        // concurrent evaluations (e.g. two replicas notified of the same import)
+        let (events, concurrent) = tokio::join!(evaluate_rules(&pool), evaluate_rules(&pool));
+        let again = evaluate_rules(&pool).await.unwrap();
+        store::delete_rule(&pool, rule.id).await.unwrap();
+
+        let emitted = events.unwrap()
+            .into_iter()
+            .chain(concurrent.unwrap())
+            .filter(|e| e.rule_id == rule.id)
+            .count();
+        assert_eq!(emitted, 1);
+        assert!(again.iter().all(|e| e.rule_id != rule.id));
+    }
+
+    #[tokio::test]
+    async fn expect_weekly_incidence_per_100k() {
+        let pool = db::connect("../config.toml").await.unwrap();
+        let mut weekly = rule(Operator::Above, 0.0);
+        weekly.metric = Metric::WeeklyIncidence as i32;
+
+        let reading = get_reading(&pool, &weekly).await.unwrap().unwrap();
+        let (positives, pop): (i64, i64) = sqlx::query_as(
+            "SELECT SUM(p)::bigint, MAX(pop) FROM positivity_rate_per_dep_by_day
+            WHERE dep = '75' AND jour > to_char($1::date - 7, 'YYYY-MM-DD') AND jour <= $1"
+        )
+            .bind(&reading.day)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+
+        assert!((reading.value - positives as f64 * 100000.0 / pop as f64).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn expect_icu_occupancy_to_use_capacity() {
+        let pool = db::connect("../config.toml").await.unwrap();
+        let mut icu = rule(Operator::Above, 0.0);
+        icu.metric = Metric::IcuOccupancy as i32;
+
+        // the capacity of the department comes from the fixtures/icu_capacity.csv file
+        let reading = get_reading(&pool, &icu).await.unwrap().unwrap();
+        let rea: i64 = sqlx::query_scalar("SELECT rea FROM hospital_dep WHERE dep = '75' AND sexe = 0 AND jour = $1")
+            .bind(&reading.day)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+
+        assert!((reading.value - rea as f64 * 100.0 / 60.0).abs() < 1e-9);
+
+        icu.department = Some("80".to_owned());
+        assert!(get_reading(&pool, &icu).await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,49 @@
+use std::sync::Arc;
+use std::time::Duration;
+use db::PGPool;
+use sqlx::postgres::PgListener;
+use tokio::sync::broadcast;
+use utils::err::MaskErr;
+use super::evaluate;
+use super::proto_alert::AlertEvent;
+
+// Channel notified by the import.py script once every dataset has been imported
+pub const IMPORT_CHANNEL: &str = "dataset_imported";
+// Delay before listening again when the connection to the database is lost
+const RETRY_DELAY: Duration = Duration::from_secs(10);
+
+/// Wait for the import notifications and evaluate the rules after each import
+///
+/// # Arguments
+/// * `pool` - &PGPool
+/// * `sender` - &broadcast::Sender<AlertEvent>
+async fn listen_imports(pool: &PGPool, sender: &broadcast::Sender<AlertEvent>) -> Result<(), MaskErr> {
+    let mut listener = PgListener::connect_with(pool).await?;
+    listener.listen(IMPORT_CHANNEL).await?;
+
+    loop {
+        listener.recv().await?;
+        info!("New import received, evaluating the alert rules");
+
+        for event in evaluate::evaluate_rules(pool).await? {
+            // an error only means that nobody is watching the alerts
+            let _ = sender.send(event);
+        }
+    }
+}
+
+/// Evaluate the alert rules each time a dataset is imported. The events are sent to the watchers
+/// through the broadcast channel
+///
+/// # Arguments
+/// * `pool` - Arc<PGPool>
+/// * `sender` - broadcast::Sender<AlertEvent>
+pub async fn listen(pool: Arc<PGPool>, sender: broadcast::Sender<AlertEvent>) {
+    loop {
+        if let Err(err) = listen_imports(&pool, &sender).await {
+            error!("Unable to listen the imports: {err}");
+        }
+
+        tokio::time::sleep(RETRY_DELAY).await;
+    }
+}
@@ -0,0 +1,10 @@
+pub mod rule;
+pub mod store;
+pub mod evaluate;
+pub mod listener;
+
+// the stream type of the watchAlerts rpc is named after the camel case rpc
+#[allow(non_camel_case_types)]
+pub mod proto_alert {
+    tonic::include_proto!("alert");
+}
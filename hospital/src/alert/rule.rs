@@ -0,0 +1,200 @@
+use std::sync::Arc;
+use db::PGPool;
+use tokio::sync::{broadcast, mpsc};
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status};
+use utils::err::MaskErr;
+use super::{store, evaluate};
+use super::proto_alert::alert_service_server::AlertService;
+use super::proto_alert::{
+    Rule,
+    RuleInput,
+    RuleList,
+    RuleId,
+    ListRulesInput,
+    WatchInput,
+    AlertEvent,
+    Metric,
+    Operator
+};
+
+// Number of events buffered for a watcher which is slower than the evaluation
+const WATCH_BUFFER: usize = 32;
+
+pub struct AlertHandler {
+    pub pool: Arc<PGPool>,
+    pub sender: broadcast::Sender<AlertEvent>
+}
+
+/// Check that the input of a rule is valid
+///
+/// # Arguments
+/// * `input` - &RuleInput
+fn validate_rule(input: &RuleInput) -> Result<(), MaskErr> {
+    let metric = Metric::from_i32(input.metric)
+        .ok_or_else(|| MaskErr::InvalidParam("the metric is unknown".to_owned()))?;
+
+    if Operator::from_i32(input.operator).is_none() {
+        return Err(MaskErr::InvalidParam("the operator is unknown".to_owned()));
+    }
+
+    if !input.threshold.is_finite() {
+        return Err(MaskErr::InvalidParam("the threshold is not a number".to_owned()));
+    }
+
+    evaluate::validate_area(metric, input.department.as_deref(), input.region)
+}
+
+#[tonic::async_trait]
+impl AlertService for AlertHandler {
+    type watchAlertsStream = ReceiverStream<Result<AlertEvent, Status>>;
+
+    /// Create a rule evaluated after each import
+    ///
+    /// # Arguments
+    /// * `&self`
+    /// * `request` - Request<RuleInput>
+    async fn create_rule(&self, request: Request<RuleInput>) -> Result<Response<Rule>, Status> {
+        let input = request.into_inner();
+        validate_rule(&input)?;
+
+        match store::insert_rule(&self.pool, input).await {
+            Ok(rule) => Ok(Response::new(rule)),
+            Err(err) => Err(MaskErr::query("alert rule", err).into())
+        }
+    }
+
+    /// Return every rule
+    ///
+    /// # Arguments
+    /// * `&self`
+    /// * `_request` - Request<ListRulesInput>
+    async fn list_rules(&self, _request: Request<ListRulesInput>) -> Result<Response<RuleList>, Status> {
+        match store::get_rules(&self.pool).await {
+            Ok(rules) => Ok(Response::new(RuleList { rules })),
+            Err(err) => Err(MaskErr::query("alert rules", err).into())
+        }
+    }
+
+    /// Delete a rule and return it
+    ///
+    /// # Arguments
+    /// * `&self`
+    /// * `request` - Request<RuleId>
+    async fn delete_rule(&self, request: Request<RuleId>) -> Result<Response<Rule>, Status> {
+        let id = request.into_inner().id;
+        match store::delete_rule(&self.pool, id).await {
+            Ok(Some(rule)) => Ok(Response::new(rule)),
+            Ok(None) => Err(Status::not_found(format!("the rule {id} does not exist"))),
+            Err(err) => Err(MaskErr::query("alert rule", err).into())
+        }
+    }
+
+    /// Stream the events of the rules. The events of every rule are streamed when
+    /// no rule is given
+    ///
+    /// # Arguments
+    /// * `&self`
+    /// * `request` - Request<WatchInput>
+    async fn watch_alerts(&self, request: Request<WatchInput>) -> Result<Response<Self::watchAlertsStream>, Status> {
+        let rule_ids = request.into_inner().rule_ids;
+        let mut receiver = self.sender.subscribe();
+        let (tx, rx) = mpsc::channel(WATCH_BUFFER);
+
+        tokio::spawn(async move {
+            loop {
+                let event = match receiver.recv().await {
+                    Ok(event) => event,
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!("alert watcher lagged, {skipped} events skipped");
+                        continue;
+                    },
+                    Err(broadcast::error::RecvError::Closed) => break
+                };
+
+                if !rule_ids.is_empty() && !rule_ids.contains(&event.rule_id) {
+                    continue;
+                }
+
+                // the client closed the stream
+                if tx.send(Ok(event)).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio_stream::StreamExt;
+
+    async fn get_handler() -> AlertHandler {
+        let pool = db::connect("../config.toml").await.unwrap();
+        store::create_tables(&pool).await.unwrap();
+        let (sender, _) = broadcast::channel(8);
+
+        AlertHandler { pool: Arc::new(pool), sender }
+    }
+
+    #[tokio::test]
+    async fn expect_to_create_and_delete_rule() {
+        let handler = get_handler().await;
+        let input = RuleInput {
+            metric: Metric::IcuOccupancy as i32,
+            department: None,
+            region: Some(11),
+            operator: Operator::Above as i32,
+            threshold: 80.0
+        };
+
+        let rule = handler.create_rule(Request::new(input)).await.unwrap().into_inner();
+        let rules = handler.list_rules(Request::new(ListRulesInput {})).await.unwrap().into_inner().rules;
+        assert!(rules.iter().any(|r| r.id == rule.id && r.region == Some(11)));
+
+        let deleted = handler.delete_rule(Request::new(RuleId { id: rule.id })).await.unwrap().into_inner();
+        assert_eq!(deleted.id, rule.id);
+        assert!(handler.delete_rule(Request::new(RuleId { id: rule.id })).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn expect_invalid_rule_to_return_error() {
+        let handler = get_handler().await;
+        let input = RuleInput {
+            metric: Metric::WeeklyIncidence as i32,
+            department: None,
+            region: Some(11),
+            operator: Operator::Above as i32,
+            threshold: 250.0
+        };
+
+        assert!(handler.create_rule(Request::new(input)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn expect_watcher_to_receive_events_of_its_rules() {
+        let handler = get_handler().await;
+        let res = handler.watch_alerts(Request::new(WatchInput { rule_ids: vec![2] })).await.unwrap();
+        let mut stream = res.into_inner();
+
+        let event = |rule_id| AlertEvent {
+            rule_id,
+            metric: Metric::Hospitalization as i32,
+            department: Some("75".to_owned()),
+            region: None,
+            operator: Operator::Above as i32,
+            threshold: 100.0,
+            day: "2022-02-28".to_owned(),
+            value: 120.0
+        };
+
+        handler.sender.send(event(1)).unwrap();
+        handler.sender.send(event(2)).unwrap();
+
+        let received = stream.next().await.unwrap().unwrap();
+        assert_eq!(received.rule_id, 2);
+    }
+}
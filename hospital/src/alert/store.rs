@@ -0,0 +1,151 @@
+use db::PGPool;
+use sqlx::{postgres::PgRow, Postgres, Row, Transaction};
+use utils::err::MaskErr;
+use super::proto_alert::{Rule, RuleInput, AlertEvent};
+
+// The metric & the operator are saved with the value of their protobuf enum
+const CREATE_RULE_TABLE: &str = "CREATE TABLE IF NOT EXISTS alert_rule (
+    id BIGSERIAL PRIMARY KEY,
+    metric INTEGER NOT NULL,
+    dep TEXT,
+    reg INTEGER,
+    operator INTEGER NOT NULL,
+    threshold DOUBLE PRECISION NOT NULL,
+    triggered BOOLEAN NOT NULL DEFAULT false,
+    created_at TIMESTAMP NOT NULL DEFAULT now()
+)";
+
+const CREATE_EVENT_TABLE: &str = "CREATE TABLE IF NOT EXISTS alert_event (
+    id BIGSERIAL PRIMARY KEY,
+    rule_id BIGINT NOT NULL REFERENCES alert_rule (id) ON DELETE CASCADE,
+    jour TEXT NOT NULL,
+    value DOUBLE PRECISION NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT now()
+)";
+
+impl TryFrom<PgRow> for Rule {
+    type Error = sqlx::Error;
+
+    fn try_from(value: PgRow) -> Result<Self, Self::Error> {
+        let res = Self {
+            id: value.try_get("id")?,
+            metric: value.try_get("metric")?,
+            department: value.try_get("dep")?,
+            region: value.try_get("reg")?,
+            operator: value.try_get("operator")?,
+            threshold: value.try_get("threshold")?,
+            triggered: value.try_get("triggered")?
+        };
+
+        Ok(res)
+    }
+}
+
+/// Create the tables of the alert rules & events if they don't exist
+///
+/// # Arguments
+/// * `pool` - &PGPool
+pub async fn create_tables(pool: &PGPool) -> Result<(), MaskErr> {
+    sqlx::query(CREATE_RULE_TABLE).execute(pool).await?;
+    sqlx::query(CREATE_EVENT_TABLE).execute(pool).await?;
+
+    Ok(())
+}
+
+/// Save a new rule
+///
+/// # Arguments
+/// * `pool` - &PGPool
+/// * `input` - RuleInput
+pub async fn insert_rule(pool: &PGPool, input: RuleInput) -> Result<Rule, MaskErr> {
+    let row = sqlx::query(
+        "INSERT INTO alert_rule (metric, dep, reg, operator, threshold) VALUES ($1, $2, $3, $4, $5) RETURNING *"
+    )
+        .bind(input.metric)
+        .bind(input.department)
+        .bind(input.region)
+        .bind(input.operator)
+        .bind(input.threshold)
+        .fetch_one(pool)
+        .await?;
+
+    Ok(Rule::try_from(row)?)
+}
+
+/// Return every rule
+///
+/// # Arguments
+/// * `pool` - &PGPool
+pub async fn get_rules(pool: &PGPool) -> Result<Vec<Rule>, MaskErr> {
+    let rows = sqlx::query("SELECT * FROM alert_rule ORDER BY id")
+        .fetch_all(pool)
+        .await?;
+
+    let rules = rows
+        .into_iter()
+        .map(Rule::try_from)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(rules)
+}
+
+/// Delete a rule and its events. Return None if the rule does not exist
+///
+/// # Arguments
+/// * `pool` - &PGPool
+/// * `id` - i64
+pub async fn delete_rule(pool: &PGPool, id: i64) -> Result<Option<Rule>, MaskErr> {
+    let row = sqlx::query("DELETE FROM alert_rule WHERE id = $1 RETURNING *")
+        .bind(id)
+        .fetch_optional(pool)
+        .await?;
+
+    Ok(row.map(Rule::try_from).transpose()?)
+}
+
+/// Lock a rule until the end of the transaction and return whether its last value crossed
+/// its threshold. Return None if the rule has been deleted
+///
+/// # Arguments
+/// * `tx` - &mut Transaction<'_, Postgres>
+/// * `id` - i64
+pub async fn lock_rule(tx: &mut Transaction<'_, Postgres>, id: i64) -> Result<Option<bool>, MaskErr> {
+    let triggered = sqlx::query_scalar("SELECT triggered FROM alert_rule WHERE id = $1 FOR UPDATE")
+        .bind(id)
+        .fetch_optional(tx)
+        .await?;
+
+    Ok(triggered)
+}
+
+/// Update whether the last value of a rule crossed its threshold
+///
+/// # Arguments
+/// * `tx` - &mut Transaction<'_, Postgres>
+/// * `id` - i64
+/// * `triggered` - bool
+pub async fn set_triggered(tx: &mut Transaction<'_, Postgres>, id: i64, triggered: bool) -> Result<(), MaskErr> {
+    sqlx::query("UPDATE alert_rule SET triggered = $2 WHERE id = $1")
+        .bind(id)
+        .bind(triggered)
+        .execute(tx)
+        .await?;
+
+    Ok(())
+}
+
+/// Save an event
+///
+/// # Arguments
+/// * `tx` - &mut Transaction<'_, Postgres>
+/// * `event` - &AlertEvent
+pub async fn insert_event(tx: &mut Transaction<'_, Postgres>, event: &AlertEvent) -> Result<(), MaskErr> {
+    sqlx::query("INSERT INTO alert_event (rule_id, jour, value) VALUES ($1, $2, $3)")
+        .bind(event.rule_id)
+        .bind(&event.day)
+        .bind(event.value)
+        .execute(tx)
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,205 @@
+use std::{convert::Infallible, sync::Arc};
+use db::PGPool;
+use export::{Export, Format};
+use hyper::{Body, Method, Request, Response, Server, StatusCode, header};
+use hyper::service::{make_service_fn, service_fn};
+use tokio_stream::wrappers::ReceiverStream;
+use utils::err::MaskErr;
+use crate::common::proto_common::CommonInput;
+use super::rows::{self, Filters};
+
+// Port of the export listener
+const EXPORT_PORT: i32 = 9001;
+const EXPORT_PATH: &str = "/export/";
+
+/// Parameters of the query string of an export
+#[derive(Debug, Default, PartialEq)]
+struct ExportParams {
+    format: Option<String>,
+    date: Option<String>,
+    areas: Vec<String>
+}
+
+/// Parse the query string of an export (e.g. format=csv&date=2021-10&areas=75,13)
+///
+/// # Arguments
+/// * `query` - &str
+fn parse_params(query: &str) -> ExportParams {
+    let mut params = ExportParams::default();
+    for (key, value) in form_urlencoded::parse(query.as_bytes()) {
+        match key.as_ref() {
+            "format" => params.format = Some(value.into_owned()),
+            "date" => params.date = Some(value.into_owned()),
+            "areas" => params.areas.extend(value.split(',').filter(|a| !a.is_empty()).map(str::to_owned)),
+            _ => {}
+        }
+    }
+
+    params
+}
+
+/// Parse the date of an export which is either a day (2021-10-01), a month (2021-10) or
+/// `latest` for the last day available
+///
+/// # Arguments
+/// * `value` - &str
+fn parse_date(value: &str) -> Result<CommonInput, MaskErr> {
+    if value == "latest" {
+        return Ok(CommonInput { day: None, month: 0, year: 0, latest: true });
+    }
+
+    let parts = value
+        .split('-')
+        .map(|p| p.parse::<i32>().map_err(|_| MaskErr::InvalidDate))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    match parts[..] {
+        [year, month] => Ok(CommonInput { day: None, month, year, latest: false }),
+        [year, month, day] => Ok(CommonInput { day: Some(day), month, year, latest: false }),
+        _ => Err(MaskErr::InvalidDate)
+    }
+}
+
+/// Return the status of an error
+///
+/// # Arguments
+/// * `err` - &MaskErr
+fn status_code(err: &MaskErr) -> StatusCode {
+    match err {
+        MaskErr::QueryError(_) | MaskErr::IO(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        MaskErr::DbUnavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+        _ => StatusCode::BAD_REQUEST
+    }
+}
+
+/// Build the response streaming the export of a dataset
+///
+/// # Arguments
+/// * `pool` - Arc<PGPool>
+/// * `name` - &str
+/// * `query` - &str
+async fn export_dataset(pool: Arc<PGPool>, name: &str, query: &str) -> Result<Response<Body>, MaskErr> {
+    let params = parse_params(query);
+    let format = params.format.as_deref().unwrap_or("csv").parse::<Format>()?;
+    let date = params.date.as_deref().map(parse_date).transpose()?;
+
+    let Filters { dataset, day, areas, resolved } = rows::resolve_filters(&pool, name, date, params.areas).await?;
+    let mut res = Response::builder()
+        .header(header::CONTENT_TYPE, format.content_type())
+        .header(header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}.{}\"", dataset.name, format.extension()));
+
+    for (key, value) in metadata::build_headers(&pool, &[&dataset.name], resolved).await {
+        res = res.header(key, value);
+    }
+
+    // an error while streaming aborts the response
+    let chunks = export::stream(pool, Export { dataset, format, day, areas });
+    res.body(Body::wrap_stream(ReceiverStream::new(chunks)))
+        .map_err(|err| MaskErr::IO(err.to_string()))
+}
+
+/// Handle a request of the export listener. The exports are served on /export/<dataset>
+///
+/// # Arguments
+/// * `pool` - Arc<PGPool>
+/// * `req` - Request<Body>
+async fn handle(pool: Arc<PGPool>, req: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let name = match (req.method(), req.uri().path().strip_prefix(EXPORT_PATH)) {
+        (&Method::GET, Some(name)) => name,
+        _ => {
+            let mut res = Response::new(Body::empty());
+            *res.status_mut() = StatusCode::NOT_FOUND;
+            return Ok(res);
+        }
+    };
+
+    let res = match export_dataset(pool, name, req.uri().query().unwrap_or_default()).await {
+        Ok(res) => res,
+        Err(err) => {
+            let mut res = Response::new(Body::from(err.to_string()));
+            *res.status_mut() = status_code(&err);
+            res
+        }
+    };
+
+    Ok(res)
+}
+
+/// Run the HTTP listener serving the exports of the datasets
+///
+/// # Arguments
+/// * `pool` - Arc<PGPool>
+pub async fn run_export_server(pool: Arc<PGPool>) {
+    let addr = match utils::get_server_addr(EXPORT_PORT).parse() {
+        Ok(addr) => addr,
+        Err(err) => {
+            error!("invalid address of the export listener {:?}", err);
+            return;
+        }
+    };
+
+    let service = make_service_fn(move |_| {
+        let pool = Arc::clone(&pool);
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req| handle(Arc::clone(&pool), req)))
+        }
+    });
+
+    if let Err(err) = Server::bind(&addr).serve(service).await {
+        error!("export listener stopped {:?}", err);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn get_pool() -> Arc<PGPool> {
+        Arc::new(db::connect("../config.toml").await.unwrap())
+    }
+
+    #[test]
+    fn expect_to_parse_params() {
+        let params = parse_params("format=parquet&date=2021-10&areas=75%2C13&areas=93");
+        assert_eq!(params.format.as_deref(), Some("parquet"));
+        assert_eq!(params.date.as_deref(), Some("2021-10"));
+        assert_eq!(params.areas, vec!["75", "13", "93"]);
+    }
+
+    #[test]
+    fn expect_to_parse_date() {
+        assert_eq!(parse_date("2021-10-01").unwrap().day, Some(1));
+        assert_eq!(parse_date("2021-10").unwrap().day, None);
+        assert!(parse_date("latest").unwrap().latest);
+        assert!(parse_date("october").is_err());
+    }
+
+    #[tokio::test]
+    async fn expect_http_to_stream_csv() {
+        let req = Request::get("/export/cases?date=2021-10-01&areas=75,13").body(Body::empty()).unwrap();
+        let res = handle(get_pool().await, req).await.unwrap();
+
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers()[header::CONTENT_TYPE], "text/csv");
+        assert_eq!(res.headers()["x-dataset"], "cases");
+        assert!(res.headers().contains_key("x-dataset-version"));
+        assert!(res.headers().contains_key("x-last-available-date"));
+
+        let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let csv = String::from_utf8(body.to_vec()).unwrap();
+        assert_eq!(csv.lines().count(), 3);
+    }
+
+    #[tokio::test]
+    async fn expect_http_to_return_error() {
+        let pool = get_pool().await;
+
+        let req = Request::get("/export/cases?date=2021-10-01&format=xlsx").body(Body::empty()).unwrap();
+        let res = handle(Arc::clone(&pool), req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+        let req = Request::get("/datasets").body(Body::empty()).unwrap();
+        let res = handle(pool, req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+}
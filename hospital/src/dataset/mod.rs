@@ -0,0 +1,10 @@
+pub mod rows;
+pub mod http;
+
+// the stream type of the export rpc is named after the camel case rpc
+#[allow(non_camel_case_types)]
+pub mod proto_dataset {
+    tonic::include_proto!("dataset");
+}
+
+pub use crate::common::proto_common as common;
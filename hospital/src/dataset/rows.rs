@@ -0,0 +1,309 @@
+use std::{pin::Pin, sync::Arc};
+use db::PGPool;
+use export::{Export, Format};
+use registry::{Level, row::{self, DatasetRow}};
+use tokio_stream::{Stream, StreamExt, wrappers::ReceiverStream};
+use tonic::{Request, Response, Status};
+use utils::{Date, err::MaskErr};
+use crate::common::proto_common::CommonInput;
+use super::proto_dataset::dataset_service_server::DatasetService;
+use super::proto_dataset::{
+    ExportInput,
+    ExportChunk,
+    ExportFormat,
+    DatasetsInput,
+    DatasetList,
+    Dataset,
+    Column,
+    RowsInput,
+    RowsOutput,
+    Row,
+    Value,
+    value::Kind
+};
+
+pub struct DatasetHandler {
+    pub pool: Arc<PGPool>
+}
+
+/// Validated filters of the rows of a dataset
+pub struct Filters {
+    pub dataset: &'static registry::Dataset,
+    // day or month used with a LIKE
+    pub day: Option<String>,
+    pub areas: Option<Vec<String>>,
+    // day used when the latest day is requested
+    pub resolved: Option<String>
+}
+
+impl From<ExportFormat> for Format {
+    fn from(f: ExportFormat) -> Self {
+        match f {
+            ExportFormat::Csv => Format::Csv,
+            ExportFormat::Arrow => Format::Arrow,
+            ExportFormat::Parquet => Format::Parquet
+        }
+    }
+}
+
+impl From<&registry::Column> for Column {
+    fn from(c: &registry::Column) -> Self {
+        Column { name: c.name.clone(), r#type: c.kind.as_str().to_owned() }
+    }
+}
+
+impl From<&registry::Dataset> for Dataset {
+    fn from(d: &registry::Dataset) -> Self {
+        Dataset {
+            name: d.name.clone(),
+            url: d.url.clone(),
+            key: d.key.clone(),
+            day: d.day.clone(),
+            level: d.geography.level.as_str().to_owned(),
+            columns: d.columns.iter().map(Column::from).collect()
+        }
+    }
+}
+
+impl From<DatasetRow> for Row {
+    fn from(r: DatasetRow) -> Self {
+        let values = r.values
+            .into_iter()
+            .map(|v| {
+                let kind = v.map(|v| match v {
+                    row::Value::Int(v) => Kind::Int(v),
+                    row::Value::Float(v) => Kind::Float(v),
+                    row::Value::Text(v) => Kind::Text(v)
+                });
+
+                Value { kind }
+            })
+            .collect();
+
+        Row { values }
+    }
+}
+
+/// Check that the areas exist at the level of the dataset. Return None when every
+/// area is requested
+///
+/// # Arguments
+/// * `dataset` - &registry::Dataset
+/// * `areas` - Vec<String>
+fn validate_areas(dataset: &registry::Dataset, areas: Vec<String>) -> Result<Option<Vec<String>>, MaskErr> {
+    if areas.is_empty() {
+        return Ok(None);
+    }
+
+    for area in &areas {
+        // the unknown areas are reported on the field of the input whatever the level
+        let unknown = || MaskErr::UnknownGeography { field: "areas".to_owned(), code: area.to_owned() };
+        match dataset.geography.level {
+            Level::Department => {
+                geography::validate_department(area).map_err(|_| unknown())?;
+            },
+            Level::Region => {
+                let code = area.parse::<i32>().map_err(|_| unknown())?;
+                geography::validate_region(code).map_err(|_| unknown())?;
+            },
+            Level::Country => {
+                return Err(MaskErr::InvalidParam(format!("the dataset {} can't be filtered by area", dataset.name)));
+            }
+        }
+    }
+
+    Ok(Some(areas))
+}
+
+/// Validate the dataset and the filters of its rows. The date is required for the datasets
+/// with days and is resolved when the latest day is requested
+///
+/// # Arguments
+/// * `pool` - &PGPool
+/// * `name` - &str
+/// * `date` - Option<CommonInput>
+/// * `areas` - Vec<String>
+pub async fn resolve_filters(pool: &PGPool, name: &str, date: Option<CommonInput>, areas: Vec<String>) -> Result<Filters, MaskErr> {
+    let dataset = registry::validate_dataset(name)?;
+    let areas = validate_areas(dataset, areas)?;
+
+    let (day, resolved) = match (&dataset.day, date) {
+        (None, _) => (None, None),
+        (Some(_), None) => return Err(MaskErr::MissingDate),
+        (Some(_), Some(date)) => {
+            let (date, resolved) = metadata::resolve_date(pool, &dataset.name, date).await?;
+            (Some(date.build_date_sql_like()?), resolved)
+        }
+    };
+
+    Ok(Filters { dataset, day, areas, resolved })
+}
+
+#[tonic::async_trait]
+impl DatasetService for DatasetHandler {
+    /// List the datasets of the registry
+    ///
+    /// # Arguments
+    /// * `&self`
+    /// * `_request` - Request<DatasetsInput>
+    async fn list_datasets(&self, _request: Request<DatasetsInput>) -> Result<Response<DatasetList>, Status> {
+        let datasets = registry::get_datasets()
+            .iter()
+            .map(Dataset::from)
+            .collect();
+
+        Ok(Response::new(DatasetList { datasets }))
+    }
+
+    /// Return the rows of a dataset for a day or a month, optionally filtered by area
+    ///
+    /// # Arguments
+    /// * `&self`
+    /// * `request` - Request<RowsInput>
+    async fn get_dataset_rows(&self, request: Request<RowsInput>) -> Result<Response<RowsOutput>, Status> {
+        let input = request.into_inner();
+        let Filters { dataset, day, areas, resolved } = resolve_filters(
+            &self.pool,
+            &input.dataset,
+            input.date,
+            input.areas
+        ).await?;
+
+        match row::get_rows(&self.pool, dataset, day.as_deref(), areas.as_deref()).await {
+            Ok(rows) => {
+                let output = RowsOutput {
+                    columns: dataset.columns.iter().map(Column::from).collect(),
+                    rows: rows.into_iter().map(Row::from).collect()
+                };
+
+                Ok(metadata::respond_with_date(&self.pool, &[&dataset.name], resolved, output).await)
+            },
+            Err(err) => Err(MaskErr::query(&format!("rows of {}", dataset.name), err).into())
+        }
+    }
+
+    type exportStream = Pin<Box<dyn Stream<Item = Result<ExportChunk, Status>> + Send>>;
+
+    /// Stream the rows of a dataset encoded as CSV, Arrow or Parquet. The rows are encoded
+    /// by chunks as they are fetched
+    ///
+    /// # Arguments
+    /// * `&self`
+    /// * `request` - Request<ExportInput>
+    async fn export(&self, request: Request<ExportInput>) -> Result<Response<Self::exportStream>, Status> {
+        let input = request.into_inner();
+        let format = ExportFormat::from_i32(input.format)
+            .ok_or_else(|| MaskErr::InvalidParam(format!("the format {} is unknown", input.format)))?;
+
+        let Filters { dataset, day, areas, resolved } = resolve_filters(
+            &self.pool,
+            &input.dataset,
+            input.date,
+            input.areas
+        ).await?;
+
+        let export = Export { dataset, format: format.into(), day, areas };
+        // the error of the stream is the status required by tonic
+        #[allow(clippy::result_large_err)]
+        let chunks = ReceiverStream::new(export::stream(Arc::clone(&self.pool), export))
+            .map(|chunk| chunk.map(|data| ExportChunk { data }).map_err(Status::from));
+
+        Ok(metadata::respond_with_date(&self.pool, &[&dataset.name], resolved, Box::pin(chunks) as Self::exportStream).await)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::common::CommonInput;
+    use tokio_stream::StreamExt;
+
+    async fn get_handler() -> DatasetHandler {
+        let pool = db::connect("../config.toml").await.unwrap();
+
+        DatasetHandler { pool: Arc::new(pool) }
+    }
+
+    #[tokio::test]
+    async fn expect_grpc_to_list_datasets() {
+        let handler = get_handler().await;
+        let list = handler.list_datasets(Request::new(DatasetsInput {})).await.unwrap().into_inner();
+
+        let cases = list.datasets.iter().find(|d| d.name == "cases").unwrap();
+        assert_eq!(cases.level, "department");
+        assert_eq!(cases.day.as_deref(), Some("jour"));
+    }
+
+    #[tokio::test]
+    async fn expect_grpc_to_return_rows_of_departments() {
+        let handler = get_handler().await;
+        let input = RowsInput {
+            dataset: "cases".to_owned(),
+            date: Some(CommonInput { day: Some(1), month: 10, year: 2021, latest: false }),
+            areas: vec!["75".to_owned(), "13".to_owned()]
+        };
+
+        let output = handler.get_dataset_rows(Request::new(input)).await.unwrap().into_inner();
+        assert_eq!(output.columns[0].name, "dep");
+        assert_eq!(output.rows.len(), 2);
+        assert_eq!(output.rows[0].values[0].kind, Some(Kind::Text("13".to_owned())));
+    }
+
+    #[tokio::test]
+    async fn expect_grpc_to_return_error() {
+        let handler = get_handler().await;
+        let date = Some(CommonInput { day: Some(1), month: 10, year: 2021, latest: false });
+
+        let unknown = RowsInput { dataset: "unknown".to_owned(), date: date.clone(), areas: Vec::new() };
+        let res = handler.get_dataset_rows(Request::new(unknown)).await;
+        assert_eq!(res.unwrap_err().code(), tonic::Code::InvalidArgument);
+
+        let missing_date = RowsInput { dataset: "cases".to_owned(), date: None, areas: Vec::new() };
+        assert!(handler.get_dataset_rows(Request::new(missing_date)).await.is_err());
+
+        let country = RowsInput { dataset: "vaxx".to_owned(), date, areas: vec!["75".to_owned()] };
+        let res = handler.get_dataset_rows(Request::new(country)).await;
+        assert_eq!(res.unwrap_err().code(), tonic::Code::InvalidArgument);
+    }
+
+    #[test]
+    fn expect_unknown_areas_to_be_reported_on_the_areas_field() {
+        for (dataset, area) in [("cases", "999"), ("hospitalization", "99"), ("hospitalization", "idf")] {
+            let dataset = registry::get_dataset(dataset).unwrap();
+            match validate_areas(dataset, vec![area.to_owned()]) {
+                Err(MaskErr::UnknownGeography { field, code }) => assert_eq!((field.as_str(), code.as_str()), ("areas", area)),
+                res => panic!("unexpected result {:?}", res.map(|_| ()))
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn expect_grpc_to_stream_export() {
+        let handler = get_handler().await;
+        let input = ExportInput {
+            dataset: "cases".to_owned(),
+            date: Some(CommonInput { day: None, month: 10, year: 2021, latest: false }),
+            areas: vec!["75".to_owned()],
+            format: ExportFormat::Csv as i32
+        };
+
+        let mut stream = handler.export(Request::new(input)).await.unwrap().into_inner();
+        let mut data = Vec::new();
+        while let Some(chunk) = stream.next().await {
+            data.extend(chunk.unwrap().data);
+        }
+
+        let csv = String::from_utf8(data).unwrap();
+        assert!(csv.starts_with("dep,jour,"));
+        assert_eq!(csv.lines().count(), 32);
+    }
+
+    #[tokio::test]
+    async fn expect_export_to_return_error() {
+        let handler = get_handler().await;
+        let input = ExportInput { dataset: "cases".to_owned(), date: None, areas: Vec::new(), format: 7 };
+
+        let res = handler.export(Request::new(input)).await;
+        assert_eq!(res.err().unwrap().code(), tonic::Code::InvalidArgument);
+    }
+}
@@ -0,0 +1,7 @@
+pub mod occupancy;
+
+pub mod proto_forecast {
+    tonic::include_proto!("forecast");
+}
+
+pub use crate::common::proto_common as common;
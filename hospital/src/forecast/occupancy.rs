@@ -0,0 +1,287 @@
+use std::sync::Arc;
+use analytics::forecast::Holt;
+use chrono::{Duration, NaiveDate};
+use db::{PGPool, query};
+use sqlx::{postgres::PgRow, Row};
+use tonic::{Request, Response, Status};
+use utils::{
+    Date,
+    err::MaskErr
+};
+use super::proto_forecast::forecast_service_server::ForecastService;
+use super::proto_forecast::{ForecastInput, ForecastOutput, ForecastResult, OccupancyForecast};
+
+const DEFAULT_HORIZON: u32 = 7;
+const MAX_HORIZON: u32 = 14;
+const DEFAULT_HISTORY: u32 = 28;
+const MIN_HISTORY: u32 = 4;
+const MAX_HISTORY: u32 = 90;
+
+pub struct ForecastHandler {
+    pub pool: Arc<PGPool>
+}
+
+/// Number of people in hospital & in ICU of a day
+struct Occupancy {
+    day: NaiveDate,
+    hosp: i64,
+    rea: i64
+}
+
+impl TryFrom<PgRow> for Occupancy {
+    type Error = sqlx::Error;
+
+    fn try_from(value: PgRow) -> Result<Self, Self::Error> {
+        let jour: String = value.try_get("jour")?;
+        let res = Self {
+            day: NaiveDate::parse_from_str(&jour, "%Y-%m-%d").map_err(|err| sqlx::Error::Decode(Box::new(err)))?,
+            hosp: value.try_get("hosp")?,
+            rea: value.try_get("rea")?
+        };
+
+        Ok(res)
+    }
+}
+
+#[tonic::async_trait]
+impl ForecastService for ForecastHandler {
+    /// Forecast the number of people in hospital & in ICU of a department or a region
+    /// for the days following the last day observed up to the given day. The forecast uses the
+    /// Holt linear trend method fitted on the days preceding the last observed day (see analytics::forecast).
+    /// The missing days of the history are linearly interpolated
+    ///
+    /// # Arguments
+    /// * `&self`
+    /// * `request` - Request<ForecastInput>
+    async fn get_occupancy_forecast(
+        &self,
+        request: Request<ForecastInput>
+    ) -> Result<Response<ForecastOutput>, Status> {
+        let input = request.into_inner();
+        if input.date.is_none() {
+            return Err(MaskErr::MissingDate.into());
+        }
+
+        let horizon = match input.horizon {
+            0 => DEFAULT_HORIZON,
+            h if h <= MAX_HORIZON => h,
+            _ => return Err(MaskErr::InvalidParam(format!("the horizon can't exceed {MAX_HORIZON} days")).into())
+        };
+
+        let history = match input.history {
+            0 => DEFAULT_HISTORY,
+            h if (MIN_HISTORY..=MAX_HISTORY).contains(&h) => h,
+            _ => return Err(MaskErr::InvalidParam(format!("the history should be between {MIN_HISTORY} and {MAX_HISTORY} days")).into())
+        };
+
+        let dataset = if input.department.is_some() { "hospital_dep" } else { "hospitalization" };
+        let (date, resolved) = metadata::resolve_date(&self.pool, dataset, input.date.unwrap()).await?;
+        let day = date.build_naive_date()?;
+        let from = (day - Duration::days(history as i64 - 1)).format("%Y-%m-%d").to_string();
+        let to = day.format("%Y-%m-%d").to_string();
+
+        let res = match (input.department, input.region) {
+            (Some(department), _) => {
+                geography::validate_department(&department)?;
+                query::get_all_by_date_range_and_gen_field::<Occupancy, String>(
+                    &self.pool,
+                    "SELECT jour, hosp, rea FROM hospital_dep WHERE jour >= $1 AND jour <= $2 AND dep = $3 AND sexe = 0 ORDER BY jour",
+                    &from,
+                    &to,
+                    department
+                ).await
+            },
+            (None, Some(region)) => {
+                geography::validate_region(region)?;
+                query::get_all_by_date_range_and_gen_field::<Occupancy, i32>(
+                    &self.pool,
+                    "SELECT jour, hosp, rea FROM hospitalization WHERE jour >= $1 AND jour <= $2 AND reg = $3 AND cl_age90 = 0 ORDER BY jour",
+                    &from,
+                    &to,
+                    region
+                ).await
+            },
+            (None, None) => return Err(MaskErr::MissingParam("department or region".to_owned()).into())
+        };
+
+        let occupancy = match res {
+            Ok(occupancy) => occupancy,
+            Err(err) => return Err(MaskErr::query("occupancy history", err).into())
+        };
+
+        // the horizon starts after the last observed day which can be before the requested day
+        let last = match occupancy.last() {
+            Some(o) => o.day,
+            None => return Err(MaskErr::InvalidParam(format!("no occupancy is available between {from} and {to}")).into())
+        };
+
+        let days = utils::get_days_between(last + Duration::days(1), last + Duration::days(horizon as i64));
+        let hosp = interpolate(&occupancy.iter().map(|o| (o.day, o.hosp as f64)).collect::<Vec<_>>());
+        let rea = interpolate(&occupancy.iter().map(|o| (o.day, o.rea as f64)).collect::<Vec<_>>());
+
+        let output = ForecastOutput {
+            hosp: Some(forecast_series(&hosp, &days)?),
+            rea: Some(forecast_series(&rea, &days)?)
+        };
+
+        Ok(metadata::respond_with_date(&self.pool, &[dataset], resolved, output).await)
+    }
+}
+
+/// Build the daily series of values ordered by day. The values of the missing days are
+/// linearly interpolated between the surrounding observed days
+///
+/// # Arguments
+/// * `observed` - &[(NaiveDate, f64)]
+fn interpolate(observed: &[(NaiveDate, f64)]) -> Vec<f64> {
+    let mut series = Vec::new();
+    for (idx, (day, value)) in observed.iter().enumerate() {
+        if let Some((prev_day, prev_value)) = idx.checked_sub(1).map(|i| observed[i]) {
+            let gap = (*day - prev_day).num_days();
+            for missing in 1..gap {
+                series.push(prev_value + (value - prev_value) * missing as f64 / gap as f64);
+            }
+        }
+
+        series.push(*value);
+    }
+
+    series
+}
+
+/// Fit a Holt model on a series and forecast the given days
+///
+/// # Arguments
+/// * `series` - &[f64]
+/// * `days` - &[String]
+fn forecast_series(series: &[f64], days: &[String]) -> Result<OccupancyForecast, MaskErr> {
+    let model = Holt::fit(series)
+        .map_err(|err| MaskErr::InvalidParam(err.to_string()))?;
+
+    let forecast = days
+        .iter()
+        .zip(model.forecast(days.len(), true))
+        .map(|(day, f)| ForecastResult {
+            day: day.to_owned(),
+            value: f.value,
+            lower: f.lower,
+            upper: f.upper
+        })
+        .collect();
+
+    Ok(OccupancyForecast {
+        forecast,
+        alpha: model.alpha,
+        beta: model.beta
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::common::CommonInput as FCommonInput;
+
+    fn date() -> Option<FCommonInput> {
+        Some(FCommonInput {
+            day: Some(15),
+            month: 12,
+            year: 2021,
+            latest: false
+        })
+    }
+
+    #[tokio::test]
+    async fn expect_grpc_to_return_forecast_for_department() {
+        let pool = db::connect("../config.toml").await.unwrap();
+        let pool_arc = Arc::new(pool);
+        let service = ForecastHandler { pool: Arc::clone(&pool_arc) };
+
+        let input = ForecastInput {
+            date: date(),
+            department: Some("75".to_owned()),
+            region: None,
+            horizon: 14,
+            history: 0
+        };
+
+        let request = Request::new(input);
+        let res = service.get_occupancy_forecast(request).await.unwrap();
+        let hosp = res.get_ref().hosp.as_ref().unwrap();
+
+        assert_eq!(hosp.forecast.len(), 14);
+        assert_eq!(hosp.forecast[0].day, "2021-12-16");
+    }
+
+    #[tokio::test]
+    async fn expect_grpc_to_return_forecast_for_region() {
+        let pool = db::connect("../config.toml").await.unwrap();
+        let pool_arc = Arc::new(pool);
+        let service = ForecastHandler { pool: Arc::clone(&pool_arc) };
+
+        let input = ForecastInput {
+            date: date(),
+            department: None,
+            region: Some(11),
+            horizon: 0,
+            history: 14
+        };
+
+        let request = Request::new(input);
+        let res = service.get_occupancy_forecast(request).await;
+
+        assert!(res.is_ok());
+    }
+
+    #[test]
+    fn expect_missing_days_to_be_interpolated() {
+        let day = |d| NaiveDate::from_ymd_opt(2021, 12, d).unwrap();
+        let series = interpolate(&[(day(1), 10.0), (day(2), 12.0), (day(5), 18.0), (day(6), 17.0)]);
+
+        assert_eq!(series, vec![10.0, 12.0, 14.0, 16.0, 18.0, 17.0]);
+    }
+
+    #[tokio::test]
+    async fn expect_horizon_to_start_after_last_observed_day() {
+        let pool = db::connect("../config.toml").await.unwrap();
+        let last: String = sqlx::query_scalar("SELECT MAX(jour) FROM hospital_dep WHERE dep = '75' AND sexe = 0")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        let last = NaiveDate::parse_from_str(&last, "%Y-%m-%d").unwrap();
+
+        let pool_arc = Arc::new(pool);
+        let service = ForecastHandler { pool: Arc::clone(&pool_arc) };
+        let input = ForecastInput {
+            date: Some(FCommonInput { day: None, month: 0, year: 0, latest: true }),
+            department: Some("75".to_owned()),
+            region: None,
+            horizon: 3,
+            history: 0
+        };
+
+        let res = service.get_occupancy_forecast(Request::new(input)).await.unwrap();
+        let hosp = res.get_ref().hosp.as_ref().unwrap();
+
+        assert_eq!(hosp.forecast[0].day, (last + Duration::days(1)).format("%Y-%m-%d").to_string());
+    }
+
+    #[tokio::test]
+    async fn expect_grpc_to_return_error_for_invalid_horizon() {
+        let pool = db::connect("../config.toml").await.unwrap();
+        let pool_arc = Arc::new(pool);
+        let service = ForecastHandler { pool: Arc::clone(&pool_arc) };
+
+        let input = ForecastInput {
+            date: date(),
+            department: Some("75".to_owned()),
+            region: None,
+            horizon: 30,
+            history: 0
+        };
+
+        let request = Request::new(input);
+        let res = service.get_occupancy_forecast(request).await;
+
+        assert_eq!(res.unwrap_err().code(), tonic::Code::InvalidArgument);
+    }
+}
@@ -6,12 +6,13 @@ use db::PGPool;
 use db::query;
 use utils::{
     Date,
-    err::MaskErr
+    err::MaskErr,
+    smoothing::{self, Smoothable}
 };
-use crate::common::proto_common::CommonInput;
+use super::common::AreaInput;
 
 // import generated struct by tonic
-use super::proto_newcase::{CaseInput, NewCases, CaseResult};
+use super::proto_newcase::{CaseInput, NewCases, CaseResult, DepartmentCases, SmoothedCases};
 use super::proto_newcase::case_service_server::CaseService;
 
 pub struct CaseServiceHandle {
@@ -27,17 +28,50 @@ impl TryFrom<PgRow> for CaseResult {
             new_entry_hospital: value.try_get("incid_hosp")?,
             new_entry_icu: value.try_get("incid_rea")?,
             death: value.try_get("incid_dc")?,
-            back_home: value.try_get("incid_rad")?
+            back_home: value.try_get("incid_rad")?,
+            department: value.try_get("dep").ok(),
+            trend: query::get_trend(&value).map(Into::into),
+            smoothed: None
         };
 
         Ok(res)
     }
 }
 
+impl Smoothable for CaseResult {
+    fn series(&self) -> String {
+        self.department.clone().unwrap_or_default()
+    }
+
+    fn day(&self) -> &str {
+        &self.date
+    }
+
+    fn values(&self) -> Vec<f64> {
+        vec![
+            self.new_entry_hospital as f64,
+            self.new_entry_icu as f64,
+            self.death as f64,
+            self.back_home as f64
+        ]
+    }
+
+    fn set_smoothed(&mut self, values: Vec<f64>) {
+        self.smoothed = Some(SmoothedCases {
+            new_entry_hospital: values[0],
+            new_entry_icu: values[1],
+            death: values[2],
+            back_home: values[3]
+        });
+    }
+}
+
 #[tonic::async_trait]
 impl CaseService for CaseServiceHandle {
     /// Return the number of new case by department.
     /// The day is optional. Hence we can query either per day or per month
+    /// Multiple departments can be queried at once, in this case the cases are grouped by department
+    /// The trend of the new entry in hospital and the rolling mean of the new cases can be included
     /// 
     /// # Arguments
     /// * `&self`
@@ -51,28 +85,97 @@ impl CaseService for CaseServiceHandle {
             return Err(MaskErr::MissingDate.into());
         }
 
-        let date: CommonInput = input.date.unwrap().into();
-        let date = date.build_date_sql_like()?;
+        let (input_date, resolved) = metadata::resolve_date(&self.pool, "cases", input.date.unwrap()).await?;
+        let date = input_date.build_date_sql_like()?;
+        let smoothing = smoothing::from_options(input.smoothing)?;
+        let (departments, multiple) = geography::resolve_departments(
+            &input.department,
+            &input.departments,
+            input.all_departments
+        )?;
 
-        match query::get_all_by_date_and_gen_field::<CaseResult, String>(
+        let sql = match input.include_trend {
+            true => query::build_trend_query("cases", "incid_hosp", "dep", "dep = ANY($2)"),
+            false => "SELECT * FROM cases WHERE jour LIKE $1 AND dep = ANY($2) ORDER BY dep, jour".to_owned()
+        };
+
+        let mut cases = match query::get_all_by_date_and_gen_field::<CaseResult, Vec<String>>(
             &self.pool,
-            "SELECT * FROM cases WHERE jour LIKE $1 AND dep = $2",
+            &sql,
             &date,
-            input.department
+            departments.clone()
         ).await {
-            Ok(cases) => Ok(Response::new(NewCases { cases })),
-            Err(err) => {
-                error!("fetch new cases error: {:?}", err);
-                return Err(MaskErr::QueryError("new case by department".into()).into());
+            Ok(cases) => cases,
+            Err(err) => return Err(MaskErr::query("new case by department", err).into())
+        };
+
+        if let Some(smoothing) = smoothing {
+            let (from, to) = smoothing.history_range(&input_date)?;
+            match query::get_all_by_date_range_and_gen_field::<CaseResult, Vec<String>>(
+                &self.pool,
+                "SELECT * FROM cases WHERE jour >= $1 AND jour <= $2 AND dep = ANY($3)",
+                &from.format("%Y-%m-%d").to_string(),
+                &to.format("%Y-%m-%d").to_string(),
+                departments
+            ).await {
+                Ok(history) => smoothing.apply(&mut cases, &history, from, to),
+                Err(err) => return Err(MaskErr::query("new case history", err).into())
             }
         }
+
+        if multiple {
+            let departments = geography::group_by_department(cases, |c| c.department.clone().unwrap_or_default())
+                .into_iter()
+                .map(|(department, cases)| DepartmentCases { department, cases })
+                .collect();
+
+            return Ok(metadata::respond_with_date(&self.pool, &["cases"], resolved, NewCases { cases: Vec::new(), departments }).await);
+        }
+
+        Ok(metadata::respond_with_date(&self.pool, &["cases"], resolved, NewCases { cases, departments: Vec::new() }).await)
+    }
+
+    /// Return the number of new case of the departments of a region, or of the whole
+    /// country if no region is given, summed by day
+    /// 
+    /// # Arguments
+    /// * `&self`
+    /// * `request` - Request<AreaInput>
+    async fn get_new_case_by_area(
+        &self,
+        request: Request<AreaInput>
+    ) -> Result<Response<NewCases>, Status> {
+        let input = request.into_inner();
+        if input.date.is_none() {
+            return Err(MaskErr::MissingDate.into());
+        }
+
+        let (date, resolved) = metadata::resolve_date(&self.pool, "cases", input.date.unwrap()).await?;
+        let date = date.build_date_sql_like()?;
+        let departments = geography::get_department_codes(input.region)?;
+
+        match query::get_all_by_date_and_gen_field::<CaseResult, Vec<String>>(
+            &self.pool,
+            "SELECT jour,
+                SUM(incid_hosp)::bigint AS incid_hosp,
+                SUM(incid_rea)::bigint AS incid_rea,
+                SUM(incid_dc)::bigint AS incid_dc,
+                SUM(incid_rad)::bigint AS incid_rad
+            FROM cases WHERE jour LIKE $1 AND dep = ANY($2)
+            GROUP BY jour ORDER BY jour",
+            &date,
+            departments
+        ).await {
+            Ok(cases) => Ok(metadata::respond_with_date(&self.pool, &["cases"], resolved, NewCases { cases, departments: Vec::new() }).await),
+            Err(err) => return Err(MaskErr::query("new case by area", err).into())
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use super::super::common::CommonInput;
+    use super::super::common::{CommonInput, SmoothingOptions, smoothing_options};
 
     #[tokio::test]
     async fn expect_grpc_to_return_response() {
@@ -86,9 +189,14 @@ mod tests {
             date: Some(CommonInput {
                 day: Some(12),
                 month: 12,
-                year: 2021
+                year: 2021,
+                latest: false
             }),
-            department: "77".to_owned()
+            department: "77".to_owned(),
+            departments: Vec::new(),
+            all_departments: false,
+            include_trend: false,
+            smoothing: None
         };
 
         let request = Request::new(input);
@@ -110,8 +218,13 @@ mod tests {
                 day: Some(50),
                 month: 12,
                 year: 2021,
+                latest: false,
             }),
-            department: "77".to_owned()
+            department: "77".to_owned(),
+            departments: Vec::new(),
+            all_departments: false,
+            include_trend: false,
+            smoothing: None
         };
 
         let request = Request::new(input);
@@ -119,4 +232,175 @@ mod tests {
 
         assert!(res.is_err());
     }
+
+    #[tokio::test]
+    async fn expect_grpc_to_return_error_for_unknown_department() {
+        let pool = db::connect("../config.toml").await.unwrap();
+        let pool_arc = Arc::new(pool);
+        let case_service = CaseServiceHandle {
+            pool: Arc::clone(&pool_arc)
+        };
+
+        let input = CaseInput {
+            date: Some(CommonInput {
+                day: Some(12),
+                month: 12,
+                year: 2021,
+                latest: false,
+            }),
+            department: "777".to_owned(),
+            departments: Vec::new(),
+            all_departments: false,
+            include_trend: false,
+            smoothing: None
+        };
+
+        let request = Request::new(input);
+        let res = case_service.get_new_case_by_department(request).await;
+
+        assert_eq!(res.unwrap_err().code(), tonic::Code::InvalidArgument);
+    }
+
+    #[tokio::test]
+    async fn expect_grpc_to_return_country_cases() {
+        let pool = db::connect("../config.toml").await.unwrap();
+        let pool_arc = Arc::new(pool);
+        let case_service = CaseServiceHandle {
+            pool: Arc::clone(&pool_arc)
+        };
+
+        let input = AreaInput {
+            date: Some(CommonInput {
+                day: None,
+                month: 12,
+                year: 2021,
+                latest: false,
+            }),
+            region: None
+        };
+
+        let request = Request::new(input);
+        let res = case_service.get_new_case_by_area(request).await;
+
+        assert!(res.is_ok());
+    }
+
+    #[tokio::test]
+    async fn expect_grpc_to_return_cases_grouped_by_department() {
+        let pool = db::connect("../config.toml").await.unwrap();
+        let pool_arc = Arc::new(pool);
+        let case_service = CaseServiceHandle {
+            pool: Arc::clone(&pool_arc)
+        };
+
+        let input = CaseInput {
+            date: Some(CommonInput {
+                day: Some(12),
+                month: 12,
+                year: 2021,
+                latest: false
+            }),
+            department: String::new(),
+            departments: vec!["77".to_owned(), "13".to_owned()],
+            all_departments: false,
+            include_trend: false,
+            smoothing: None
+        };
+
+        let request = Request::new(input);
+        let res = case_service.get_new_case_by_department(request).await.unwrap();
+
+        assert!(res.get_ref().cases.is_empty());
+        assert!(res.get_ref().departments.iter().all(|d| d.department == "77" || d.department == "13"));
+    }
+
+    #[tokio::test]
+    async fn expect_grpc_to_return_cases_with_trend() {
+        let pool = db::connect("../config.toml").await.unwrap();
+        let pool_arc = Arc::new(pool);
+        let case_service = CaseServiceHandle {
+            pool: Arc::clone(&pool_arc)
+        };
+
+        let input = CaseInput {
+            date: Some(CommonInput {
+                day: Some(12),
+                month: 12,
+                year: 2021,
+                latest: false
+            }),
+            department: "77".to_owned(),
+            departments: Vec::new(),
+            all_departments: false,
+            include_trend: true,
+            smoothing: None
+        };
+
+        let request = Request::new(input);
+        let res = case_service.get_new_case_by_department(request).await.unwrap();
+
+        assert!(res.get_ref().cases.iter().all(|c| c.trend.is_some()));
+    }
+
+    #[tokio::test]
+    async fn expect_grpc_to_return_smoothed_cases() {
+        let pool = db::connect("../config.toml").await.unwrap();
+        let pool_arc = Arc::new(pool);
+        let case_service = CaseServiceHandle {
+            pool: Arc::clone(&pool_arc)
+        };
+
+        let input = CaseInput {
+            date: Some(CommonInput {
+                day: None,
+                month: 12,
+                year: 2021,
+                latest: false
+            }),
+            department: "77".to_owned(),
+            departments: Vec::new(),
+            all_departments: false,
+            include_trend: false,
+            smoothing: Some(SmoothingOptions {
+                method: smoothing_options::Method::Centered as i32,
+                window: 7
+            })
+        };
+
+        let request = Request::new(input);
+        let res = case_service.get_new_case_by_department(request).await.unwrap();
+
+        assert!(res.get_ref().cases.iter().all(|c| c.smoothed.is_some()));
+    }
+
+    #[tokio::test]
+    async fn expect_grpc_to_return_error_for_even_centered_window() {
+        let pool = db::connect("../config.toml").await.unwrap();
+        let pool_arc = Arc::new(pool);
+        let case_service = CaseServiceHandle {
+            pool: Arc::clone(&pool_arc)
+        };
+
+        let input = CaseInput {
+            date: Some(CommonInput {
+                day: Some(12),
+                month: 12,
+                year: 2021,
+                latest: false
+            }),
+            department: "77".to_owned(),
+            departments: Vec::new(),
+            all_departments: false,
+            include_trend: false,
+            smoothing: Some(SmoothingOptions {
+                method: smoothing_options::Method::Centered as i32,
+                window: 6
+            })
+        };
+
+        let request = Request::new(input);
+        let res = case_service.get_new_case_by_department(request).await;
+
+        assert_eq!(res.unwrap_err().code(), tonic::Code::InvalidArgument);
+    }
 }
@@ -5,36 +5,45 @@ use sqlx::Row;
 use tonic::{Request, Response, Status};
 use utils::{
     Date,
-    err::MaskErr
+    err::MaskErr,
+    page::{Page, PageKey, Paginated}
 };
-use crate::common::proto_common::CommonInput;
+use super::common::{AreaInput, DepartmentRanking, RankingResult, ranking_options::Period};
 use super::proto_hospital::{
     level_service_server::LevelService,
     LevelInput,
-    LevelOutput, LevelResult, level_result::Sex
+    LevelOutput, LevelResult, level_result::Sex, DepartmentLevel,
+    LevelAreaOutput, LevelAreaResult,
+    LevelRankingInput, level_ranking_input::Metric
 };
 
 pub struct LevelHandler {
     pub pool: Arc<PGPool>
 }
 
+/// Return the sex of the row
+///
+/// # Arguments
+/// * `value` - &PgRow
+fn get_sex(value: &PgRow) -> Sex {
+    match value.try_get::<i64, &str>("sexe") {
+        Ok(s) => match s {
+            0 => Sex::Both,
+            1 => Sex::Male,
+            2 => Sex::Female,
+            _ => Sex::Both
+        },
+        Err(_) => Sex::Male
+    }
+}
+
 impl TryFrom<PgRow> for LevelResult {
     type Error = sqlx::Error;
 
     fn try_from(value: PgRow) -> Result<Self, Self::Error> {
-        let sex = match value.try_get::<i64, &str>("sexe") {
-            Ok(s) => match s {
-                0 => Sex::Both,
-                1 => Sex::Male,
-                2 => Sex::Female,
-                _ => Sex::Both
-            },
-            Err(_) => Sex::Male
-        };
-        
         let res = Self {
             department: value.try_get("dep")?,
-            sex: sex.into(),
+            sex: get_sex(&value).into(),
             date: value.try_get("jour")?,
             hospitalization: value.try_get("hosp")?,
             icu: value.try_get("rea")?,
@@ -43,6 +52,34 @@ impl TryFrom<PgRow> for LevelResult {
             other_care_services: value.try_get("autres").ok(),
             back_home: value.try_get("rad")?,
             death: value.try_get("dc")?,
+            trend: query::get_trend(&value).map(Into::into)
+        };
+
+        Ok(res)
+    }
+}
+
+impl Paginated for LevelResult {
+    fn page_key(&self) -> PageKey {
+        PageKey { day: self.date.clone(), area: self.department.clone(), age: self.sex as i64 }
+    }
+}
+
+impl TryFrom<PgRow> for LevelAreaResult {
+    type Error = sqlx::Error;
+
+    fn try_from(value: PgRow) -> Result<Self, Self::Error> {
+        let res = Self {
+            region: None,
+            sex: get_sex(&value).into(),
+            date: value.try_get("jour")?,
+            hospitalization: value.try_get("hosp")?,
+            icu: value.try_get("rea")?,
+            conventional_care: value.try_get("hospconv").unwrap_or_default(),
+            different_care_services: value.try_get("ssr_usld").unwrap_or_default(),
+            other_care_services: value.try_get("autres").unwrap_or_default(),
+            back_home: value.try_get("rad")?,
+            death: value.try_get("dc")?,
         };
 
         Ok(res)
@@ -51,6 +88,13 @@ impl TryFrom<PgRow> for LevelResult {
 
 #[tonic::async_trait]
 impl LevelService for LevelHandler {
+    /// Return the level in hospital by department. The day is optional
+    /// Multiple departments can be queried at once, in this case the data is grouped by department
+    /// The trend of the hospitalization can be included
+    ///
+    /// # Arguments
+    /// * `&self`
+    /// * `request` - Request<LevelInput>
     async fn get_hospital_level_by_department(
         &self,
         request: Request<LevelInput>
@@ -60,20 +104,126 @@ impl LevelService for LevelHandler {
             return Err(MaskErr::MissingDate.into());
         }
 
-        let date: CommonInput = input.date.unwrap().into();
+        let (date, resolved) = metadata::resolve_date(&self.pool, "hospital_dep", input.date.unwrap()).await?;
+        let date = date.build_date_sql_like()?;
+        let (departments, multiple) = geography::resolve_departments(
+            &input.department,
+            &input.departments,
+            input.all_departments
+        )?;
+        let page = Page::new(input.page_size, &input.page_token)?;
+
+        let keys = "jour, dep, sexe";
+        let sql = match input.include_trend {
+            true => query::build_trend_page_query("hospital_dep", "hosp", "dep, sexe", "dep = ANY($2)", keys, 3),
+            false => query::build_page_query("SELECT * FROM hospital_dep WHERE jour LIKE $1 AND dep = ANY($2)", keys, 3)
+        };
+
+        let data = query::get_page::<LevelResult>(
+            &self.pool,
+            sqlx::query(&sql).bind(&date).bind(departments),
+            page.after(),
+            page.limit()
+        ).await;
+
+        match data {
+            Ok(mut data) if multiple => {
+                let next_page_token = page.next_token(&mut data);
+                let departments = geography::group_by_department(data, |l| l.department.clone())
+                    .into_iter()
+                    .map(|(department, data)| DepartmentLevel { department, data })
+                    .collect();
+
+                Ok(metadata::respond_with_date(&self.pool, &["hospital_dep"], resolved, LevelOutput { data: Vec::new(), departments, next_page_token }).await)
+            },
+            Ok(mut data) => {
+                let next_page_token = page.next_token(&mut data);
+                Ok(metadata::respond_with_date(&self.pool, &["hospital_dep"], resolved, LevelOutput { data, departments: Vec::new(), next_page_token }).await)
+            },
+            Err(err) => return Err(MaskErr::query("level in hospital", err).into())
+        }
+    }
+
+    /// Return the level in hospital of the departments of a region, or of the whole country
+    /// if no region is given, summed by day and by sex
+    ///
+    /// # Arguments
+    /// * `&self`
+    /// * `request` - Request<AreaInput>
+    async fn get_hospital_level_by_area(
+        &self,
+        request: Request<AreaInput>
+    ) -> Result<Response<LevelAreaOutput>, Status> {
+        let input = request.into_inner();
+        if input.date.is_none() {
+            return Err(MaskErr::MissingDate.into());
+        }
+
+        let (date, resolved) = metadata::resolve_date(&self.pool, "hospital_dep", input.date.unwrap()).await?;
         let date = date.build_date_sql_like()?;
+        let departments = geography::get_department_codes(input.region)?;
 
-        match query::get_all_by_date_and_gen_field::<LevelResult, String>(
+        match query::get_all_by_date_and_gen_field::<LevelAreaResult, Vec<String>>(
             &self.pool,
-            "SELECT * FROM hospital_dep WHERE jour LIKE $1 AND dep = $2",
+            "SELECT jour, sexe,
+                SUM(hosp)::bigint AS hosp,
+                SUM(rea)::bigint AS rea,
+                SUM(hospconv) AS hospconv,
+                SUM(ssr_usld) AS ssr_usld,
+                SUM(autres) AS autres,
+                SUM(rad)::bigint AS rad,
+                SUM(dc)::bigint AS dc
+            FROM hospital_dep WHERE jour LIKE $1 AND dep = ANY($2)
+            GROUP BY jour, sexe ORDER BY jour, sexe",
             &date,
-            input.department
+            departments
+        ).await {
+            Ok(mut data) => {
+                data.iter_mut().for_each(|d| d.region = input.region);
+                Ok(metadata::respond_with_date(&self.pool, &["hospital_dep"], resolved, LevelAreaOutput { data }).await)
+            },
+            Err(err) => return Err(MaskErr::query("level in hospital by area", err).into())
+        }
+    }
+
+    /// Rank the departments by a level in hospital for a day or for the last 7 days
+    /// including the given day. The level is averaged over the period
+    ///
+    /// # Arguments
+    /// * `&self`
+    /// * `request` - Request<LevelRankingInput>
+    async fn get_department_ranking(
+        &self,
+        request: Request<LevelRankingInput>
+    ) -> Result<Response<DepartmentRanking>, Status> {
+        let input = request.into_inner();
+        if input.date.is_none() {
+            return Err(MaskErr::MissingDate.into());
+        }
+
+        let column = match input.metric() {
+            Metric::Hospitalization => "hosp",
+            Metric::Icu => "rea",
+            Metric::BackHome => "rad",
+            Metric::Death => "dc"
+        };
+
+        let (date, resolved) = metadata::resolve_date(&self.pool, "hospital_dep", input.date.unwrap()).await?;
+        let day = date.build_naive_date()?;
+        let options = input.options.unwrap_or_default();
+        let (days, previous_days) = utils::get_ranking_days(day, options.period() == Period::Week);
+
+        // sexe = 0 is the sum of both male & female
+        let sql = query::build_ranking_query("hospital_dep", &format!("AVG({column})"), Some("sexe = 0"), options.ascending);
+        match query::get_ranking::<RankingResult>(
+            &self.pool,
+            &sql,
+            days,
+            previous_days,
+            (options.limit > 0).then_some(options.limit as i64)
         ).await {
-            Ok(data) => Ok(Response::new(LevelOutput { data })),
-            Err(err) => {
-                error!("fetch level in hospital fail {:?}", err);
-                return Err(MaskErr::QueryError("level in hospital".into()).into());
-            }
+            Ok(ranks) => Ok(metadata::respond_with_date(&self.pool, &["hospital_dep"], resolved, DepartmentRanking { ranks }).await),
+            Err(err) => return Err(MaskErr::query("hospital level ranking", err).into())
         }
     }
 }
@@ -81,7 +231,7 @@ impl LevelService for LevelHandler {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use super::super::common::CommonInput;
+    use super::super::common::{CommonInput, RankingOptions};
 
     #[tokio::test]
     async fn expect_grpc_to_return_response() {
@@ -96,8 +246,14 @@ mod tests {
                 day: Some(8),
                 month: 1,
                 year: 2022,
+                latest: false,
             }),
-            department: "77".to_owned()
+            department: "77".to_owned(),
+            departments: Vec::new(),
+            all_departments: false,
+            include_trend: false,
+            page_size: 0,
+            page_token: String::new()
         };
 
         let request = Request::new(input);
@@ -119,8 +275,14 @@ mod tests {
                 day: None,
                 month: 1,
                 year: 2022,
+                latest: false,
             }),
-            department: "77".to_owned()
+            department: "77".to_owned(),
+            departments: Vec::new(),
+            all_departments: false,
+            include_trend: false,
+            page_size: 0,
+            page_token: String::new()
         };
 
         let request = Request::new(input);
@@ -128,4 +290,145 @@ mod tests {
 
         assert!(res.is_ok());
     }
+
+    #[tokio::test]
+    async fn expect_to_get_level_by_region() {
+        let pool = db::connect("../config.toml").await.unwrap();
+        let pool_arc = Arc::new(pool);
+        let level_handle = LevelHandler {
+            pool: Arc::clone(&pool_arc)
+        };
+
+        let input = AreaInput {
+            date: Some(CommonInput {
+                day: Some(8),
+                month: 1,
+                year: 2022,
+                latest: false,
+            }),
+            region: Some(11)
+        };
+
+        let request = Request::new(input);
+        let res = level_handle.get_hospital_level_by_area(request).await;
+
+        assert!(res.is_ok());
+    }
+
+    #[tokio::test]
+    async fn expect_level_by_area_to_return_error_for_unknown_region() {
+        let pool = db::connect("../config.toml").await.unwrap();
+        let pool_arc = Arc::new(pool);
+        let level_handle = LevelHandler {
+            pool: Arc::clone(&pool_arc)
+        };
+
+        let input = AreaInput {
+            date: Some(CommonInput {
+                day: Some(8),
+                month: 1,
+                year: 2022,
+                latest: false,
+            }),
+            region: Some(12)
+        };
+
+        let request = Request::new(input);
+        let res = level_handle.get_hospital_level_by_area(request).await;
+
+        assert!(res.is_err());
+    }
+
+    #[tokio::test]
+    async fn expect_to_get_level_of_all_departments() {
+        let pool = db::connect("../config.toml").await.unwrap();
+        let pool_arc = Arc::new(pool);
+        let level_handle = LevelHandler {
+            pool: Arc::clone(&pool_arc)
+        };
+
+        let input = LevelInput {
+            date: Some(CommonInput {
+                day: Some(8),
+                month: 1,
+                year: 2022,
+                latest: false,
+            }),
+            department: String::new(),
+            departments: Vec::new(),
+            all_departments: true,
+            include_trend: true,
+            page_size: 0,
+            page_token: String::new()
+        };
+
+        let request = Request::new(input);
+        let res = level_handle.get_hospital_level_by_department(request).await;
+
+        assert!(res.is_ok());
+    }
+
+    #[tokio::test]
+    async fn expect_to_get_department_ranking() {
+        let pool = db::connect("../config.toml").await.unwrap();
+        let pool_arc = Arc::new(pool);
+        let level_handle = LevelHandler {
+            pool: Arc::clone(&pool_arc)
+        };
+
+        let input = LevelRankingInput {
+            date: Some(CommonInput {
+                day: Some(8),
+                month: 1,
+                year: 2022,
+                latest: false,
+            }),
+            metric: Metric::Icu.into(),
+            options: Some(RankingOptions {
+                period: Period::Day.into(),
+                ascending: true,
+                limit: 5
+            })
+        };
+
+        let request = Request::new(input);
+        let res = level_handle.get_department_ranking(request).await.unwrap();
+        let ranks = &res.get_ref().ranks;
+
+        assert!(ranks.len() <= 5);
+        assert!(ranks.windows(2).all(|w| w[0].value <= w[1].value));
+    }
+
+    #[tokio::test]
+    async fn expect_to_get_pages_of_all_departments() {
+        let pool = db::connect("../config.toml").await.unwrap();
+        let level_handle = LevelHandler {
+            pool: Arc::new(pool)
+        };
+
+        let input = |page_token| LevelInput {
+            date: Some(CommonInput {
+                day: Some(8),
+                month: 1,
+                year: 2022,
+                latest: false,
+            }),
+            department: String::new(),
+            departments: Vec::new(),
+            all_departments: true,
+            include_trend: true,
+            page_size: 20,
+            page_token
+        };
+
+        let first = level_handle.get_hospital_level_by_department(Request::new(input(String::new()))).await.unwrap().into_inner();
+        let rows: usize = first.departments.iter().map(|d| d.data.len()).sum();
+        assert_eq!(rows, 20);
+
+        let second = level_handle.get_hospital_level_by_department(Request::new(input(first.next_page_token))).await.unwrap().into_inner();
+        let last = first.departments.last().unwrap().data.last().unwrap();
+        let next = second.departments.first().unwrap().data.first().unwrap();
+        assert!((&last.department, last.sex) < (&next.department, next.sex));
+        assert!(second.departments.iter().flat_map(|d| &d.data).all(|l| l.trend.is_some()));
+    }
 }
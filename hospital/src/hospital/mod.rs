@@ -11,6 +11,4 @@ pub mod proto_newcase {
     tonic::include_proto!("newcase");
 }
 
-pub mod common {
-    tonic::include_proto!("common");
-}
+pub use crate::common::proto_common as common;
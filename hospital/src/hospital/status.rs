@@ -7,9 +7,9 @@ use tonic::{Request, Response, Status};
 use db::{PGPool, query};
 use utils::{
     Date,
-    err::MaskErr
+    err::MaskErr,
+    page::{Page, PageKey, Paginated}
 };
-use crate::common::proto_common::CommonInput;
 use super::proto_hospital::{CareStatusResult, CareStatusInput, CareStatusOutput};
 use super::proto_hospital::care_status_server::CareStatus;
 
@@ -33,17 +33,25 @@ impl TryFrom<PgRow> for CareStatusResult {
             different_care_services: value.try_get("ssr_usld").ok(),
             conventional_care: value.try_get("hospconv").ok(),
             other_care_district: value.try_get("autres").ok(),
-            day: value.try_get("jour")?
+            day: value.try_get("jour")?,
+            trend: query::get_trend(&value).map(Into::into)
         };
 
         Ok(res)
     }
 }
 
+impl Paginated for CareStatusResult {
+    fn page_key(&self) -> PageKey {
+        PageKey { day: self.day.clone(), area: self.region.to_string(), age: self.age }
+    }
+}
+
 #[tonic::async_trait]
 impl CareStatus for CareService {
     /// Return the number of case in hospital for a date and a region
     /// The day is optional. Hence we can query either per day or per month
+    /// The trend of the hospitalization can be included
     /// 
     /// # Arguments
     /// * `&self`
@@ -57,20 +65,28 @@ impl CareStatus for CareService {
             return Err(MaskErr::MissingDate.into());
         }
 
-        let date: CommonInput = input.date.unwrap().into();
+        let (date, resolved) = metadata::resolve_date(&self.pool, "hospitalization", input.date.unwrap()).await?;
         let date = date.build_date_sql_like()?;
+        geography::validate_region(input.region)?;
+        let page = Page::new(input.page_size, &input.page_token)?;
+
+        let keys = "jour, reg::text, cl_age90";
+        let sql = match input.include_trend {
+            true => query::build_trend_page_query("hospitalization", "hosp", "reg, cl_age90", "reg = $2", keys, 3),
+            false => query::build_page_query("SELECT * FROM hospitalization WHERE jour LIKE $1 AND reg = $2", keys, 3)
+        };
 
-        match query::get_all_by_date_and_gen_field::<CareStatusResult, i32>(
+        match query::get_page::<CareStatusResult>(
             &self.pool,
-            "SELECT * FROM hospitalization WHERE jour LIKE $1 AND reg = $2",
-            &date,
-            input.region
+            sqlx::query(&sql).bind(&date).bind(input.region),
+            page.after(),
+            page.limit()
         ).await {
-            Ok(cases) => Ok(Response::new(CareStatusOutput { cases })),
-            Err(err) => {
-                error!("fetch hospitalization {:?}", err);
-                return Err(MaskErr::QueryError("hospitalization by region".into()).into());
-            }
+            Ok(mut cases) => {
+                let next_page_token = page.next_token(&mut cases);
+                Ok(metadata::respond_with_date(&self.pool, &["hospitalization"], resolved, CareStatusOutput { cases, next_page_token }).await)
+            },
+            Err(err) => return Err(MaskErr::query("hospitalization by region", err).into())
         }
     }
 }
@@ -93,8 +109,12 @@ mod tests {
                 day: Some(12),
                 month: 12,
                 year: 2021,
+                latest: false,
             }),
-            region: 11 
+            region: 11,
+            include_trend: false,
+            page_size: 0,
+            page_token: String::new()
         };
 
         let request = Request::new(input);
@@ -116,8 +136,12 @@ mod tests {
                 day: None,
                 month: 32,
                 year: 2021,
+                latest: false,
             }),
-            region: 11 
+            region: 11,
+            include_trend: false,
+            page_size: 0,
+            page_token: String::new()
         };
 
         let request = Request::new(input);
@@ -125,4 +149,96 @@ mod tests {
 
         assert!(res.is_err());
     }
+
+    #[tokio::test]
+    async fn expect_grpc_to_return_response_with_trend() {
+        let pool = db::connect("../config.toml").await.unwrap();
+        let pool_arc = Arc::new(pool);
+        let care_service = CareService {
+            pool: Arc::clone(&pool_arc)
+        };
+
+        let input = CareStatusInput {
+            date: Some(CommonInput {
+                day: Some(12),
+                month: 12,
+                year: 2021,
+                latest: false,
+            }),
+            region: 11,
+            include_trend: true,
+            page_size: 0,
+            page_token: String::new()
+        };
+
+        let request = Request::new(input);
+        let res = care_service.get_hospital_status_by_region(request).await.unwrap();
+
+        assert!(res.get_ref().cases.iter().all(|c| c.trend.is_some()));
+    }
+
+    #[tokio::test]
+    async fn expect_grpc_to_resolve_latest_day() {
+        let pool = db::connect("../config.toml").await.unwrap();
+        let pool_arc = Arc::new(pool);
+        let care_service = CareService {
+            pool: Arc::clone(&pool_arc)
+        };
+
+        let input = CareStatusInput {
+            date: Some(CommonInput {
+                day: None,
+                month: 0,
+                year: 0,
+                latest: true,
+            }),
+            region: 11,
+            include_trend: false,
+            page_size: 0,
+            page_token: String::new()
+        };
+
+        let request = Request::new(input);
+        let res = care_service.get_hospital_status_by_region(request).await.unwrap();
+        let resolved = res.metadata().get("x-resolved-date").unwrap().to_str().unwrap().to_owned();
+
+        assert!(!res.get_ref().cases.is_empty());
+        assert!(res.get_ref().cases.iter().all(|c| c.day == resolved));
+    }
+
+    #[tokio::test]
+    async fn expect_grpc_to_return_pages_with_trend() {
+        let pool = db::connect("../config.toml").await.unwrap();
+        let care_service = CareService {
+            pool: Arc::new(pool)
+        };
+
+        let input = |page_token| CareStatusInput {
+            date: Some(CommonInput {
+                day: None,
+                month: 12,
+                year: 2021,
+                latest: false,
+            }),
+            region: 11,
+            include_trend: true,
+            page_size: 50,
+            page_token
+        };
+
+        let first = care_service.get_hospital_status_by_region(Request::new(input(String::new()))).await.unwrap().into_inner();
+        assert_eq!(first.cases.len(), 50);
+        assert!(!first.next_page_token.is_empty());
+
+        let second = care_service.get_hospital_status_by_region(Request::new(input(first.next_page_token))).await.unwrap().into_inner();
+        let (last, next) = (first.cases.last().unwrap(), second.cases.first().unwrap());
+        assert!((&last.day, last.age) < (&next.day, next.age));
+        // the trend is computed on the history of the page
+        assert!(second.cases.iter().all(|c| c.trend.is_some()));
+
+        let mut all = input(String::new());
+        all.page_size = 0;
+        let all = care_service.get_hospital_status_by_region(Request::new(all)).await.unwrap().into_inner();
+        assert!(second.cases.iter().all(|c| all.cases.contains(c)));
+    }
 }
@@ -0,0 +1,87 @@
+use std::collections::HashMap;
+use super::proto_icu::{IcuResult, IcuComparisonResult, IcuComparisonOutput};
+
+/// Align the ICU entry rate of the vaxx & unvaxx people per day. Each day of the range
+/// is returned even if one of the datasets does not have any value for this day. In this
+/// case the day is also added to the list of missing days of the related dataset
+///
+/// # Arguments
+/// * `days` - Vec<String>
+/// * `vaxx` - Vec<IcuResult>
+/// * `unvaxx` - Vec<IcuResult>
+pub fn compare_by_day(days: Vec<String>, vaxx: Vec<IcuResult>, unvaxx: Vec<IcuResult>) -> IcuComparisonOutput {
+    let vaxx: HashMap<String, f64> = vaxx.into_iter().map(|r| (r.day, r.rate)).collect();
+    let unvaxx: HashMap<String, f64> = unvaxx.into_iter().map(|r| (r.day, r.rate)).collect();
+
+    let mut output = IcuComparisonOutput::default();
+    for day in days {
+        let vaxx_rate = vaxx.get(&day).copied();
+        let unvaxx_rate = unvaxx.get(&day).copied();
+
+        if vaxx_rate.is_none() {
+            output.missing_vaxx_days.push(day.clone());
+        }
+
+        if unvaxx_rate.is_none() {
+            output.missing_unvaxx_days.push(day.clone());
+        }
+
+        let (ratio, difference) = match (vaxx_rate, unvaxx_rate) {
+            (Some(v), Some(u)) => ((v != 0.0).then(|| u / v), Some(u - v)),
+            _ => (None, None)
+        };
+
+        output.data.push(IcuComparisonResult {
+            day,
+            vaxx_rate,
+            unvaxx_rate,
+            ratio,
+            difference
+        });
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rate(day: &str, rate: f64) -> IcuResult {
+        IcuResult { day: day.to_owned(), rate }
+    }
+
+    #[test]
+    fn expect_to_align_days() {
+        let days = vec!["2021-12-01".to_owned(), "2021-12-02".to_owned()];
+        let output = compare_by_day(
+            days,
+            vec![rate("2021-12-01", 2.0), rate("2021-12-02", 4.0)],
+            vec![rate("2021-12-02", 10.0), rate("2021-12-01", 6.0)]
+        );
+
+        assert_eq!(output.data.len(), 2);
+        assert_eq!(output.data[0].ratio, Some(3.0));
+        assert_eq!(output.data[0].difference, Some(4.0));
+        assert_eq!(output.data[1].ratio, Some(2.5));
+        assert!(output.missing_vaxx_days.is_empty());
+        assert!(output.missing_unvaxx_days.is_empty());
+    }
+
+    #[test]
+    fn expect_to_report_missing_days() {
+        let days = vec!["2021-12-01".to_owned(), "2021-12-02".to_owned(), "2021-12-03".to_owned()];
+        let output = compare_by_day(
+            days,
+            vec![rate("2021-12-01", 0.0), rate("2021-12-03", 1.0)],
+            vec![rate("2021-12-01", 6.0)]
+        );
+
+        assert_eq!(output.data[0].ratio, None);
+        assert_eq!(output.data[0].difference, Some(6.0));
+        assert_eq!(output.data[2].vaxx_rate, Some(1.0));
+        assert_eq!(output.data[2].unvaxx_rate, None);
+        assert_eq!(output.missing_vaxx_days, vec!["2021-12-02".to_owned()]);
+        assert_eq!(output.missing_unvaxx_days, vec!["2021-12-02".to_owned(), "2021-12-03".to_owned()]);
+    }
+}
@@ -9,9 +9,18 @@ use utils::{
     Date,
     err::MaskErr
 };
-use crate::common::proto_common::CommonInput;
 use super::proto_icu::icu_service_server::IcuService;
-use super::proto_icu::{IcuOutput, IcuResult, IcuInput};
+use super::proto_icu::{
+    IcuOutput,
+    IcuResult,
+    IcuInput,
+    IcuRangeInput,
+    IcuComparisonOutput,
+    SaturationInput,
+    SaturationOutput,
+    SaturationResult
+};
+use super::comparison;
 
 pub struct IcuHandler {
     pub pool: Arc<PGPool>
@@ -50,7 +59,7 @@ impl IcuService for IcuHandler {
             return Err(MaskErr::MissingDate.into());
         }
     
-        let date: CommonInput = input.date.unwrap().into();
+        let (date, resolved) = metadata::resolve_date(&self.pool, "unvaxx", input.date.unwrap()).await?;
         let date = date.build_date_sql_like()?;
 
         match query::get_all_by_date_only(
@@ -58,11 +67,8 @@ impl IcuService for IcuHandler {
             "SELECT * FROM unvaxx WHERE date LIKE $1",
             &date
         ).await {
-            Ok(data) => Ok(Response::new(IcuOutput { data })),
-            Err(err) => {
-                error!("fetch unvaccinated people error {:?}", err);
-                Err(MaskErr::from(err).into())
-            }
+            Ok(data) => Ok(metadata::respond_with_date(&self.pool, &["unvaxx"], resolved, IcuOutput { data }).await),
+            Err(err) => Err(MaskErr::query("unvaccinated people", err).into())
         }
     }
 
@@ -80,7 +86,7 @@ impl IcuService for IcuHandler {
             return Err(MaskErr::MissingDate.into());
         }
     
-        let date: CommonInput = input.date.unwrap().into();
+        let (date, resolved) = metadata::resolve_date(&self.pool, "vaxx", input.date.unwrap()).await?;
         let date = date.build_date_sql_like()?;
 
         match query::get_all_by_date_only(
@@ -88,11 +94,131 @@ impl IcuService for IcuHandler {
             "SELECT * FROM vaxx WHERE date LIKE $1",
             &date
         ).await {
-            Ok(data) => Ok(Response::new(IcuOutput { data })),
-            Err(err) => {
-                error!("fetch vaccinated people error {:?}", err);
-                Err(MaskErr::from(err).into())
-            }
+            Ok(data) => Ok(metadata::respond_with_date(&self.pool, &["vaxx"], resolved, IcuOutput { data }).await),
+            Err(err) => Err(MaskErr::query("vaccinated people", err).into())
+        }
+    }
+
+    /// Compare the ICU level of the vaxx & unvaxx people in the whole country for each day
+    /// of a date range. Days which are missing in one of the datasets are reported in the output
+    ///
+    /// # Arguments
+    /// * `&self` - Self
+    /// * `request` - Request<IcuRangeInput>
+    async fn get_france_icu_level_comparison(
+        &self,
+        request: Request<IcuRangeInput>
+    ) -> Result<Response<IcuComparisonOutput>, Status> {
+        let input = request.into_inner();
+        let (start, end) = match input.range.map(|r| (r.start, r.end)) {
+            Some((Some(start), Some(end))) => (start, end),
+            _ => return Err(MaskErr::MissingDate.into())
+        };
+
+        // both datasets are published together
+        let (start, end, resolved) = metadata::resolve_range(&self.pool, "vaxx", start, end).await?;
+        let (from, to) = utils::build_date_range(&start, &end)?;
+        let days = utils::get_days_between(from, to);
+        let (from, to) = (from.format("%Y-%m-%d").to_string(), to.format("%Y-%m-%d").to_string());
+
+        let vaxx = query::get_all_by_date_range::<IcuResult>(
+            &self.pool,
+            "SELECT * FROM vaxx WHERE date >= $1 AND date <= $2",
+            &from,
+            &to
+        ).await;
+
+        let unvaxx = query::get_all_by_date_range::<IcuResult>(
+            &self.pool,
+            "SELECT * FROM unvaxx WHERE date >= $1 AND date <= $2",
+            &from,
+            &to
+        ).await;
+
+        match vaxx.and_then(|vaxx| unvaxx.map(|unvaxx| (vaxx, unvaxx))) {
+            Ok((vaxx, unvaxx)) => {
+                let output = comparison::compare_by_day(days, vaxx, unvaxx);
+                Ok(metadata::respond_with_date(&self.pool, &["vaxx", "unvaxx"], resolved, output).await)
+            },
+            Err(err) => Err(MaskErr::query("icu level comparison", err).into())
+        }
+    }
+
+    /// Get the ICU occupancy of a department compared to its ICU capacity for a day or a month.
+    /// The saturation level is unknown when the capacity of the department hasn't been imported
+    ///
+    /// # Arguments
+    /// * `&self` - Self
+    /// * `request` - Request<SaturationInput>
+    async fn get_icu_saturation_by_department(
+        &self,
+        request: Request<SaturationInput>
+    ) -> Result<Response<SaturationOutput>, Status> {
+        let input = request.into_inner();
+        if input.date.is_none() {
+            return Err(MaskErr::MissingDate.into());
+        }
+
+        let (date, resolved) = metadata::resolve_date(&self.pool, "hospital_dep", input.date.unwrap()).await?;
+        let date = date.build_date_sql_like()?;
+
+        let department = match input.department {
+            Some(dep) => dep,
+            None => return Err(MaskErr::MissingParam("department".to_owned()).into())
+        };
+        geography::validate_department(&department)?;
+
+        match query::get_all_by_date_and_gen_field::<SaturationResult, &str>(
+            &self.pool,
+            "SELECT h.jour, h.dep, h.rea, c.beds, h.rea::float8 / NULLIF(c.beds, 0) AS ratio
+            FROM hospital_dep h LEFT JOIN icu_capacity c ON c.dep = h.dep
+            WHERE h.jour LIKE $1 AND h.dep = $2 AND h.sexe = 0
+            ORDER BY h.jour",
+            &date,
+            &department
+        ).await {
+            Ok(data) => Ok(metadata::respond_with_date(&self.pool, &["hospital_dep", "icu_capacity"], resolved, SaturationOutput { data }).await),
+            Err(err) => Err(MaskErr::query("icu saturation by department", err).into())
+        }
+    }
+
+    /// Get the ICU occupancy of a region compared to its ICU capacity for a day or a month.
+    /// The capacity of a region is the sum of the capacity of its departments
+    ///
+    /// # Arguments
+    /// * `&self` - Self
+    /// * `request` - Request<SaturationInput>
+    async fn get_icu_saturation_by_region(
+        &self,
+        request: Request<SaturationInput>
+    ) -> Result<Response<SaturationOutput>, Status> {
+        let input = request.into_inner();
+        if input.date.is_none() {
+            return Err(MaskErr::MissingDate.into());
+        }
+
+        let (date, resolved) = metadata::resolve_date(&self.pool, "hospitalization", input.date.unwrap()).await?;
+        let date = date.build_date_sql_like()?;
+
+        let region = match input.region {
+            Some(reg) => reg,
+            None => return Err(MaskErr::MissingParam("region".to_owned()).into())
+        };
+        geography::validate_region(region)?;
+
+        match query::get_all_by_date_and_gen_field::<SaturationResult, i32>(
+            &self.pool,
+            "SELECT h.jour, h.reg::int AS reg, h.rea, c.beds, h.rea::float8 / NULLIF(c.beds, 0) AS ratio
+            FROM hospitalization h LEFT JOIN (
+                SELECT reg, SUM(beds)::bigint AS beds FROM icu_capacity GROUP BY reg
+            ) c ON c.reg = h.reg
+            WHERE h.jour LIKE $1 AND h.reg = $2 AND h.cl_age90 = 0
+            ORDER BY h.jour",
+            &date,
+            region
+        ).await {
+            Ok(data) => Ok(metadata::respond_with_date(&self.pool, &["hospitalization", "icu_capacity"], resolved, SaturationOutput { data }).await),
+            Err(err) => Err(MaskErr::query("icu saturation by region", err).into())
         }
     }
 }
@@ -100,7 +226,20 @@ impl IcuService for IcuHandler {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use super::super::common::CommonInput as ICommonInput;
+    use super::super::common::{CommonInput as ICommonInput, DateRange};
+    use super::super::proto_icu::SaturationLevel;
+    use super::super::saturation::get_saturation_level;
+
+    /// Sum the ICU beds of the fixture matching the department & region filter
+    fn fixture_beds(filter: impl Fn(&str, &str) -> bool) -> i64 {
+        include_str!("../../../fixtures/icu_capacity.csv")
+            .lines()
+            .skip(1)
+            .map(|line| line.split(';').collect::<Vec<_>>())
+            .filter(|cols| filter(cols[0], cols[1]))
+            .map(|cols| cols[2].parse::<i64>().unwrap())
+            .sum()
+    }
 
     #[tokio::test]
     async fn expect_grpc_to_return_response_for_unvaxx() {
@@ -114,7 +253,8 @@ mod tests {
             date: Some(ICommonInput {
                 day: Some(18),
                 month: 12,
-                year: 2021
+                year: 2021,
+                latest: false
             })
         };
 
@@ -136,7 +276,8 @@ mod tests {
             date: Some(ICommonInput {
                 day: Some(18),
                 month: 12,
-                year: 2021
+                year: 2021,
+                latest: false
             })
         };
 
@@ -145,4 +286,181 @@ mod tests {
 
         assert!(res.is_ok());
     }
+
+    #[tokio::test]
+    async fn expect_grpc_to_return_comparison() {
+        let pool = db::connect("../config.toml").await.unwrap();
+        let pool_arc = Arc::new(pool);
+        let icu_service = IcuHandler {
+            pool: Arc::clone(&pool_arc)
+        };
+
+        let input = IcuRangeInput {
+            range: Some(DateRange {
+                start: Some(ICommonInput {
+                    day: Some(1),
+                    month: 12,
+                    year: 2021,
+                    latest: false
+                }),
+                end: Some(ICommonInput {
+                    day: None,
+                    month: 12,
+                    year: 2021,
+                    latest: false
+                })
+            })
+        };
+
+        let request = Request::new(input);
+        let res = icu_service.get_france_icu_level_comparison(request).await.unwrap();
+
+        assert_eq!(res.get_ref().data.len(), 31);
+    }
+
+    #[tokio::test]
+    async fn expect_grpc_comparison_to_return_error() {
+        let pool = db::connect("../config.toml").await.unwrap();
+        let pool_arc = Arc::new(pool);
+        let icu_service = IcuHandler {
+            pool: Arc::clone(&pool_arc)
+        };
+
+        let input = IcuRangeInput {
+            range: Some(DateRange {
+                start: Some(ICommonInput {
+                    day: Some(18),
+                    month: 12,
+                    year: 2021,
+                    latest: false
+                }),
+                end: Some(ICommonInput {
+                    day: Some(1),
+                    month: 12,
+                    year: 2021,
+                    latest: false
+                })
+            })
+        };
+
+        let request = Request::new(input);
+        let res = icu_service.get_france_icu_level_comparison(request).await;
+
+        assert!(res.is_err());
+    }
+
+    #[tokio::test]
+    async fn expect_grpc_to_return_saturation_by_department() {
+        let pool = db::connect("../config.toml").await.unwrap();
+        let pool_arc = Arc::new(pool);
+        let icu_service = IcuHandler {
+            pool: Arc::clone(&pool_arc)
+        };
+
+        let input = SaturationInput {
+            date: Some(ICommonInput {
+                day: None,
+                month: 12,
+                year: 2021,
+                latest: false
+            }),
+            department: Some("75".to_owned()),
+            region: None
+        };
+
+        let request = Request::new(input);
+        let res = icu_service.get_icu_saturation_by_department(request).await.unwrap();
+
+        // the capacity of the department comes from the fixtures/icu_capacity.csv file
+        let beds = fixture_beds(|dep, _| dep == "75");
+        assert!(!res.get_ref().data.is_empty());
+        for r in &res.get_ref().data {
+            let ratio = r.icu as f64 / beds as f64;
+            assert_eq!(r.beds, Some(beds));
+            assert!((r.ratio.unwrap() - ratio).abs() < 1e-12);
+            assert_eq!(r.level(), get_saturation_level(Some(ratio)));
+            assert_ne!(r.level(), SaturationLevel::Unknown);
+        }
+    }
+
+    #[tokio::test]
+    async fn expect_grpc_to_return_saturation_by_region() {
+        let pool = db::connect("../config.toml").await.unwrap();
+        let pool_arc = Arc::new(pool);
+        let icu_service = IcuHandler {
+            pool: Arc::clone(&pool_arc)
+        };
+
+        let input = SaturationInput {
+            date: Some(ICommonInput {
+                day: Some(18),
+                month: 12,
+                year: 2021,
+                latest: false
+            }),
+            department: None,
+            region: Some(11)
+        };
+
+        let request = Request::new(input);
+        let res = icu_service.get_icu_saturation_by_region(request).await.unwrap();
+
+        // the capacity of a region is the sum of the capacity of its departments
+        let beds = fixture_beds(|_, reg| reg == "11");
+        let r = &res.get_ref().data[0];
+        assert_eq!(r.day, "2021-12-18");
+        assert_eq!(r.beds, Some(beds));
+        assert!((r.ratio.unwrap() - r.icu as f64 / beds as f64).abs() < 1e-12);
+        assert_eq!(r.level(), get_saturation_level(r.ratio));
+    }
+
+    #[tokio::test]
+    async fn expect_saturation_to_be_unknown_without_capacity() {
+        let pool = db::connect("../config.toml").await.unwrap();
+        let pool_arc = Arc::new(pool);
+        let icu_service = IcuHandler {
+            pool: Arc::clone(&pool_arc)
+        };
+
+        let input = SaturationInput {
+            date: Some(ICommonInput {
+                day: Some(18),
+                month: 12,
+                year: 2021,
+                latest: false
+            }),
+            department: Some("80".to_owned()),
+            region: None
+        };
+
+        let request = Request::new(input);
+        let res = icu_service.get_icu_saturation_by_department(request).await.unwrap();
+
+        assert!(res.get_ref().data.iter().all(|r| r.beds.is_none() && r.level() == SaturationLevel::Unknown));
+    }
+
+    #[tokio::test]
+    async fn expect_grpc_saturation_to_return_error_without_region() {
+        let pool = db::connect("../config.toml").await.unwrap();
+        let pool_arc = Arc::new(pool);
+        let icu_service = IcuHandler {
+            pool: Arc::clone(&pool_arc)
+        };
+
+        let input = SaturationInput {
+            date: Some(ICommonInput {
+                day: Some(18),
+                month: 12,
+                year: 2021,
+                latest: false
+            }),
+            department: None,
+            region: None
+        };
+
+        let request = Request::new(input);
+        let res = icu_service.get_icu_saturation_by_region(request).await;
+
+        assert!(res.is_err());
+    }
 }
@@ -1,9 +1,9 @@
 pub mod level;
+pub mod comparison;
+pub mod saturation;
 
 pub mod proto_icu {
     tonic::include_proto!("icu");
 }
 
-pub mod common {
-    tonic::include_proto!("common");
-}
+pub use crate::common::proto_common as common;
@@ -0,0 +1,58 @@
+use sqlx::{postgres::PgRow, Row};
+use super::proto_icu::{SaturationResult, SaturationLevel};
+
+// Lower bound of the occupancy ratio of each saturation level
+const MODERATE_RATIO: f64 = 0.4;
+const HIGH_RATIO: f64 = 0.6;
+const CRITICAL_RATIO: f64 = 0.8;
+const SATURATED_RATIO: f64 = 1.0;
+
+impl TryFrom<PgRow> for SaturationResult {
+    type Error = sqlx::Error;
+
+    fn try_from(value: PgRow) -> Result<Self, Self::Error> {
+        let ratio: Option<f64> = value.try_get("ratio")?;
+
+        let res = Self {
+            day: value.try_get("jour")?,
+            department: value.try_get("dep").ok(),
+            region: value.try_get("reg").ok(),
+            icu: value.try_get("rea")?,
+            beds: value.try_get("beds")?,
+            ratio,
+            level: get_saturation_level(ratio) as i32
+        };
+
+        Ok(res)
+    }
+}
+
+/// Return the saturation level of an ICU occupancy ratio
+///
+/// # Arguments
+/// * `ratio` - Option<f64>
+pub fn get_saturation_level(ratio: Option<f64>) -> SaturationLevel {
+    match ratio {
+        None => SaturationLevel::Unknown,
+        Some(r) if r >= SATURATED_RATIO => SaturationLevel::Saturated,
+        Some(r) if r >= CRITICAL_RATIO => SaturationLevel::Critical,
+        Some(r) if r >= HIGH_RATIO => SaturationLevel::High,
+        Some(r) if r >= MODERATE_RATIO => SaturationLevel::Moderate,
+        Some(_) => SaturationLevel::Normal
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expect_to_return_saturation_level() {
+        assert_eq!(get_saturation_level(None), SaturationLevel::Unknown);
+        assert_eq!(get_saturation_level(Some(0.1)), SaturationLevel::Normal);
+        assert_eq!(get_saturation_level(Some(0.4)), SaturationLevel::Moderate);
+        assert_eq!(get_saturation_level(Some(0.79)), SaturationLevel::High);
+        assert_eq!(get_saturation_level(Some(0.8)), SaturationLevel::Critical);
+        assert_eq!(get_saturation_level(Some(1.2)), SaturationLevel::Saturated);
+    }
+}
@@ -1,5 +1,6 @@
 use tonic::transport::Server;
 use std::sync::Arc;
+use tokio::sync::broadcast;
 
 #[macro_use]
 extern crate log;
@@ -7,6 +8,13 @@ extern crate log;
 mod hospital;
 mod mix;
 mod icu;
+mod quality;
+mod forecast;
+mod alert;
+mod admin;
+mod dataset;
+mod vaccination;
+mod mortality;
 mod common;
 
 use hospital::proto_newcase::case_service_server::CaseServiceServer;
@@ -19,6 +27,27 @@ use mix::proto_mix::mix_service_server::MixServiceServer;
 use mix::drees::MixHandler;
 use icu::proto_icu::icu_service_server::IcuServiceServer;
 use icu::level::IcuHandler;
+use quality::proto_quality::quality_service_server::QualityServiceServer;
+use quality::issue::QualityHandler;
+use forecast::proto_forecast::forecast_service_server::ForecastServiceServer;
+use forecast::occupancy::ForecastHandler;
+use alert::proto_alert::alert_service_server::AlertServiceServer;
+use alert::rule::AlertHandler;
+use geography::{GeographyHandler, GeographyServiceServer};
+use metadata::{MetadataHandler, MetadataServiceServer};
+use admin::proto_admin::admin_service_server::AdminServiceServer;
+use admin::import::AdminHandler;
+use admin::auth::TokenInterceptor;
+use admin::runner::{Runner, ImportConfig};
+use dataset::proto_dataset::dataset_service_server::DatasetServiceServer;
+use dataset::rows::DatasetHandler;
+use vaccination::proto_vaccination::vaccination_service_server::VaccinationServiceServer;
+use vaccination::coverage::VaccinationHandler;
+use mortality::proto_mortality::mortality_service_server::MortalityServiceServer;
+use mortality::excess::MortalityHandler;
+
+// Number of alert events kept for the watchers which are lagging
+const ALERT_BUFFER: usize = 64;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -27,6 +56,21 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     info!("Connecting to the database");
     let db_pool = db::connect("../config.toml").await?;
     let db_handle = Arc::new(db_pool);
+
+    // evaluate the alert rules after each import
+    alert::store::create_tables(&db_handle).await?;
+    let (alert_sender, _) = broadcast::channel(ALERT_BUFFER);
+    tokio::spawn(alert::listener::listen(Arc::clone(&db_handle), alert_sender.clone()));
+
+    // run the imports triggered by the admin service & the scheduler
+    admin::store::create_tables(&db_handle).await?;
+    let import_config = ImportConfig::from_env();
+    admin::store::fail_interrupted(&db_handle, &import_config.instance).await?;
+    let import_runner = Arc::new(Runner::new(Arc::clone(&db_handle), import_config));
+    admin::scheduler::start(Arc::clone(&import_runner));
+
+    // serve the exports of the datasets over http
+    tokio::spawn(dataset::http::run_export_server(Arc::clone(&db_handle)));
     
     // setup the server
     let addr = utils::get_server_addr(9000).parse()?;
@@ -46,9 +90,36 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .add_service(LevelServiceServer::new(LevelHandler {
             pool: Arc::clone(&db_handle)
         }))
+        .add_service(QualityServiceServer::new(QualityHandler {
+            pool: Arc::clone(&db_handle)
+        }))
+        .add_service(ForecastServiceServer::new(ForecastHandler {
+            pool: Arc::clone(&db_handle)
+        }))
+        .add_service(AlertServiceServer::new(AlertHandler {
+            pool: Arc::clone(&db_handle),
+            sender: alert_sender
+        }))
+        .add_service(AdminServiceServer::with_interceptor(
+            AdminHandler { runner: import_runner },
+            TokenInterceptor::from_env()
+        ))
+        .add_service(MetadataServiceServer::new(MetadataHandler {
+            pool: Arc::clone(&db_handle)
+        }))
+        .add_service(VaccinationServiceServer::new(VaccinationHandler {
+            pool: Arc::clone(&db_handle)
+        }))
+        .add_service(MortalityServiceServer::new(MortalityHandler {
+            pool: Arc::clone(&db_handle)
+        }))
+        .add_service(DatasetServiceServer::new(DatasetHandler {
+            pool: Arc::clone(&db_handle)
+        }))
+        .add_service(GeographyServiceServer::new(GeographyHandler::default()))
         .serve(addr);
 
-    info!("Server is running on port 9000 & Healthcheck server port 5601");
+    info!("Server is running on port 9000, Export listener port 9001 & Healthcheck server port 5601");
     tokio::try_join!(server, health::run_health_server())?;
 
     Ok(())
@@ -7,11 +7,41 @@ use sqlx::{
 use tonic::{Request, Response, Status};
 use utils::{
     Date,
-    err::MaskErr
+    err::MaskErr,
+    field_mask::{FieldMask, Masked}
 };
-use crate::common::proto_common::CommonInput;
 use super::proto_mix::mix_service_server::MixService;
-use super::proto_mix::{MixOutput, MixResult, MixInput};
+use super::proto_mix::{MixOutput, MixResult, MixInput, MixRates};
+
+// Value of the vac_statut column used as a reference to compute the relative risk
+const UNVACCINATED_STATUS: &str = "Non-vaccinés";
+const PER_MILLION: f64 = 1_000_000.0;
+
+// Fields of MixResult which can be requested by a field mask
+const MIX_FIELDS: [&str; 14] = [
+    "date", "vaxx_status", "pcr_done", "pcr_symptom", "pcr_positive", "pcr_symptom_positive", "hospital_entry",
+    "hospital_entry_pcr_positive", "icu_entry", "icu_entry_pcr_positive", "death", "pcr_positive_death",
+    "resident_population", "rates"
+];
+
+// Columns of the fields of MixResult. The date & the status are always selected as they
+// are used to filter the rows and to compute the relative risks
+const MIX_COLUMNS: [(&str, &[&str]); 11] = [
+    ("pcr_done", &["nb_pcr"]),
+    ("pcr_symptom", &["nb_pcr_sympt"]),
+    ("pcr_positive", &["nb_pcr+"]),
+    ("pcr_symptom_positive", &["nb_pcr+_sympt"]),
+    ("hospital_entry", &["hc"]),
+    ("hospital_entry_pcr_positive", &["hc_pcr+"]),
+    ("icu_entry", &["sc"]),
+    ("icu_entry_pcr_positive", &["sc_pcr+"]),
+    ("death", &["dc"]),
+    ("pcr_positive_death", &["dc_pcr+"]),
+    ("resident_population", &["effectif"])
+];
+
+// Columns needed to compute the rates
+const RATES_COLUMNS: [&str; 7] = ["date", "vac_statut", "nb_pcr+", "hc", "sc", "dc", "effectif"];
 
 pub struct MixHandler {
     pub pool: Arc<PGPool>
@@ -21,30 +51,55 @@ impl TryFrom<PgRow> for MixResult {
     type Error = sqlx::Error;
 
     fn try_from(value: PgRow) -> Result<Self, Self::Error> {
+        // the columns of the fields which are not requested are not selected
         let res = Self {
             date: value.try_get("date")?,
             vaxx_status: value.try_get("vac_statut")?,
-            pcr_done: value.try_get("nb_pcr")?,
-            pcr_symptom: value.try_get("nb_pcr_sympt")?,
-            pcr_positive: value.try_get("nb_pcr+")?,
-            pcr_symptom_positive: value.try_get("nb_pcr+_sympt")?,
-            hospital_entry: value.try_get("hc")?,
-            hospital_entry_pcr_positive: value.try_get("hc_pcr+")?,
-            icu_entry: value.try_get("sc")?,
-            icu_entry_pcr_positive: value.try_get("sc_pcr+")?,
-            death: value.try_get("dc")?,
-            pcr_positive_death: value.try_get("dc_pcr+")?,
-            resident_population: value.try_get("effectif")?
+            pcr_done: query::get_or_default(&value, "nb_pcr")?,
+            pcr_symptom: query::get_or_default(&value, "nb_pcr_sympt")?,
+            pcr_positive: query::get_or_default(&value, "nb_pcr+")?,
+            pcr_symptom_positive: query::get_or_default(&value, "nb_pcr+_sympt")?,
+            hospital_entry: query::get_or_default(&value, "hc")?,
+            hospital_entry_pcr_positive: query::get_or_default(&value, "hc_pcr+")?,
+            icu_entry: query::get_or_default(&value, "sc")?,
+            icu_entry_pcr_positive: query::get_or_default(&value, "sc_pcr+")?,
+            death: query::get_or_default(&value, "dc")?,
+            pcr_positive_death: query::get_or_default(&value, "dc_pcr+")?,
+            resident_population: query::get_or_default(&value, "effectif")?,
+            rates: None
         };
 
         Ok(res)
     }
 }
 
+impl Masked for MixResult {
+    fn clear(&mut self, field: &str) {
+        match field {
+            "date" => self.date = String::new(),
+            "vaxx_status" => self.vaxx_status = String::new(),
+            "pcr_done" => self.pcr_done = 0.0,
+            "pcr_symptom" => self.pcr_symptom = 0.0,
+            "pcr_positive" => self.pcr_positive = 0.0,
+            "pcr_symptom_positive" => self.pcr_symptom_positive = 0.0,
+            "hospital_entry" => self.hospital_entry = 0.0,
+            "hospital_entry_pcr_positive" => self.hospital_entry_pcr_positive = 0.0,
+            "icu_entry" => self.icu_entry = 0.0,
+            "icu_entry_pcr_positive" => self.icu_entry_pcr_positive = 0.0,
+            "death" => self.death = 0.0,
+            "pcr_positive_death" => self.pcr_positive_death = 0.0,
+            "resident_population" => self.resident_population = 0.0,
+            "rates" => self.rates = None,
+            _ => {}
+        }
+    }
+}
+
 #[tonic::async_trait]
 impl MixService for MixHandler {
     /// Return the global covid mix data by date. It's a mix of 
-    /// VAC-SI, SI-DEP & VAC-SI
+    /// VAC-SI, SI-DEP & VAC-SI. The data can be filtered by vaccination status
+    /// and can include the rates per 1M residents
     /// 
     /// # Arguments
     /// * `&self` - &Self
@@ -58,28 +113,154 @@ impl MixService for MixHandler {
             return Err(MaskErr::MissingDate.into());
         }
 
-        let date: CommonInput = input.date.unwrap().into();
+        let (date, resolved) = metadata::resolve_date(&self.pool, "data_mix", input.date.unwrap()).await?;
         let date = date.build_date_sql_like()?;
+        let mask = FieldMask::new(input.fields.map(|f| f.paths).unwrap_or_default(), &MIX_FIELDS)?;
+        let required: &[&str] = match input.include_rates {
+            true => &RATES_COLUMNS,
+            false => &["date", "vac_statut"]
+        };
+
+        // the unvaccinated people are the reference of the relative risks hence they're fetched
+        // along with the requested statuses when the rates are requested
+        let mut statuses = input.vaxx_status.clone();
+        let add_reference = input.include_rates
+            && !statuses.is_empty()
+            && !statuses.iter().any(|s| s == UNVACCINATED_STATUS);
 
-        match query::get_all_by_date_only::<MixResult>(
+        if add_reference {
+            statuses.push(UNVACCINATED_STATUS.to_owned());
+        }
+
+        let sql = format!(
+            "SELECT {} FROM data_mix WHERE date LIKE $1 AND ($2::text[] IS NULL OR vac_statut = ANY($2))",
+            mask.select(&MIX_COLUMNS, required)
+        );
+        match query::get_all_by_date_and_gen_field::<MixResult, Option<Vec<String>>>(
             &self.pool,
-            "SELECT * FROM data_mix WHERE date LIKE $1",
-            &date
+            &sql,
+            &date,
+            (!statuses.is_empty()).then_some(statuses)
         ).await {
-            Ok(data) => Ok(Response::new(MixOutput { data })),
-            Err(err) => {
-                error!("fetch covid mix data error: {:?}", err);
-                return Err(MaskErr::QueryError("fetch covid mix data".to_owned()).into());
-            }
+            Ok(mut data) => {
+                if input.include_rates {
+                    set_rates(&mut data);
+                }
+
+                // remove the reference when it has not been requested
+                if add_reference {
+                    data.retain(|r| r.vaxx_status != UNVACCINATED_STATUS);
+                }
+
+                mask.apply(&mut data, &MIX_FIELDS);
+                Ok(metadata::respond_with_date(&self.pool, &["data_mix"], resolved, MixOutput { data }).await)
+            },
+            Err(err) => return Err(MaskErr::query("fetch covid mix data", err).into())
+        }
+    }
+}
+
+/// Compute the rates per 1M residents of each row and the relative risk
+/// compared to the unvaccinated people of the same date
+///
+/// # Arguments
+/// * `data` - &mut [MixResult]
+fn set_rates(data: &mut [MixResult]) {
+    for row in data.iter_mut() {
+        row.rates = get_rates_per_million(row);
+    }
+
+    let references: Vec<(String, MixRates)> = data
+        .iter()
+        .filter(|r| r.vaxx_status == UNVACCINATED_STATUS)
+        .filter_map(|r| r.rates.clone().map(|rates| (r.date.clone(), rates)))
+        .collect();
+
+    for row in data.iter_mut() {
+        let reference = references
+            .iter()
+            .find(|(date, _)| *date == row.date)
+            .map(|(_, rates)| rates);
+
+        if let (Some(rates), Some(reference)) = (row.rates.as_mut(), reference) {
+            rates.pcr_positive_relative_risk = get_relative_risk(rates.pcr_positive, reference.pcr_positive);
+            rates.hospital_entry_relative_risk = get_relative_risk(rates.hospital_entry, reference.hospital_entry);
+            rates.icu_entry_relative_risk = get_relative_risk(rates.icu_entry, reference.icu_entry);
+            rates.death_relative_risk = get_relative_risk(rates.death, reference.death);
         }
     }
 }
 
+/// Return the rates per 1M residents. None is returned if the population is unknown
+///
+/// # Arguments
+/// * `row` - &MixResult
+fn get_rates_per_million(row: &MixResult) -> Option<MixRates> {
+    if row.resident_population <= 0.0 {
+        return None;
+    }
+
+    let ratio = PER_MILLION / row.resident_population;
+
+    Some(MixRates {
+        pcr_positive: row.pcr_positive * ratio,
+        hospital_entry: row.hospital_entry * ratio,
+        icu_entry: row.icu_entry * ratio,
+        death: row.death * ratio,
+        ..Default::default()
+    })
+}
+
+/// Return the relative risk of a rate compared to the reference rate
+///
+/// # Arguments
+/// * `rate` - f64
+/// * `reference` - f64
+fn get_relative_risk(rate: f64, reference: f64) -> Option<f64> {
+    (reference > 0.0).then(|| rate / reference)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use super::super::common::CommonInput as DCommonInput;
 
+    fn row(status: &str, pcr_positive: f64, resident_population: f64) -> MixResult {
+        MixResult {
+            date: "2021-10-10".to_owned(),
+            vaxx_status: status.to_owned(),
+            pcr_positive,
+            hospital_entry: pcr_positive / 10.0,
+            icu_entry: pcr_positive / 100.0,
+            death: 0.0,
+            resident_population,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn expect_to_compute_rates_and_relative_risk() {
+        let mut data = vec![
+            row(UNVACCINATED_STATUS, 400.0, 2_000_000.0),
+            row("Complet de 6 mois et plus - avec rappel", 500.0, 10_000_000.0),
+            row("Primo dose récente", 10.0, 0.0)
+        ];
+
+        set_rates(&mut data);
+
+        let reference = data[0].rates.as_ref().unwrap();
+        assert_eq!(reference.pcr_positive, 200.0);
+        assert_eq!(reference.pcr_positive_relative_risk, Some(1.0));
+
+        let vaccinated = data[1].rates.as_ref().unwrap();
+        assert_eq!(vaccinated.pcr_positive, 50.0);
+        assert_eq!(vaccinated.pcr_positive_relative_risk, Some(0.25));
+        assert_eq!(vaccinated.hospital_entry_relative_risk, Some(0.25));
+        assert_eq!(vaccinated.death_relative_risk, None);
+
+        assert!(data[2].rates.is_none());
+    }
+
     #[tokio::test]
     async fn expect_grpc_to_return_response() {
         let pool = db::connect("../config.toml").await.unwrap();
@@ -90,8 +271,12 @@ mod tests {
             date: Some(DCommonInput {
                 day: Some(10),
                 month: 10,
-                year: 2021
-            })
+                year: 2021,
+                latest: false
+            }),
+            vaxx_status: Vec::new(),
+            include_rates: false,
+            fields: None
         };
 
         let request = Request::new(input);
@@ -99,4 +284,76 @@ mod tests {
 
         assert!(res.is_ok());
     }
+
+    #[tokio::test]
+    async fn expect_grpc_to_return_filtered_response_with_rates() {
+        let pool = db::connect("../config.toml").await.unwrap();
+        let db_handler = Arc::new(pool);
+        let mix_service = MixHandler { pool: Arc::clone(&db_handler) };
+
+        let input = MixInput {
+            date: Some(DCommonInput {
+                day: Some(10),
+                month: 10,
+                year: 2021,
+                latest: false
+            }),
+            vaxx_status: vec![UNVACCINATED_STATUS.to_owned()],
+            include_rates: true,
+            fields: None
+        };
+
+        let request = Request::new(input);
+        let res = mix_service.get_global_covid_data_by_date(request).await.unwrap();
+
+        assert!(res.get_ref().data.iter().all(|r| r.vaxx_status == UNVACCINATED_STATUS));
+    }
+
+    #[tokio::test]
+    async fn expect_rates_to_be_relative_to_unrequested_reference() {
+        let pool = db::connect("../config.toml").await.unwrap();
+        let mix_service = MixHandler { pool: Arc::new(pool) };
+
+        let status = "Primo dose récente";
+        let input = MixInput {
+            date: Some(DCommonInput {
+                day: Some(10),
+                month: 10,
+                year: 2021,
+                latest: false
+            }),
+            vaxx_status: vec![status.to_owned()],
+            include_rates: true,
+            fields: None
+        };
+
+        let data = mix_service.get_global_covid_data_by_date(Request::new(input)).await.unwrap().into_inner().data;
+        assert_eq!(data.len(), 1);
+        assert_eq!(data[0].vaxx_status, status);
+        assert!(data[0].rates.as_ref().unwrap().death_relative_risk.is_some());
+    }
+
+    #[tokio::test]
+    async fn expect_grpc_to_return_requested_fields() {
+        let pool = db::connect("../config.toml").await.unwrap();
+        let mix_service = MixHandler { pool: Arc::new(pool) };
+
+        let input = MixInput {
+            date: Some(DCommonInput {
+                day: Some(10),
+                month: 10,
+                year: 2021,
+                latest: false
+            }),
+            vaxx_status: Vec::new(),
+            include_rates: true,
+            fields: Some(prost_types::FieldMask { paths: vec!["vaxx_status".to_owned(), "death".to_owned(), "rates.death".to_owned()] })
+        };
+
+        let data = mix_service.get_global_covid_data_by_date(Request::new(input)).await.unwrap().into_inner().data;
+        assert!(!data.is_empty());
+        // the population is used by the rates but is not returned
+        assert!(data.iter().all(|r| r.date.is_empty() && r.resident_population == 0.0 && !r.vaxx_status.is_empty()));
+        assert!(data.iter().any(|r| r.rates.is_some()));
+    }
 }
@@ -4,6 +4,4 @@ pub mod proto_mix {
     tonic::include_proto!("mix");
 }
 
-pub mod common {
-    tonic::include_proto!("common");
-}
+pub use crate::common::proto_common as common;
@@ -0,0 +1,231 @@
+use std::sync::Arc;
+use db::PGPool;
+use sqlx::{postgres::PgRow, Row};
+use tonic::{Request, Response, Status};
+use utils::err::MaskErr;
+use super::proto_mortality::mortality_service_server::MortalityService;
+use super::proto_mortality::{MortalityInput, MortalityOutput, MortalityResult};
+
+// Years preceding the pandemic used as baseline by default
+const DEFAULT_BASELINE_YEARS: [i32; 5] = [2015, 2016, 2017, 2018, 2019];
+
+// Number of days taken on each side of the day to smooth the baseline
+const BASELINE_HALF_WINDOW: i32 = 3;
+
+// The baseline of a day is the mean of the deaths of the days around the same date ($5 days
+// on each side) during the baseline years ($4). The 29th of February is moved to the 28th
+// on the years which aren't leap years. The covid deaths are the new deaths in hospital of
+// the cases dataset
+const EXCESS_QUERY: &str = "SELECT d.jour, d.deces, b.baseline, c.incid_dc
+    FROM deaths_department d
+    LEFT JOIN LATERAL (
+        SELECT AVG(p.deces)::double precision AS baseline
+        FROM (
+            SELECT (d.jour::date - make_interval(years => EXTRACT(YEAR FROM d.jour::date)::integer - y.year))::date AS day
+            FROM unnest($4::integer[]) AS y(year)
+        ) s
+        JOIN deaths_department p ON p.dep = d.dep
+            AND p.jour >= to_char(s.day - $5::integer, 'YYYY-MM-DD')
+            AND p.jour <= to_char(s.day + $5::integer, 'YYYY-MM-DD')
+    ) b ON true
+    LEFT JOIN cases c ON c.dep = d.dep AND c.jour = d.jour
+    WHERE d.dep = $3 AND d.jour >= $1 AND d.jour <= $2
+    ORDER BY d.jour";
+
+pub struct MortalityHandler {
+    pub pool: Arc<PGPool>
+}
+
+impl TryFrom<PgRow> for MortalityResult {
+    type Error = sqlx::Error;
+
+    fn try_from(value: PgRow) -> Result<Self, Self::Error> {
+        let observed: i64 = value.try_get("deces")?;
+        let baseline: Option<f64> = value.try_get("baseline")?;
+        let excess = baseline.map(|b| observed as f64 - b);
+
+        let res = Self {
+            day: value.try_get("jour")?,
+            observed,
+            baseline,
+            excess,
+            excess_rate: baseline.zip(excess).and_then(|(b, e)| (b > 0.0).then(|| e / b)),
+            covid_deaths: value.try_get("incid_dc")?
+        };
+
+        Ok(res)
+    }
+}
+
+#[tonic::async_trait]
+impl MortalityService for MortalityHandler {
+    /// Compare the all-cause deaths of a department with the deaths of the same days
+    /// during the baseline years
+    ///
+    /// # Arguments
+    /// * `&self`
+    /// * `request` - Request<MortalityInput>
+    async fn get_excess_mortality_by_department(
+        &self,
+        request: Request<MortalityInput>
+    ) -> Result<Response<MortalityOutput>, Status> {
+        let input = request.into_inner();
+        let (start, end) = match input.range.map(|r| (r.start, r.end)) {
+            Some((Some(start), Some(end))) => (start, end),
+            _ => return Err(MaskErr::MissingDate.into())
+        };
+
+        geography::validate_department(&input.department)?;
+        let baseline_years = match input.baseline_years.is_empty() {
+            true => DEFAULT_BASELINE_YEARS.to_vec(),
+            false => input.baseline_years
+        };
+
+        let (start, end, resolved) = metadata::resolve_range(&self.pool, "deaths_department", start, end).await?;
+        let (from, to) = utils::build_date_range(&start, &end)?;
+
+        let rows = sqlx::query(EXCESS_QUERY)
+            .bind(from.format("%Y-%m-%d").to_string())
+            .bind(to.format("%Y-%m-%d").to_string())
+            .bind(input.department)
+            .bind(baseline_years)
+            .bind(BASELINE_HALF_WINDOW)
+            .fetch_all(self.pool.as_ref())
+            .await;
+
+        let data = match rows {
+            Ok(rows) => rows
+                .into_iter()
+                .map(MortalityResult::try_from)
+                .collect::<Result<Vec<_>, _>>(),
+            Err(err) => Err(err)
+        };
+
+        match data {
+            Ok(data) => {
+                let total_excess = data.iter().filter_map(|r| r.excess).sum();
+                let output = MortalityOutput { data, total_excess };
+
+                Ok(metadata::respond_with_date(&self.pool, &["deaths_department", "cases"], resolved, output).await)
+            },
+            Err(err) => Err(MaskErr::query("excess mortality", err).into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Datelike, Duration, NaiveDate};
+    use super::super::common::{CommonInput as MCommonInput, DateRange};
+
+    fn input(department: &str, baseline_years: Vec<i32>) -> MortalityInput {
+        range_input(department, baseline_years, (2022, 1, 1), (2022, 1, 31))
+    }
+
+    fn range_input(department: &str, baseline_years: Vec<i32>, start: (i32, i32, i32), end: (i32, i32, i32)) -> MortalityInput {
+        MortalityInput {
+            range: Some(DateRange {
+                start: Some(MCommonInput { day: Some(start.2), month: start.1, year: start.0, latest: false }),
+                end: Some(MCommonInput { day: Some(end.2), month: end.1, year: end.0, latest: false })
+            }),
+            department: department.to_owned(),
+            baseline_years
+        }
+    }
+
+    /// Compute the baseline of a day from the deaths of the fixture
+    fn fixture_baseline(department: &str, day: NaiveDate) -> f64 {
+        let deaths: Vec<(NaiveDate, f64)> = include_str!("../../../fixtures/deaths_dep.csv")
+            .lines()
+            .skip(1)
+            .filter_map(|line| {
+                let cols: Vec<&str> = line.split(';').collect();
+                (cols[0] == department).then(|| (
+                    NaiveDate::parse_from_str(cols[1], "%Y-%m-%d").unwrap(),
+                    cols[2].parse().unwrap()
+                ))
+            })
+            .collect();
+
+        let half_window = Duration::days(BASELINE_HALF_WINDOW as i64);
+        let values: Vec<f64> = DEFAULT_BASELINE_YEARS
+            .iter()
+            .flat_map(|year| {
+                let center = day.with_year(*year).unwrap_or_else(|| NaiveDate::from_ymd_opt(*year, 2, 28).unwrap());
+                deaths
+                    .iter()
+                    .filter(move |(d, _)| *d >= center - half_window && *d <= center + half_window)
+                    .map(|(_, v)| *v)
+            })
+            .collect();
+
+        values.iter().sum::<f64>() / values.len() as f64
+    }
+
+    async fn get_handler() -> MortalityHandler {
+        let pool = db::connect("../config.toml").await.unwrap();
+
+        MortalityHandler { pool: Arc::new(pool) }
+    }
+
+    #[tokio::test]
+    async fn expect_grpc_to_return_excess_mortality() {
+        let handler = get_handler().await;
+        let output = handler.get_excess_mortality_by_department(Request::new(input("75", Vec::new()))).await.unwrap().into_inner();
+
+        assert_eq!(output.data.len(), 31);
+        assert!(output.data.iter().all(|r| r.baseline.is_some()));
+
+        let day = &output.data[0];
+        assert_eq!(day.excess, Some(day.observed as f64 - day.baseline.unwrap()));
+
+        let total: f64 = output.data.iter().filter_map(|r| r.excess).sum();
+        assert_eq!(output.total_excess, total);
+    }
+
+    #[tokio::test]
+    async fn expect_baseline_to_be_smoothed_around_the_day() {
+        let handler = get_handler().await;
+        let output = handler.get_excess_mortality_by_department(Request::new(input("75", Vec::new()))).await.unwrap().into_inner();
+
+        let day = output.data.iter().find(|r| r.day == "2022-01-15").unwrap();
+        let expected = fixture_baseline("75", NaiveDate::from_ymd_opt(2022, 1, 15).unwrap());
+        assert!((day.baseline.unwrap() - expected).abs() < 1e-6);
+    }
+
+    #[tokio::test]
+    async fn expect_leap_day_to_have_a_baseline_of_every_year() {
+        let handler = get_handler().await;
+        let input = range_input("75", Vec::new(), (2020, 2, 27), (2020, 3, 2));
+        let output = handler.get_excess_mortality_by_department(Request::new(input)).await.unwrap().into_inner();
+
+        assert_eq!(output.data.len(), 5);
+        assert!(output.data.iter().all(|r| r.baseline.is_some()));
+
+        let leap_day = output.data.iter().find(|r| r.day == "2020-02-29").unwrap();
+        let expected = fixture_baseline("75", NaiveDate::from_ymd_opt(2020, 2, 29).unwrap());
+        assert!((leap_day.baseline.unwrap() - expected).abs() < 1e-6);
+    }
+
+    #[tokio::test]
+    async fn expect_baseline_without_data_to_be_empty() {
+        let handler = get_handler().await;
+        let output = handler.get_excess_mortality_by_department(Request::new(input("75", vec![1990]))).await.unwrap().into_inner();
+
+        assert!(output.data.iter().all(|r| r.baseline.is_none() && r.excess.is_none()));
+        assert_eq!(output.total_excess, 0.0);
+    }
+
+    #[tokio::test]
+    async fn expect_grpc_to_return_error() {
+        let handler = get_handler().await;
+        let res = handler.get_excess_mortality_by_department(Request::new(input("999", Vec::new()))).await;
+        assert_eq!(res.unwrap_err().code(), tonic::Code::InvalidArgument);
+
+        let mut missing = input("75", Vec::new());
+        missing.range = None;
+        let res = handler.get_excess_mortality_by_department(Request::new(missing)).await;
+        assert_eq!(res.unwrap_err().code(), tonic::Code::InvalidArgument);
+    }
+}
@@ -0,0 +1,7 @@
+pub mod excess;
+
+pub mod proto_mortality {
+    tonic::include_proto!("mortality");
+}
+
+pub use crate::common::proto_common as common;
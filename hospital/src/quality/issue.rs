@@ -0,0 +1,240 @@
+use std::sync::Arc;
+use db::PGPool;
+use sqlx::{postgres::PgRow, Row};
+use tonic::{Request, Response, Status};
+use utils::err::MaskErr;
+use super::proto_quality::quality_service_server::QualityService;
+use super::proto_quality::{QualityInput, QualityOutput, QualityIssue, IssueKind};
+
+pub struct QualityHandler {
+    pub pool: Arc<PGPool>
+}
+
+impl IssueKind {
+    /// Return the name of the kind stored in the data_quality table
+    ///
+    /// # Arguments
+    /// * `&self` - Self
+    fn as_column(&self) -> &'static str {
+        match self {
+            IssueKind::Gap => "gap",
+            IssueKind::Negative => "negative",
+            IssueKind::Outlier => "outlier",
+            IssueKind::Duplicate => "duplicate"
+        }
+    }
+
+    /// Return the kind from the name stored in the data_quality table
+    ///
+    /// # Arguments
+    /// * `column` - &str
+    fn from_column(column: &str) -> Option<IssueKind> {
+        match column {
+            "gap" => Some(IssueKind::Gap),
+            "negative" => Some(IssueKind::Negative),
+            "outlier" => Some(IssueKind::Outlier),
+            "duplicate" => Some(IssueKind::Duplicate),
+            _ => None
+        }
+    }
+}
+
+impl TryFrom<PgRow> for QualityIssue {
+    type Error = sqlx::Error;
+
+    fn try_from(value: PgRow) -> Result<Self, Self::Error> {
+        let kind: String = value.try_get("kind")?;
+        let kind = IssueKind::from_column(&kind)
+            .ok_or_else(|| sqlx::Error::Decode(format!("unknown issue kind {kind}").into()))?;
+
+        let res = Self {
+            dataset: value.try_get("dataset")?,
+            series: value.try_get("series")?,
+            day: value.try_get("jour")?,
+            kind: kind as i32,
+            metric: value.try_get("metric")?,
+            value: value.try_get("value")?,
+            detail: value.try_get("detail")?
+        };
+
+        Ok(res)
+    }
+}
+
+#[tonic::async_trait]
+impl QualityService for QualityHandler {
+    /// Return the issues found by the data-quality scan made after each import. The issues
+    /// can be filtered by dataset, date range, kind & series
+    ///
+    /// # Arguments
+    /// * `&self`
+    /// * `request` - Request<QualityInput>
+    async fn get_data_quality_issues(
+        &self,
+        request: Request<QualityInput>
+    ) -> Result<Response<QualityOutput>, Status> {
+        let input = request.into_inner();
+        if let Some(dataset) = &input.dataset {
+            // the quality.py script only scans the datasets with days
+            if registry::validate_dataset(dataset)?.day.is_none() {
+                return Err(MaskErr::InvalidParam(format!("the dataset {dataset} is not scanned")).into());
+            }
+        }
+
+        let (from, to, resolved) = match input.range.clone().map(|r| (r.start, r.end)) {
+            Some((Some(start), Some(end))) => {
+                // the latest day & the days available are only known for a dataset
+                let (start, end, resolved) = match (&input.dataset, start.latest || end.latest) {
+                    (Some(dataset), _) => metadata::resolve_range(&self.pool, dataset, start, end).await?,
+                    (None, true) => return Err(MaskErr::MissingParam("dataset".to_owned()).into()),
+                    (None, false) => (start, end, None)
+                };
+
+                let (from, to) = utils::build_date_range(&start, &end)?;
+                (Some(from.format("%Y-%m-%d").to_string()), Some(to.format("%Y-%m-%d").to_string()), resolved)
+            },
+            Some(_) => return Err(MaskErr::MissingDate.into()),
+            None => (None, None, None)
+        };
+
+        let mut kinds: Vec<&str> = input.kinds()
+            .map(|k| k.as_column())
+            .collect();
+        if kinds.is_empty() {
+            kinds = vec!["gap", "negative", "outlier", "duplicate"];
+        }
+
+        let datasets: Vec<&str> = input.dataset.iter().map(String::as_str).collect();
+        match get_issues(&self.pool, input.dataset.clone(), from, to, kinds, input.series).await {
+            Ok(issues) => Ok(metadata::respond_with_date(&self.pool, &datasets, resolved, QualityOutput { issues }).await),
+            Err(err) => Err(MaskErr::query("data quality issues", err).into())
+        }
+    }
+}
+
+/// SQL query to get the data quality issues. Filters which are None are ignored
+///
+/// # Arguments
+/// * `pool` - &PGPool
+/// * `dataset` - Option<String>
+/// * `from` - Option<String>
+/// * `to` - Option<String>
+/// * `kinds` - Vec<&str>
+/// * `series` - Option<String>
+async fn get_issues(
+    pool: &PGPool,
+    dataset: Option<String>,
+    from: Option<String>,
+    to: Option<String>,
+    kinds: Vec<&str>,
+    series: Option<String>
+) -> Result<Vec<QualityIssue>, MaskErr> {
+    let rows = sqlx::query(
+        "SELECT * FROM data_quality
+        WHERE ($1::text IS NULL OR dataset = $1)
+        AND ($2::text IS NULL OR jour >= $2)
+        AND ($3::text IS NULL OR jour <= $3)
+        AND kind = ANY($4)
+        AND ($5::text IS NULL OR series = $5 OR left(series, length($5) + 1) = $5 || ',')
+        ORDER BY dataset, series, jour"
+    )
+        .bind(dataset)
+        .bind(from)
+        .bind(to)
+        .bind(kinds)
+        .bind(series)
+        .fetch_all(pool)
+        .await?;
+
+    let issues = rows
+        .into_iter()
+        .map(QualityIssue::try_from)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(issues)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::common::{CommonInput as QCommonInput, DateRange};
+
+    #[tokio::test]
+    async fn expect_grpc_to_return_issues() {
+        let pool = db::connect("../config.toml").await.unwrap();
+        let pool_arc = Arc::new(pool);
+        let service = QualityHandler { pool: Arc::clone(&pool_arc) };
+
+        let input = QualityInput {
+            dataset: Some("cases".to_owned()),
+            range: Some(DateRange {
+                start: Some(QCommonInput { day: None, month: 12, year: 2021, latest: false }),
+                end: Some(QCommonInput { day: None, month: 12, year: 2021, latest: false })
+            }),
+            kinds: vec![IssueKind::Negative as i32, IssueKind::Gap as i32],
+            series: Some("dep=75".to_owned())
+        };
+
+        let request = Request::new(input);
+        let res = service.get_data_quality_issues(request).await.unwrap();
+
+        assert!(res.get_ref().issues.iter().all(|i| i.dataset == "cases" && i.series == "dep=75"));
+    }
+
+    #[tokio::test]
+    async fn expect_range_without_dataset_to_not_be_resolved() {
+        let pool = db::connect("../config.toml").await.unwrap();
+        let service = QualityHandler { pool: Arc::new(pool) };
+
+        let input = |latest| QualityInput {
+            dataset: None,
+            range: Some(DateRange {
+                start: Some(QCommonInput { day: Some(1), month: 12, year: 2021, latest: false }),
+                end: Some(QCommonInput { day: Some(31), month: 12, year: 2021, latest })
+            }),
+            kinds: Vec::new(),
+            series: None
+        };
+
+        let res = service.get_data_quality_issues(Request::new(input(false))).await.unwrap();
+        assert!(res.metadata().get(metadata::RESOLVED_DATE_HEADER).is_none());
+        assert!(res.get_ref().issues.iter().all(|i| i.day.as_str() >= "2021-12-01" && i.day.as_str() <= "2021-12-31"));
+
+        let res = service.get_data_quality_issues(Request::new(input(true))).await;
+        assert_eq!(res.unwrap_err().code(), tonic::Code::FailedPrecondition);
+    }
+
+    #[tokio::test]
+    async fn expect_series_prefix_to_not_be_a_pattern() {
+        let pool = db::connect("../config.toml").await.unwrap();
+
+        let issues = get_issues(&pool, None, None, None, vec!["gap", "negative", "outlier", "duplicate"], Some("dep=7_".to_owned()))
+            .await
+            .unwrap();
+        assert!(issues.is_empty());
+
+        let issues = get_issues(&pool, None, None, None, vec!["gap", "negative", "outlier", "duplicate"], Some("dep=75".to_owned()))
+            .await
+            .unwrap();
+        assert!(issues.iter().all(|i| i.series == "dep=75" || i.series.starts_with("dep=75,")));
+    }
+
+    #[tokio::test]
+    async fn expect_grpc_to_return_error_for_unknown_dataset() {
+        let pool = db::connect("../config.toml").await.unwrap();
+        let pool_arc = Arc::new(pool);
+        let service = QualityHandler { pool: Arc::clone(&pool_arc) };
+
+        let input = QualityInput {
+            dataset: Some("unknown".to_owned()),
+            range: None,
+            kinds: Vec::new(),
+            series: None
+        };
+
+        let request = Request::new(input);
+        let res = service.get_data_quality_issues(request).await;
+
+        assert_eq!(res.unwrap_err().code(), tonic::Code::InvalidArgument);
+    }
+}
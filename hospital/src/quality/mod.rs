@@ -0,0 +1,7 @@
+pub mod issue;
+
+pub mod proto_quality {
+    tonic::include_proto!("quality");
+}
+
+pub use crate::common::proto_common as common;
@@ -0,0 +1,214 @@
+use std::sync::Arc;
+use db::PGPool;
+use sqlx::{postgres::PgRow, Postgres, Row};
+use tonic::{Request, Response, Status};
+use utils::{Date, err::MaskErr};
+use crate::common::proto_common::CommonInput;
+use super::proto_vaccination::vaccination_service_server::VaccinationService;
+use super::proto_vaccination::{
+    VaccinationInput,
+    VaccinationRegionInput,
+    VaccinationResult,
+    VaccinationOutput
+};
+
+// Age classes of the VAC-SI datasets. 0 is every age
+const AGE_CLASSES: [i32; 15] = [0, 4, 9, 11, 17, 24, 29, 39, 49, 59, 64, 69, 74, 79, 80];
+
+// Every age class is returned when the age class ($3) is null
+const DEPARTMENT_QUERY: &str = "SELECT dep, clage_vacsi::integer AS age_class, jour, n_dose1, n_complet, n_rappel,
+    n_cum_dose1, n_cum_complet, n_cum_rappel, couv_dose1, couv_complet, couv_rappel
+    FROM vaccination_department
+    WHERE jour LIKE $1 AND dep = $2 AND ($3::integer IS NULL OR clage_vacsi = $3)
+    ORDER BY jour, clage_vacsi";
+
+const REGION_QUERY: &str = "SELECT reg::integer AS reg, clage_vacsi::integer AS age_class, jour, n_dose1, n_complet, n_rappel,
+    n_cum_dose1, n_cum_complet, n_cum_rappel, couv_dose1, couv_complet, couv_rappel
+    FROM vaccination_region
+    WHERE jour LIKE $1 AND reg = $2 AND ($3::integer IS NULL OR clage_vacsi = $3)
+    ORDER BY jour, clage_vacsi";
+
+pub struct VaccinationHandler {
+    pub pool: Arc<PGPool>
+}
+
+impl TryFrom<PgRow> for VaccinationResult {
+    type Error = sqlx::Error;
+
+    fn try_from(value: PgRow) -> Result<Self, Self::Error> {
+        let res = Self {
+            department: value.try_get("dep").ok(),
+            region: value.try_get("reg").ok(),
+            age_class: value.try_get("age_class")?,
+            day: value.try_get("jour")?,
+            first_dose: value.try_get("n_dose1")?,
+            complete: value.try_get("n_complet")?,
+            booster: value.try_get("n_rappel")?,
+            cumulative_first_dose: value.try_get("n_cum_dose1")?,
+            cumulative_complete: value.try_get("n_cum_complet")?,
+            cumulative_booster: value.try_get("n_cum_rappel")?,
+            first_dose_coverage: value.try_get("couv_dose1")?,
+            complete_coverage: value.try_get("couv_complet")?,
+            booster_coverage: value.try_get("couv_rappel")?
+        };
+
+        Ok(res)
+    }
+}
+
+/// Check that the age class is one of the classes of VAC-SI
+///
+/// # Arguments
+/// * `age_class` - Option<i32>
+fn validate_age_class(age_class: Option<i32>) -> Result<(), MaskErr> {
+    match age_class {
+        Some(age) if !AGE_CLASSES.contains(&age) => Err(MaskErr::InvalidParam(format!("the age class {age} is unknown"))),
+        _ => Ok(())
+    }
+}
+
+/// Return the vaccination of an area for a day or a month
+///
+/// # Arguments
+/// * `pool` - &PGPool
+/// * `dataset` - &str
+/// * `query` - &str
+/// * `code` - C
+/// * `date` - CommonInput
+/// * `age_class` - Option<i32>
+async fn get_vaccination<C>(
+    pool: &PGPool,
+    dataset: &str,
+    query: &str,
+    code: C,
+    date: CommonInput,
+    age_class: Option<i32>
+) -> Result<Response<VaccinationOutput>, Status>
+where
+    C: for<'q> sqlx::Encode<'q, Postgres> + sqlx::Type<Postgres> + Send
+{
+    validate_age_class(age_class)?;
+
+    let (date, resolved) = metadata::resolve_date(pool, dataset, date).await?;
+    let date = date.build_date_sql_like()?;
+
+    let rows = sqlx::query(query)
+        .bind(date)
+        .bind(code)
+        .bind(age_class)
+        .fetch_all(pool)
+        .await;
+
+    let data = match rows {
+        Ok(rows) => rows
+            .into_iter()
+            .map(VaccinationResult::try_from)
+            .collect::<Result<Vec<_>, _>>(),
+        Err(err) => Err(err)
+    };
+
+    match data {
+        Ok(data) => Ok(metadata::respond_with_date(pool, &[dataset], resolved, VaccinationOutput { data }).await),
+        Err(err) => Err(MaskErr::query("vaccination", err).into())
+    }
+}
+
+#[tonic::async_trait]
+impl VaccinationService for VaccinationHandler {
+    /// Get the doses administered & the vaccination coverage of a department by age class
+    ///
+    /// # Arguments
+    /// * `&self`
+    /// * `request` - Request<VaccinationInput>
+    async fn get_vaccination_by_department(
+        &self,
+        request: Request<VaccinationInput>
+    ) -> Result<Response<VaccinationOutput>, Status> {
+        let input = request.into_inner();
+        let date = input.date.ok_or(MaskErr::MissingDate)?;
+        geography::validate_department(&input.department)?;
+
+        get_vaccination(
+            &self.pool,
+            "vaccination_department",
+            DEPARTMENT_QUERY,
+            input.department,
+            date,
+            input.age_class
+        ).await
+    }
+
+    /// Get the doses administered & the vaccination coverage of a region by age class
+    ///
+    /// # Arguments
+    /// * `&self`
+    /// * `request` - Request<VaccinationRegionInput>
+    async fn get_vaccination_by_region(
+        &self,
+        request: Request<VaccinationRegionInput>
+    ) -> Result<Response<VaccinationOutput>, Status> {
+        let input = request.into_inner();
+        let date = input.date.ok_or(MaskErr::MissingDate)?;
+        geography::validate_region(input.region)?;
+
+        get_vaccination(
+            &self.pool,
+            "vaccination_region",
+            REGION_QUERY,
+            input.region,
+            date,
+            input.age_class
+        ).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::common::CommonInput as VCommonInput;
+
+    async fn get_handler() -> VaccinationHandler {
+        let pool = db::connect("../config.toml").await.unwrap();
+
+        VaccinationHandler { pool: Arc::new(pool) }
+    }
+
+    fn get_date(day: Option<i32>) -> Option<VCommonInput> {
+        Some(VCommonInput { day, month: 9, year: 2021, latest: false })
+    }
+
+    #[tokio::test]
+    async fn expect_grpc_to_return_vaccination_of_department() {
+        let handler = get_handler().await;
+        let input = VaccinationInput { date: get_date(Some(1)), department: "75".to_owned(), age_class: None };
+
+        let data = handler.get_vaccination_by_department(Request::new(input)).await.unwrap().into_inner().data;
+        assert!(data.len() > 1);
+        assert!(data.iter().all(|r| r.department.as_deref() == Some("75") && r.region.is_none()));
+        assert_eq!(data[0].age_class, 0);
+    }
+
+    #[tokio::test]
+    async fn expect_grpc_to_return_vaccination_of_region_by_age() {
+        let handler = get_handler().await;
+        let input = VaccinationRegionInput { date: get_date(None), region: 11, age_class: Some(0) };
+
+        let data = handler.get_vaccination_by_region(Request::new(input)).await.unwrap().into_inner().data;
+        assert!(!data.is_empty());
+        assert!(data.iter().all(|r| r.region == Some(11) && r.age_class == 0));
+        // the cumulative doses grow with the days
+        assert!(data.windows(2).all(|w| w[0].cumulative_first_dose <= w[1].cumulative_first_dose));
+    }
+
+    #[tokio::test]
+    async fn expect_grpc_to_return_error() {
+        let handler = get_handler().await;
+        let input = VaccinationInput { date: get_date(Some(1)), department: "75".to_owned(), age_class: Some(18) };
+        let res = handler.get_vaccination_by_department(Request::new(input)).await;
+        assert_eq!(res.unwrap_err().code(), tonic::Code::InvalidArgument);
+
+        let input = VaccinationRegionInput { date: None, region: 11, age_class: None };
+        let res = handler.get_vaccination_by_region(Request::new(input)).await;
+        assert_eq!(res.unwrap_err().code(), tonic::Code::InvalidArgument);
+    }
+}
@@ -0,0 +1,7 @@
+pub mod coverage;
+
+pub mod proto_vaccination {
+    tonic::include_proto!("vaccination");
+}
+
+pub use crate::common::proto_common as common;
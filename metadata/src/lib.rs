@@ -0,0 +1,528 @@
+use std::{collections::HashMap, sync::{Arc, RwLock}, time::{Duration, Instant}};
+use chrono::NaiveDate;
+use db::PGPool;
+use once_cell::sync::Lazy;
+use proto::metadata_service_server::MetadataService;
+use proto::{ImportInput, ImportOutput, DatasetImport};
+use sqlx::{postgres::PgRow, Row};
+use tonic::{Request, Response, Status, metadata::MetadataValue};
+use utils::{Date, err::MaskErr};
+
+#[macro_use]
+extern crate log;
+
+pub mod proto {
+    tonic::include_proto!("metadata");
+}
+
+pub use proto::metadata_service_server::MetadataServiceServer;
+
+// Headers added to the responses of the RPCs. When a RPC uses several datasets
+// the values are separated by a comma in the same order as the datasets
+const DATASET_HEADER: &str = "x-dataset";
+const VERSION_HEADER: &str = "x-dataset-version";
+const LAST_DATE_HEADER: &str = "x-last-available-date";
+const IMPORTED_AT_HEADER: &str = "x-imported-at";
+// day used by a RPC when the latest day has been requested
+pub const RESOLVED_DATE_HEADER: &str = "x-resolved-date";
+
+// Time during which the first & last days of a dataset are kept to check the requested dates
+const DAY_BOUNDS_TTL: Duration = Duration::from_secs(60);
+
+// First & last days of a dataset along with the time they were read
+type DayBounds = (Instant, NaiveDate, NaiveDate);
+
+static DAY_BOUNDS: Lazy<RwLock<HashMap<String, DayBounds>>> = Lazy::new(Default::default);
+
+// The import_log table is written by the import.py script
+const SELECT_IMPORT: &str = "dataset, source, file_hash, row_count, max_date,
+    to_char(imported_at, 'YYYY-MM-DD\"T\"HH24:MI:SS') AS imported_at
+    FROM import_log";
+
+impl TryFrom<PgRow> for DatasetImport {
+    type Error = sqlx::Error;
+
+    fn try_from(value: PgRow) -> Result<Self, Self::Error> {
+        let res = Self {
+            dataset: value.try_get("dataset")?,
+            source: value.try_get("source")?,
+            file_hash: value.try_get("file_hash")?,
+            row_count: value.try_get("row_count")?,
+            max_date: value.try_get("max_date")?,
+            imported_at: value.try_get("imported_at")?
+        };
+
+        Ok(res)
+    }
+}
+
+/// Return the imports of the datasets. Only the last import of each dataset is returned
+/// unless the history is requested
+///
+/// # Arguments
+/// * `pool` - &PGPool
+/// * `datasets` - Option<Vec<String>>
+/// * `history` - bool
+pub async fn get_imports(pool: &PGPool, datasets: Option<Vec<String>>, history: bool) -> Result<Vec<DatasetImport>, MaskErr> {
+    // the text of imported_at is sorted like the timestamp
+    let distinct = if history { "SELECT" } else { "SELECT DISTINCT ON (dataset)" };
+    let query = format!(
+        "{distinct} {SELECT_IMPORT} WHERE ($1::text[] IS NULL OR dataset = ANY($1)) ORDER BY dataset, imported_at DESC, id DESC"
+    );
+
+    let rows = sqlx::query(&query)
+        .bind(datasets)
+        .fetch_all(pool)
+        .await?;
+
+    let imports = rows
+        .into_iter()
+        .map(DatasetImport::try_from)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(imports)
+}
+
+/// Return the last day available in a dataset. The day is read from the import log and
+/// from the dataset itself when the dataset is not in the log. The last day of a week is its end
+///
+/// # Arguments
+/// * `pool` - &PGPool
+/// * `dataset` - &str
+pub async fn get_latest_day(pool: &PGPool, dataset: &str) -> Result<NaiveDate, MaskErr> {
+    let logged = match get_imports(pool, Some(vec![dataset.to_owned()]), false).await {
+        Ok(imports) => imports.into_iter().next().and_then(|i| i.max_date),
+        Err(err) => {
+            warn!("unable to read the import log {:?}", err);
+            None
+        }
+    };
+
+    let day = match logged {
+        Some(day) => Some(day),
+        None => {
+            let column = registry::validate_dataset(dataset)?
+                .day
+                .as_deref()
+                .ok_or(MaskErr::MissingDate)?;
+            sqlx::query(&format!("SELECT MAX(\"{column}\") AS day FROM \"{dataset}\""))
+                .fetch_one(pool)
+                .await?
+                .try_get("day")?
+        }
+    };
+
+    let day: String = day.ok_or(MaskErr::MissingDate)?;
+
+    parse_last_day(&day)
+}
+
+/// Parse a day of a dataset. The days of some datasets contain the time or the end of a week
+///
+/// # Arguments
+/// * `day` - &str
+fn parse_day(day: &str) -> Result<NaiveDate, MaskErr> {
+    NaiveDate::parse_from_str(day.get(..10).unwrap_or(day), "%Y-%m-%d")
+        .map_err(|_| MaskErr::InvalidDate)
+}
+
+/// Parse the last day of a dataset. The last day of a week (e.g. 2021-09-06-2021-09-12) is its end
+///
+/// # Arguments
+/// * `day` - &str
+fn parse_last_day(day: &str) -> Result<NaiveDate, MaskErr> {
+    match day.get(11..).and_then(|end| NaiveDate::parse_from_str(end, "%Y-%m-%d").ok()) {
+        Some(end) => Ok(end),
+        None => parse_day(day)
+    }
+}
+
+/// Return the first & last days of a dataset. The days are read from the dataset and kept
+/// for DAY_BOUNDS_TTL unless a refresh is requested
+///
+/// # Arguments
+/// * `pool` - &PGPool
+/// * `dataset` - &str
+/// * `refresh` - bool
+async fn get_day_bounds(pool: &PGPool, dataset: &str, refresh: bool) -> Result<(NaiveDate, NaiveDate), MaskErr> {
+    let cached = DAY_BOUNDS
+        .read()
+        .ok()
+        .and_then(|bounds| bounds.get(dataset).copied())
+        .filter(|(read_at, _, _)| !refresh && read_at.elapsed() < DAY_BOUNDS_TTL);
+
+    if let Some((_, first, last)) = cached {
+        return Ok((first, last));
+    }
+
+    let column = registry::validate_dataset(dataset)?
+        .day
+        .as_deref()
+        .ok_or(MaskErr::MissingDate)?;
+    let row = sqlx::query(&format!("SELECT MIN(\"{column}\") AS first_day, MAX(\"{column}\") AS last_day FROM \"{dataset}\""))
+        .fetch_one(pool)
+        .await?;
+
+    let first: Option<String> = row.try_get("first_day")?;
+    let last: Option<String> = row.try_get("last_day")?;
+    let (first, last) = match (first, last) {
+        (Some(first), Some(last)) => (parse_day(&first)?, parse_last_day(&last)?),
+        _ => return Err(MaskErr::MissingDate)
+    };
+
+    if let Ok(mut bounds) = DAY_BOUNDS.write() {
+        bounds.insert(dataset.to_owned(), (Instant::now(), first, last));
+    }
+
+    Ok((first, last))
+}
+
+/// Return the first & last days of a dataset used to check a date. None when they are unknown,
+/// in which case the date isn't checked
+///
+/// # Arguments
+/// * `pool` - &PGPool
+/// * `dataset` - &str
+/// * `refresh` - bool
+async fn get_checked_bounds(pool: &PGPool, dataset: &str, refresh: bool) -> Result<Option<(NaiveDate, NaiveDate)>, MaskErr> {
+    match get_day_bounds(pool, dataset, refresh).await {
+        Ok(bounds) => Ok(Some(bounds)),
+        Err(MaskErr::DbUnavailable(msg)) => Err(MaskErr::DbUnavailable(msg)),
+        Err(err) => {
+            warn!("unable to get the days of {dataset} {:?}", err);
+            Ok(None)
+        }
+    }
+}
+
+/// Check that the period of a date (a day or a month) overlaps the days available in a dataset.
+/// The days are refreshed before rejecting a date after the last day, which may have been
+/// imported since they were read. The check is skipped when the days are unknown
+///
+/// # Arguments
+/// * `pool` - &PGPool
+/// * `dataset` - &str
+/// * `date` - &D
+pub async fn check_date_range<D: Date>(pool: &PGPool, dataset: &str, date: &D) -> Result<(), MaskErr> {
+    // an invalid date is rejected by the handler
+    let (from, to) = match utils::build_date_range(date, date) {
+        Ok(range) => range,
+        Err(_) => return Ok(())
+    };
+
+    let (first, mut last) = match get_checked_bounds(pool, dataset, false).await? {
+        Some(bounds) => bounds,
+        None => return Ok(())
+    };
+
+    if from > last {
+        if let Some((_, refreshed)) = get_checked_bounds(pool, dataset, true).await? {
+            last = refreshed;
+        }
+    }
+
+    match from > last || to < first {
+        true => Err(MaskErr::DateOutOfRange {
+            dataset: dataset.to_owned(),
+            first_day: first.format("%Y-%m-%d").to_string(),
+            last_day: last.format("%Y-%m-%d").to_string()
+        }),
+        false => Ok(())
+    }
+}
+
+/// Clamp the end of a date range to the last day available in a dataset. The last day is
+/// returned when it replaces the end. The end must not be before the first day available
+///
+/// # Arguments
+/// * `pool` - &PGPool
+/// * `dataset` - &str
+/// * `end` - D
+async fn clamp_end<D: Date + From<NaiveDate>>(pool: &PGPool, dataset: &str, end: D) -> Result<(D, Option<String>), MaskErr> {
+    // an invalid date is rejected by the handler
+    let to = match utils::build_date_range(&end, &end) {
+        Ok((_, to)) => to,
+        Err(_) => return Ok((end, None))
+    };
+
+    let (first, last) = match get_checked_bounds(pool, dataset, false).await? {
+        Some(bounds) => bounds,
+        None => return Ok((end, None))
+    };
+
+    if to < first {
+        return Err(MaskErr::DateOutOfRange {
+            dataset: dataset.to_owned(),
+            first_day: first.format("%Y-%m-%d").to_string(),
+            last_day: last.format("%Y-%m-%d").to_string()
+        });
+    }
+
+    match to > last {
+        true => Ok((D::from(last), Some(last.format("%Y-%m-%d").to_string()))),
+        false => Ok((end, None))
+    }
+}
+
+/// Resolve the date of an input. When the latest day is requested, the date is replaced by
+/// the last day available in the dataset. This day is also returned in order to be reported
+/// in the response. Otherwise the date must overlap the days available
+///
+/// # Arguments
+/// * `pool` - &PGPool
+/// * `dataset` - &str
+/// * `date` - D
+pub async fn resolve_date<D: Date + From<NaiveDate>>(pool: &PGPool, dataset: &str, date: D) -> Result<(D, Option<String>), MaskErr> {
+    if !date.is_latest() {
+        check_date_range(pool, dataset, &date).await?;
+        return Ok((date, None));
+    }
+
+    let day = get_latest_day(pool, dataset).await?;
+
+    Ok((D::from(day), Some(day.format("%Y-%m-%d").to_string())))
+}
+
+/// Resolve the boundaries of a date range. The start is resolved by `resolve_date` and the
+/// end is either the latest day or clamped to the last day available, in which case the day
+/// is returned in order to be reported in the response
+///
+/// # Arguments
+/// * `pool` - &PGPool
+/// * `dataset` - &str
+/// * `start` - D
+/// * `end` - D
+pub async fn resolve_range<D: Date + From<NaiveDate>>(
+    pool: &PGPool,
+    dataset: &str,
+    start: D,
+    end: D
+) -> Result<(D, D, Option<String>), MaskErr> {
+    let (start, resolved_start) = resolve_date(pool, dataset, start).await?;
+    let (end, resolved_end) = match end.is_latest() {
+        true => resolve_date(pool, dataset, end).await?,
+        false => clamp_end(pool, dataset, end).await?
+    };
+
+    Ok((start, end, resolved_end.or(resolved_start)))
+}
+
+/// Build a response with the version & the last available date of the datasets used by a RPC.
+/// The response is returned without the metadata if the import log can't be read
+///
+/// # Arguments
+/// * `pool` - &PGPool
+/// * `datasets` - &[&str]
+/// * `body` - T
+pub async fn respond<T>(pool: &PGPool, datasets: &[&str], body: T) -> Response<T> {
+    respond_with_date(pool, datasets, None, body).await
+}
+
+/// Build the headers with the metadata of the datasets and the day used when the latest
+/// day has been requested. Only the resolved day is returned if the import log can't be read
+///
+/// # Arguments
+/// * `pool` - &PGPool
+/// * `datasets` - &[&str]
+/// * `resolved` - Option<String>
+pub async fn build_headers(pool: &PGPool, datasets: &[&str], resolved: Option<String>) -> Vec<(&'static str, String)> {
+    let mut headers = Vec::new();
+    if let Some(resolved) = resolved {
+        headers.push((RESOLVED_DATE_HEADER, resolved));
+    }
+
+    let names: Vec<String> = datasets.iter().map(|d| d.to_string()).collect();
+    let imports = match get_imports(pool, Some(names), false).await {
+        Ok(imports) => imports,
+        Err(err) => {
+            warn!("unable to read the import log {:?}", err);
+            return headers;
+        }
+    };
+
+    // keep the order of the datasets. Datasets which were never imported are skipped
+    let imports: Vec<&DatasetImport> = datasets
+        .iter()
+        .filter_map(|d| imports.iter().find(|i| i.dataset == *d))
+        .collect();
+
+    if imports.is_empty() {
+        return headers;
+    }
+
+    headers.extend([
+        (DATASET_HEADER, imports.iter().map(|i| i.dataset.as_str()).collect::<Vec<_>>().join(",")),
+        (VERSION_HEADER, imports.iter().map(|i| i.file_hash.as_str()).collect::<Vec<_>>().join(",")),
+        (LAST_DATE_HEADER, imports.iter().map(|i| i.max_date.as_deref().unwrap_or_default()).collect::<Vec<_>>().join(",")),
+        (IMPORTED_AT_HEADER, imports.iter().map(|i| i.imported_at.as_str()).collect::<Vec<_>>().join(","))
+    ]);
+
+    headers
+}
+
+/// Build a response with the metadata of the datasets and the day used by the RPC when
+/// the latest day has been requested
+///
+/// # Arguments
+/// * `pool` - &PGPool
+/// * `datasets` - &[&str]
+/// * `resolved` - Option<String>
+/// * `body` - T
+pub async fn respond_with_date<T>(pool: &PGPool, datasets: &[&str], resolved: Option<String>, body: T) -> Response<T> {
+    let mut res = Response::new(body);
+    for (key, value) in build_headers(pool, datasets, resolved).await {
+        if let Ok(value) = MetadataValue::from_str(&value) {
+            res.metadata_mut().insert(key, value);
+        }
+    }
+
+    res
+}
+
+pub struct MetadataHandler {
+    pub pool: Arc<PGPool>
+}
+
+#[tonic::async_trait]
+impl MetadataService for MetadataHandler {
+    /// List the imports of the datasets
+    ///
+    /// # Arguments
+    /// * `&self`
+    /// * `request` - Request<ImportInput>
+    async fn list_imports(
+        &self,
+        request: Request<ImportInput>
+    ) -> Result<Response<ImportOutput>, Status> {
+        let input = request.into_inner();
+        match get_imports(&self.pool, input.dataset.map(|d| vec![d]), input.history).await {
+            Ok(imports) => Ok(Response::new(ImportOutput { imports })),
+            Err(err) => Err(MaskErr::query("import log", err).into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Datelike;
+    use utils::common::proto_common::CommonInput;
+
+    #[tokio::test]
+    async fn expect_grpc_to_return_last_import_of_each_dataset() {
+        let pool = db::connect("../config.toml").await.unwrap();
+        let handler = MetadataHandler { pool: Arc::new(pool) };
+
+        let request = Request::new(ImportInput { dataset: None, history: false });
+        let imports = handler.list_imports(request).await.unwrap().into_inner().imports;
+
+        assert!(imports.iter().any(|i| i.dataset == "hospitalization"));
+        assert!(imports.windows(2).all(|w| w[0].dataset != w[1].dataset));
+    }
+
+    #[tokio::test]
+    async fn expect_response_to_contain_metadata() {
+        let pool = db::connect("../config.toml").await.unwrap();
+        let res = respond(&pool, &["vaxx", "unknown", "unvaxx"], ()).await;
+
+        let datasets = res.metadata().get(DATASET_HEADER).unwrap().to_str().unwrap();
+        assert_eq!(datasets, "vaxx,unvaxx");
+        assert!(res.metadata().get(VERSION_HEADER).is_some());
+        assert!(res.metadata().get(LAST_DATE_HEADER).is_some());
+        assert!(res.metadata().get(RESOLVED_DATE_HEADER).is_none());
+    }
+
+    #[tokio::test]
+    async fn expect_to_get_latest_day() {
+        let pool = db::connect("../config.toml").await.unwrap();
+        let day = get_latest_day(&pool, "hospitalization").await.unwrap();
+        let res = respond_with_date(&pool, &["hospitalization"], Some(day.format("%Y-%m-%d").to_string()), ()).await;
+
+        let resolved = res.metadata().get(RESOLVED_DATE_HEADER).unwrap().to_str().unwrap();
+        let last = res.metadata().get(LAST_DATE_HEADER).unwrap().to_str().unwrap();
+        assert_eq!(resolved, last);
+    }
+
+    #[tokio::test]
+    async fn expect_latest_day_to_be_resolved() {
+        let pool = db::connect("../config.toml").await.unwrap();
+        let last_day = get_latest_day(&pool, "hospitalization").await.unwrap();
+        let latest = CommonInput { day: None, month: 0, year: 0, latest: true };
+
+        let (date, resolved) = resolve_date(&pool, "hospitalization", latest).await.unwrap();
+        assert_eq!(date, CommonInput::from(last_day));
+        assert_eq!(resolved, Some(last_day.format("%Y-%m-%d").to_string()));
+
+        let (date, resolved) = resolve_date(&pool, "hospitalization", CommonInput::from(last_day)).await.unwrap();
+        assert_eq!(date.build_naive_date().unwrap(), last_day);
+        assert!(resolved.is_none());
+    }
+
+    #[tokio::test]
+    async fn expect_date_after_last_day_to_be_out_of_range() {
+        struct Day(i32, i32, Option<i32>);
+        impl Date for Day {
+            fn get_year(&self) -> i32 { self.0 }
+            fn get_month(&self) -> i32 { self.1 }
+            fn get_day(&self) -> Option<i32> { self.2 }
+        }
+
+        let pool = db::connect("../config.toml").await.unwrap();
+        let last_day = get_latest_day(&pool, "hospitalization").await.unwrap();
+        assert!(check_date_range(&pool, "hospitalization", &Day(last_day.year(), last_day.month() as i32, None)).await.is_ok());
+
+        let res = check_date_range(&pool, "hospitalization", &Day(last_day.year() + 1, 1, Some(1))).await;
+        assert!(matches!(res, Err(MaskErr::DateOutOfRange { .. })));
+    }
+
+    #[tokio::test]
+    async fn expect_date_before_first_day_to_be_out_of_range() {
+        let pool = db::connect("../config.toml").await.unwrap();
+        let (first_day, _) = get_day_bounds(&pool, "hospitalization", false).await.unwrap();
+
+        let before = CommonInput::from(first_day.pred_opt().unwrap());
+        let res = check_date_range(&pool, "hospitalization", &before).await;
+        assert!(matches!(res, Err(MaskErr::DateOutOfRange { first_day: day, .. }) if day == first_day.format("%Y-%m-%d").to_string()));
+        assert!(check_date_range(&pool, "hospitalization", &CommonInput::from(first_day)).await.is_ok());
+
+        let res = resolve_range(&pool, "hospitalization", CommonInput::from(first_day - chrono::Duration::days(10)), before).await;
+        assert!(matches!(res, Err(MaskErr::DateOutOfRange { .. })));
+    }
+
+    #[test]
+    fn expect_last_day_of_a_week_to_be_its_end() {
+        assert_eq!(parse_last_day("2022-02-21-2022-02-27").unwrap(), NaiveDate::from_ymd_opt(2022, 2, 27).unwrap());
+        assert_eq!(parse_last_day("2022-02-21 12:00:00").unwrap(), NaiveDate::from_ymd_opt(2022, 2, 21).unwrap());
+    }
+
+    #[tokio::test]
+    async fn expect_latest_week_to_resolve_like_the_clamped_end() {
+        let pool = db::connect("../config.toml").await.unwrap();
+        let latest = CommonInput { day: None, month: 0, year: 0, latest: true };
+        let (_, resolved_latest) = resolve_date(&pool, "variants_region", latest).await.unwrap();
+
+        let (_, last_day) = get_day_bounds(&pool, "variants_region", false).await.unwrap();
+        let start = CommonInput::from(last_day - chrono::Duration::days(14));
+        let open_end = CommonInput::from(last_day + chrono::Duration::days(30));
+        let (_, _, resolved_end) = resolve_range(&pool, "variants_region", start, open_end).await.unwrap();
+
+        assert_eq!(resolved_latest, resolved_end);
+        assert_eq!(resolved_latest, Some(last_day.format("%Y-%m-%d").to_string()));
+    }
+
+    #[tokio::test]
+    async fn expect_end_of_range_to_be_clamped_to_last_day() {
+        let pool = db::connect("../config.toml").await.unwrap();
+        let (_, last_day) = get_day_bounds(&pool, "hospitalization", false).await.unwrap();
+        let start = CommonInput::from(last_day - chrono::Duration::days(5));
+
+        let after = CommonInput::from(last_day + chrono::Duration::days(30));
+        let (_, end, resolved) = resolve_range(&pool, "hospitalization", start.clone(), after).await.unwrap();
+        assert_eq!(end, CommonInput::from(last_day));
+        assert_eq!(resolved, Some(last_day.format("%Y-%m-%d").to_string()));
+
+        let (_, end, resolved) = resolve_range(&pool, "hospitalization", start, CommonInput::from(last_day)).await.unwrap();
+        assert_eq!(end, CommonInput::from(last_day));
+        assert!(resolved.is_none());
+    }
+}
@@ -6,6 +6,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             &[
                 "../proto/pcr.proto",
                 "../proto/positivity.proto",
+                "../proto/reproduction.proto",
+                "../proto/variant.proto",
             ], 
             &["../proto"]
         )?;
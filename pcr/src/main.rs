@@ -6,6 +6,8 @@ extern crate log;
 
 mod pcr;
 mod positivity;
+mod reproduction;
+mod variant;
 mod common;
 
 use pcr::{
@@ -16,6 +18,15 @@ use positivity::{
     dep::PosServiceHandle,
     proto::positivity_rate_server::PositivityRateServer
 };
+use reproduction::{
+    rt::ReproductionHandle,
+    proto::reproduction_service_server::ReproductionServiceServer
+};
+use variant::{
+    share::VariantHandle,
+    proto::variant_service_server::VariantServiceServer
+};
+use metadata::{MetadataHandler, MetadataServiceServer};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -33,6 +44,15 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .add_service(PositivityRateServer::new(PosServiceHandle {
             pool: Arc::clone(&db_handle)
         }))
+        .add_service(ReproductionServiceServer::new(ReproductionHandle {
+            pool: Arc::clone(&db_handle)
+        }))
+        .add_service(VariantServiceServer::new(VariantHandle {
+            pool: Arc::clone(&db_handle)
+        }))
+        .add_service(MetadataServiceServer::new(MetadataHandler {
+            pool: Arc::clone(&db_handle)
+        }))
         .serve(addr);
 
     info!("Starting the server port 9090 & Healthcheck server port 5601");
@@ -7,14 +7,45 @@ use sqlx::{
 use tonic::{Request, Response, Status};
 use utils::{
     Date,
-    err::MaskErr
+    err::MaskErr,
+    field_mask::{FieldMask, Masked},
+    page::{Page, PageKey, Paginated},
+    smoothing::{self, Smoothable, Smoothing}
 };
-use crate::common::proto_common::CommonInput;
 use super::proto::{
     pcr_service_server::PcrService,
-    PcrInput, PcrOutput, PcrResult
+    PcrInput, PcrOutput, PcrResult, SmoothedPcr
 };
 
+// Keys of the pages of each area. The rows of the country have no area
+const DEPARTMENT_KEYS: &str = "jour, dep, cl_age90";
+const REGION_KEYS: &str = "jour, reg::text, cl_age90";
+const COUNTRY_KEYS: &str = "jour, ''::text, cl_age90";
+
+// Fields of PcrResult which can be requested by a field mask
+const PCR_FIELDS: [&str; 13] = [
+    "day", "age", "region", "population_by_region", "positive_pcr_test_male", "positive_pcr_test_female",
+    "total_positive_pcr_test", "pcr_test_male", "pcr_test_female", "total_pcr_test_done", "department",
+    "population_by_department", "smoothed"
+];
+
+// Columns of each table needed by the fields of PcrResult. The keys of the rows are always selected
+const DEPARTMENT_COLUMNS: [(&str, &[&str]); 3] = [
+    ("population_by_department", &["pop"]),
+    ("total_positive_pcr_test", &["p"]),
+    ("total_pcr_test_done", &["t"])
+];
+
+const REGION_COLUMNS: [(&str, &[&str]); 7] = [
+    ("population_by_region", &["pop"]),
+    ("positive_pcr_test_male", &["p_h"]),
+    ("positive_pcr_test_female", &["p_f"]),
+    ("total_positive_pcr_test", &["p"]),
+    ("pcr_test_male", &["t_h"]),
+    ("pcr_test_female", &["t_f"]),
+    ("total_pcr_test_done", &["t"])
+];
+
 pub struct PcrServiceHandle {
     pub pool: Arc<PGPool>
 }
@@ -36,13 +67,77 @@ impl TryFrom<PgRow> for PcrResult {
             pcr_test_female: value.try_get("t_f").ok(),
             pcr_test_male: value.try_get("t_h").ok(),
             region: value.try_get("reg").ok(),
-            population_by_region: value.try_get("pop").ok()
+            population_by_region: value.try_get("pop").ok(),
+            smoothed: None
         };
 
         Ok(res)
     }
 }
 
+impl PcrResult {
+    /// Return the code of the area of the result. Empty for the whole country
+    ///
+    /// # Arguments
+    /// * `&self` - Self
+    fn area(&self) -> String {
+        self.department.clone()
+            .or_else(|| self.region.map(|r| r.to_string()))
+            .unwrap_or_default()
+    }
+}
+
+impl Paginated for PcrResult {
+    fn page_key(&self) -> PageKey {
+        PageKey { day: self.day.clone(), area: self.area(), age: self.age }
+    }
+}
+
+impl Masked for PcrResult {
+    fn clear(&mut self, field: &str) {
+        match field {
+            "day" => self.day = String::new(),
+            "age" => self.age = 0,
+            "region" => self.region = None,
+            "population_by_region" => self.population_by_region = None,
+            "positive_pcr_test_male" => self.positive_pcr_test_male = None,
+            "positive_pcr_test_female" => self.positive_pcr_test_female = None,
+            "total_positive_pcr_test" => self.total_positive_pcr_test = None,
+            "pcr_test_male" => self.pcr_test_male = None,
+            "pcr_test_female" => self.pcr_test_female = None,
+            "total_pcr_test_done" => self.total_pcr_test_done = None,
+            "department" => self.department = None,
+            "population_by_department" => self.population_by_department = None,
+            "smoothed" => self.smoothed = None,
+            _ => {}
+        }
+    }
+}
+
+impl Smoothable for PcrResult {
+    fn series(&self) -> String {
+        format!("{}-{}", self.area(), self.age)
+    }
+
+    fn day(&self) -> &str {
+        &self.day
+    }
+
+    fn values(&self) -> Vec<f64> {
+        vec![
+            self.total_positive_pcr_test.unwrap_or_default() as f64,
+            self.total_pcr_test_done.unwrap_or_default() as f64
+        ]
+    }
+
+    fn set_smoothed(&mut self, values: Vec<f64>) {
+        self.smoothed = Some(SmoothedPcr {
+            total_positive_pcr_test: values[0],
+            total_pcr_test_done: values[1]
+        });
+    }
+}
+
 #[tonic::async_trait]
 impl PcrService for PcrServiceHandle {
     /// Get the list of pcr test made per department
@@ -59,26 +154,51 @@ impl PcrService for PcrServiceHandle {
             return Err(MaskErr::MissingDate.into());
         }
 
-        let date: CommonInput = input.date.unwrap().into();
-        let date = date.build_date_sql_like()?;
+        let (input_date, resolved) = metadata::resolve_date(&self.pool, "pcr_test_department", input.date.unwrap()).await?;
+        let date = input_date.build_date_sql_like()?;
+        let smoothing = smoothing::from_options(input.smoothing)?;
+
+        let page = Page::new(input.page_size, &input.page_token)?;
+        let mask = FieldMask::new(input.fields.map(|f| f.paths).unwrap_or_default(), &PCR_FIELDS)?;
 
         let department = match input.department {
             Some(dep) => dep,
             None => return Err(MaskErr::MissingParam("department".to_owned()).into())
         };
+        geography::validate_department(&department)?;
 
-        match query::get_all_by_date_and_gen_field::<PcrResult, &str>(
+        let columns = mask.select(&DEPARTMENT_COLUMNS, &["jour", "dep", "cl_age90"]);
+        let sql = query::build_page_query(
+            &format!("SELECT {columns} FROM pcr_test_department WHERE jour LIKE $1 AND dep = $2"),
+            DEPARTMENT_KEYS,
+            3
+        );
+        let mut pcr = match query::get_page::<PcrResult>(
             &self.pool,
-            "SELECT * FROM pcr_test_department WHERE jour LIKE $1 AND dep = $2",
-            &date,
-            &department
+            sqlx::query(&sql).bind(&date).bind(&department),
+            page.after(),
+            page.limit()
         ).await {
-            Ok(pcr) => Ok(Response::new(PcrOutput { pcr })),
-            Err(err) => {
-                error!("fetch pcr by department {:?}", err);
-                return Err(MaskErr::QueryError("pcr by department".into()).into());
+            Ok(pcr) => pcr,
+            Err(err) => return Err(MaskErr::query("pcr by department", err).into())
+        };
+        let next_page_token = page.next_token(&mut pcr);
+
+        if let Some(smoothing) = smoothing {
+            if let Err(err) = apply_smoothing(
+                &self.pool,
+                smoothing,
+                &mut pcr,
+                "SELECT * FROM pcr_test_department WHERE jour >= $1 AND jour <= $2 AND dep = $3",
+                Some(department.as_str())
+            ).await {
+                return Err(MaskErr::query("pcr history by department", err).into());
             }
         }
+
+        mask.apply(&mut pcr, &PCR_FIELDS);
+
+        Ok(metadata::respond_with_date(&self.pool, &["pcr_test_department"], resolved, PcrOutput { pcr, next_page_token }).await)
     }
 
     /// Retrieve PCR test made by region
@@ -96,26 +216,51 @@ impl PcrService for PcrServiceHandle {
             return Err(MaskErr::MissingDate.into());
         }
 
-        let date: CommonInput = input.date.unwrap().into();
-        let date = date.build_date_sql_like()?;
+        let (input_date, resolved) = metadata::resolve_date(&self.pool, "pcr_test_region", input.date.unwrap()).await?;
+        let date = input_date.build_date_sql_like()?;
+        let smoothing = smoothing::from_options(input.smoothing)?;
+
+        let page = Page::new(input.page_size, &input.page_token)?;
+        let mask = FieldMask::new(input.fields.map(|f| f.paths).unwrap_or_default(), &PCR_FIELDS)?;
 
         let region = match input.region {
             Some(reg) => reg,
             None => return Err(MaskErr::MissingParam("region".to_owned()).into())
         };
+        geography::validate_region(region)?;
 
-        match query::get_all_by_date_and_gen_field::<PcrResult, i32>(
+        let columns = mask.select(&REGION_COLUMNS, &["jour", "reg", "cl_age90"]);
+        let sql = query::build_page_query(
+            &format!("SELECT {columns} FROM pcr_test_region WHERE jour LIKE $1 AND reg = $2"),
+            REGION_KEYS,
+            3
+        );
+        let mut pcr = match query::get_page::<PcrResult>(
             &self.pool,
-            "SELECT * FROM pcr_test_region WHERE jour LIKE $1 AND reg = $2",
-            &date,
-            region
+            sqlx::query(&sql).bind(&date).bind(region),
+            page.after(),
+            page.limit()
         ).await {
-            Ok(pcr) => Ok(Response::new(PcrOutput { pcr })),
-            Err(err) => {
-                error!("fetch pcr test by region {:?}", err);
-                return Err(MaskErr::QueryError("pcr by region".into()).into());
+            Ok(pcr) => pcr,
+            Err(err) => return Err(MaskErr::query("pcr by region", err).into())
+        };
+        let next_page_token = page.next_token(&mut pcr);
+
+        if let Some(smoothing) = smoothing {
+            if let Err(err) = apply_smoothing(
+                &self.pool,
+                smoothing,
+                &mut pcr,
+                "SELECT * FROM pcr_test_region WHERE jour >= $1 AND jour <= $2 AND reg = $3",
+                Some(region)
+            ).await {
+                return Err(MaskErr::query("pcr test history by region", err).into());
             }
         }
+
+        mask.apply(&mut pcr, &PCR_FIELDS);
+
+        Ok(metadata::respond_with_date(&self.pool, &["pcr_test_region"], resolved, PcrOutput { pcr, next_page_token }).await)
     }
 
     /// Retrieve the pcr made in the whole country
@@ -132,27 +277,85 @@ impl PcrService for PcrServiceHandle {
             return Err(MaskErr::MissingDate.into());
         }
 
-        let date: CommonInput = input.date.unwrap().into();
-        let date = date.build_date_sql_like()?;
+        let (input_date, resolved) = metadata::resolve_date(&self.pool, "pcr_country", input.date.unwrap()).await?;
+        let date = input_date.build_date_sql_like()?;
+        let smoothing = smoothing::from_options(input.smoothing)?;
+        let page = Page::new(input.page_size, &input.page_token)?;
+        let mask = FieldMask::new(input.fields.map(|f| f.paths).unwrap_or_default(), &PCR_FIELDS)?;
 
-        match query::get_all_by_date_only::<PcrResult>(
+        // the country has the same columns as the regions
+        let columns = mask.select(&REGION_COLUMNS, &["jour", "cl_age90"]);
+        let sql = query::build_page_query(&format!("SELECT {columns} FROM pcr_country WHERE jour LIKE $1"), COUNTRY_KEYS, 2);
+        let mut pcr = match query::get_page::<PcrResult>(
             &self.pool,
-            "SELECT * FROM pcr_country WHERE jour LIKE $1",
-            &date
+            sqlx::query(&sql).bind(&date),
+            page.after(),
+            page.limit()
         ).await {
-            Ok(pcr) => Ok(Response::new(PcrOutput { pcr })),
-            Err(err) => {
-                error!("fetch pcr test in the whole country {:?}", err);
-                return Err(MaskErr::QueryError("pcr pcr test in the whole country".into()).into());
+            Ok(pcr) => pcr,
+            Err(err) => return Err(MaskErr::query("pcr pcr test in the whole country", err).into())
+        };
+        let next_page_token = page.next_token(&mut pcr);
+
+        if let Some(smoothing) = smoothing {
+            if let Err(err) = apply_smoothing::<i32>(
+                &self.pool,
+                smoothing,
+                &mut pcr,
+                "SELECT * FROM pcr_country WHERE jour >= $1 AND jour <= $2",
+                None
+            ).await {
+                return Err(MaskErr::query("pcr test history in the whole country", err).into());
             }
         }
+
+        mask.apply(&mut pcr, &PCR_FIELDS);
+
+        Ok(metadata::respond_with_date(&self.pool, &["pcr_country"], resolved, PcrOutput { pcr, next_page_token }).await)
     }
 }
 
+/// Fetch the history of the pcr test needed to smooth the pcr test of a page and smooth them.
+/// Only the days of the page & of the window are read. The query expects the first & last day
+/// of the history as $1 & $2 and the optional area as $3
+///
+/// # Arguments
+/// * `pool` - &PGPool
+/// * `smoothing` - Smoothing
+/// * `pcr` - &mut [PcrResult]
+/// * `query` - &str
+/// * `area` - Option<I>
+async fn apply_smoothing<I>(
+    pool: &PGPool,
+    smoothing: Smoothing,
+    pcr: &mut [PcrResult],
+    query: &str,
+    area: Option<I>
+) -> Result<(), MaskErr>
+where
+    I: for<'q> sqlx::Encode<'q, sqlx::Postgres> + sqlx::Type<sqlx::Postgres> + std::marker::Send
+{
+    let (from, to) = match smoothing.rows_range(pcr) {
+        Some(range) => range,
+        None => return Ok(())
+    };
+    let (start, end) = (from.format("%Y-%m-%d").to_string(), to.format("%Y-%m-%d").to_string());
+
+    let history = match area {
+        Some(area) => query::get_all_by_date_range_and_gen_field::<PcrResult, I>(pool, query, &start, &end, area).await?,
+        None => query::get_all_by_date_range::<PcrResult>(pool, query, &start, &end).await?
+    };
+    smoothing.apply(pcr, &history, from, to);
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use super::super::common::CommonInput as PCommandInput;
+    use prost::Message;
+    use utils::err::rpc;
+    use super::super::common::{CommonInput as PCommandInput, SmoothingOptions};
 
     #[tokio::test]
     async fn expect_grpc_dep_to_return_ok() {
@@ -166,10 +369,15 @@ mod tests {
             date: Some(PCommandInput {
                 day: Some(1),
                 month: 12,
-                year: 2021
+                year: 2021,
+                latest: false
             }),
             department: Some("75".to_string()),
-            region: None
+            region: None,
+            smoothing: None,
+            page_size: 0,
+            page_token: String::new(),
+            fields: None
         };
 
         let request = Request::new(input);
@@ -190,10 +398,15 @@ mod tests {
             date: Some(PCommandInput {
                 day: Some(12),
                 month: 12,
-                year: 2021
+                year: 2021,
+                latest: false
             }),
             region: Some(93),
-            department: None
+            department: None,
+            smoothing: None,
+            page_size: 0,
+            page_token: String::new(),
+            fields: None
         };
 
         let request = Request::new(input);
@@ -214,10 +427,15 @@ mod tests {
             date: Some(PCommandInput {
                 day: Some(12),
                 month: 12,
-                year: 2021
+                year: 2021,
+                latest: false
             }),
             region: None,
-            department: None
+            department: None,
+            smoothing: None,
+            page_size: 0,
+            page_token: String::new(),
+            fields: None
         };
 
         let request = Request::new(input);
@@ -226,6 +444,36 @@ mod tests {
         assert!(res.is_ok());
     }
 
+    #[tokio::test]
+    async fn expect_grpc_country_to_resolve_latest_day() {
+        let pool = db::connect("../config.toml").await.unwrap();
+        let pool_arc = Arc::new(pool);
+        let service = PcrServiceHandle {
+            pool: Arc::clone(&pool_arc)
+        };
+
+        let input = PcrInput {
+            date: Some(PCommandInput {
+                day: None,
+                month: 0,
+                year: 0,
+                latest: true
+            }),
+            region: None,
+            department: None,
+            smoothing: None,
+            page_size: 0,
+            page_token: String::new(),
+            fields: None
+        };
+
+        let request = Request::new(input);
+        let res = service.get_pcr_test_made_country(request).await.unwrap();
+
+        assert!(res.metadata().get("x-resolved-date").is_some());
+        assert!(!res.get_ref().pcr.is_empty());
+    }
+
     #[tokio::test]
     async fn expect_grpc_to_return_error() {
         let pool = db::connect("../config.toml").await.unwrap();
@@ -238,10 +486,15 @@ mod tests {
             date: Some(PCommandInput {
                 day: Some(2222222),
                 month: 12,
-                year: 2021
+                year: 2021,
+                latest: false
             }),
             department: Some("75".to_string()),
-            region: None
+            region: None,
+            smoothing: None,
+            page_size: 0,
+            page_token: String::new(),
+            fields: None
         };
 
         let request = Request::new(input);
@@ -249,4 +502,200 @@ mod tests {
         
         assert!(res.is_err());
     }
+
+    #[tokio::test]
+    async fn expect_grpc_region_to_return_error_for_unknown_region() {
+        let pool = db::connect("../config.toml").await.unwrap();
+        let pool_arc = Arc::new(pool);
+        let service = PcrServiceHandle {
+            pool: Arc::clone(&pool_arc)
+        };
+
+        let input = PcrInput {
+            date: Some(PCommandInput {
+                day: Some(12),
+                month: 12,
+                year: 2021,
+                latest: false
+            }),
+            region: Some(12),
+            department: None,
+            smoothing: None,
+            page_size: 0,
+            page_token: String::new(),
+            fields: None
+        };
+
+        let request = Request::new(input);
+        let res = service.get_pcr_test_made_by_region(request).await;
+
+        assert_eq!(res.unwrap_err().code(), tonic::Code::InvalidArgument);
+    }
+
+    #[tokio::test]
+    async fn expect_grpc_dep_to_return_smoothed_pcr() {
+        let pool = db::connect("../config.toml").await.unwrap();
+        let pool_arc = Arc::new(pool);
+        let service = PcrServiceHandle {
+            pool: Arc::clone(&pool_arc)
+        };
+
+        let input = PcrInput {
+            date: Some(PCommandInput {
+                day: Some(1),
+                month: 12,
+                year: 2021,
+                latest: false
+            }),
+            department: Some("75".to_string()),
+            region: None,
+            smoothing: Some(SmoothingOptions::default()),
+            page_size: 0,
+            page_token: String::new(),
+            fields: None
+        };
+
+        let request = Request::new(input);
+        let res = service.get_pcr_test_made_by_department(request).await.unwrap();
+
+        assert!(res.get_ref().pcr.iter().all(|p| p.smoothed.is_some()));
+    }
+
+    #[tokio::test]
+    async fn expect_grpc_dep_to_return_pages() {
+        let pool = db::connect("../config.toml").await.unwrap();
+        let service = PcrServiceHandle {
+            pool: Arc::new(pool)
+        };
+
+        let input = |page_size, page_token| PcrInput {
+            date: Some(PCommandInput {
+                day: None,
+                month: 12,
+                year: 2021,
+                latest: false
+            }),
+            department: Some("75".to_string()),
+            region: None,
+            smoothing: Some(SmoothingOptions::default()),
+            page_size,
+            page_token,
+            fields: None
+        };
+
+        let all = service.get_pcr_test_made_by_department(Request::new(input(0, String::new()))).await.unwrap().into_inner();
+        assert!(all.next_page_token.is_empty());
+
+        let mut pcr = Vec::new();
+        let mut token = String::new();
+        loop {
+            let page = service.get_pcr_test_made_by_department(Request::new(input(100, token))).await.unwrap().into_inner();
+            assert!(page.pcr.len() <= 100);
+
+            pcr.extend(page.pcr);
+            token = page.next_page_token;
+            if token.is_empty() {
+                break;
+            }
+        }
+
+        // the smoothing of a page uses the history of its days
+        assert_eq!(pcr, all.pcr);
+        assert!(pcr.windows(2).all(|w| (&w[0].day, w[0].age) < (&w[1].day, w[1].age)));
+    }
+
+    #[tokio::test]
+    async fn expect_grpc_country_to_return_error_for_invalid_token() {
+        let pool = db::connect("../config.toml").await.unwrap();
+        let service = PcrServiceHandle {
+            pool: Arc::new(pool)
+        };
+
+        let input = PcrInput {
+            date: Some(PCommandInput {
+                day: Some(12),
+                month: 12,
+                year: 2021,
+                latest: false
+            }),
+            region: None,
+            department: None,
+            smoothing: None,
+            page_size: 5,
+            page_token: "invalid".to_owned(),
+            fields: None
+        };
+
+        let res = service.get_pcr_test_made_country(Request::new(input)).await;
+        assert_eq!(res.unwrap_err().code(), tonic::Code::InvalidArgument);
+    }
+
+    #[tokio::test]
+    async fn expect_grpc_region_to_return_requested_fields() {
+        let pool = db::connect("../config.toml").await.unwrap();
+        let service = PcrServiceHandle {
+            pool: Arc::new(pool)
+        };
+
+        let input = |paths: Vec<&str>| PcrInput {
+            date: Some(PCommandInput {
+                day: Some(12),
+                month: 12,
+                year: 2021,
+                latest: false
+            }),
+            region: Some(93),
+            department: None,
+            smoothing: Some(SmoothingOptions::default()),
+            page_size: 0,
+            page_token: String::new(),
+            fields: Some(prost_types::FieldMask { paths: paths.into_iter().map(str::to_owned).collect() })
+        };
+
+        let pcr = service.get_pcr_test_made_by_region(Request::new(input(vec!["age", "total_positive_pcr_test", "smoothed"]))).await.unwrap().into_inner().pcr;
+        assert!(!pcr.is_empty());
+        assert!(pcr.iter().all(|p| p.day.is_empty() && p.region.is_none() && p.population_by_region.is_none()));
+        assert!(pcr.iter().all(|p| p.total_positive_pcr_test.is_some() && p.smoothed.is_some()));
+
+        let res = service.get_pcr_test_made_by_region(Request::new(input(vec!["unknown"]))).await;
+        assert_eq!(res.unwrap_err().code(), tonic::Code::InvalidArgument);
+    }
+
+    #[tokio::test]
+    async fn expect_grpc_to_return_error_details() {
+        let pool = db::connect("../config.toml").await.unwrap();
+        let service = PcrServiceHandle {
+            pool: Arc::new(pool)
+        };
+
+        let input = |year: i32, department: &str| PcrInput {
+            date: Some(PCommandInput {
+                day: Some(1),
+                month: 1,
+                year,
+                latest: false
+            }),
+            region: None,
+            department: Some(department.to_owned()),
+            smoothing: None,
+            page_size: 0,
+            page_token: String::new(),
+            fields: None
+        };
+
+        let status = service.get_pcr_test_made_by_department(Request::new(input(2100, "75"))).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::OutOfRange);
+
+        let status = service.get_pcr_test_made_by_department(Request::new(input(2022, "999"))).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+
+        let details = rpc::Status::decode(status.details()).unwrap();
+        let bad_request = details.details
+            .iter()
+            .find(|d| d.type_url.ends_with("BadRequest"))
+            .map(|d| rpc::BadRequest::decode(d.value.as_slice()).unwrap())
+            .unwrap();
+
+        assert_eq!(bad_request.field_violations[0].field, "department");
+    }
 }
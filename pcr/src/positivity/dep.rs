@@ -4,15 +4,23 @@ use sqlx::{postgres::PgRow, Row};
 use tonic::{Request, Response, Status};
 use utils::{
     Date,
-    err::MaskErr
+    err::MaskErr,
+    smoothing::{self, Smoothable}
 };
-use crate::common::proto_common::CommonInput;
+use super::common::{AreaInput, DepartmentRanking, RankingResult, ranking_options::Period};
 use super::proto::{
     positivity_rate_server::PositivityRate,
     PositivityInput,
     PositivityCollection,
     PositivityDayResult,
-    PositivityWeekCollection
+    PositivityWeekCollection,
+    PositivityAreaCollection,
+    PositivityAreaResult,
+    DepartmentPositivity,
+    DepartmentWeekPositivity,
+    SmoothedPositivity,
+    PositivityRankingInput,
+    positivity_ranking_input::Metric
 };
 
 pub struct PosServiceHandle {
@@ -28,6 +36,45 @@ impl TryFrom<PgRow> for PositivityDayResult {
             day: value.try_get("jour")?,
             population_reference: value.try_get("pop")?,
             pcr_positive: value.try_get("p")?,
+            infection_rate: value.try_get("tx_std")?,
+            trend: query::get_trend(&value).map(Into::into),
+            smoothed: None
+        };
+
+        Ok(res)
+    }
+}
+
+impl Smoothable for PositivityDayResult {
+    fn series(&self) -> String {
+        self.department.clone()
+    }
+
+    fn day(&self) -> &str {
+        &self.day
+    }
+
+    fn values(&self) -> Vec<f64> {
+        vec![self.pcr_positive as f64, self.infection_rate]
+    }
+
+    fn set_smoothed(&mut self, values: Vec<f64>) {
+        self.smoothed = Some(SmoothedPositivity {
+            pcr_positive: values[0],
+            infection_rate: values[1]
+        });
+    }
+}
+
+impl TryFrom<PgRow> for PositivityAreaResult {
+    type Error = sqlx::Error;
+
+    fn try_from(value: PgRow) -> Result<Self, Self::Error> {
+        let res = Self {
+            region: None,
+            day: value.try_get("jour")?,
+            population_reference: value.try_get("pop")?,
+            pcr_positive: value.try_get("p")?,
             infection_rate: value.try_get("tx_std")?
         };
 
@@ -38,6 +85,8 @@ impl TryFrom<PgRow> for PositivityDayResult {
 #[tonic::async_trait]
 impl PositivityRate for PosServiceHandle {
     /// Retrieve the positivity rate by department and by day
+    /// Multiple departments can be queried at once, in this case the rates are grouped by department
+    /// The trend and the rolling mean of the infection rate can be included
     /// 
     /// # Arguments
     /// * `&self`
@@ -51,26 +100,59 @@ impl PositivityRate for PosServiceHandle {
             return Err(MaskErr::MissingDate.into());
         }
 
-        let date: CommonInput = input.date.unwrap().into();
-        let date = date.build_date_sql_like()?;
+        let (input_date, resolved) = metadata::resolve_date(&self.pool, "positivity_rate_per_dep_by_day", input.date.unwrap()).await?;
+        let date = input_date.build_date_sql_like()?;
+        let smoothing = smoothing::from_options(input.smoothing)?;
+        let (departments, multiple) = geography::resolve_departments(
+            &input.department,
+            &input.departments,
+            input.all_departments
+        )?;
+
+        let sql = match input.include_trend {
+            true => query::build_trend_query("positivity_rate_per_dep_by_day", "tx_std", "dep", "dep = ANY($2)"),
+            false => "SELECT * FROM positivity_rate_per_dep_by_day WHERE jour LIKE $1 AND dep = ANY($2) ORDER BY dep, jour".to_owned()
+        };
 
-        match query::get_all_by_date_and_gen_field::<PositivityDayResult, &str>(
+        let mut rates = match query::get_all_by_date_and_gen_field::<PositivityDayResult, Vec<String>>(
             &self.pool,
-            "SELECT * FROM positivity_rate_per_dep_by_day WHERE jour LIKE $1 AND dep = $2",
+            &sql,
             &date,
-            &input.department
+            departments.clone()
         ).await {
-            Ok(rates) => Ok(Response::new(PositivityCollection { rates })),
-            Err(err) => {
-                error!("fetch positivity cases {:?}", err);
-                Err(MaskErr::QueryError("positivity per day".into()).into())
+            Ok(rates) => rates,
+            Err(err) => return Err(MaskErr::query("positivity per day", err).into())
+        };
+
+        if let Some(smoothing) = smoothing {
+            let (from, to) = smoothing.history_range(&input_date)?;
+            match query::get_all_by_date_range_and_gen_field::<PositivityDayResult, Vec<String>>(
+                &self.pool,
+                "SELECT * FROM positivity_rate_per_dep_by_day WHERE jour >= $1 AND jour <= $2 AND dep = ANY($3)",
+                &from.format("%Y-%m-%d").to_string(),
+                &to.format("%Y-%m-%d").to_string(),
+                departments
+            ).await {
+                Ok(history) => smoothing.apply(&mut rates, &history, from, to),
+                Err(err) => return Err(MaskErr::query("positivity history", err).into())
             }
         }
+
+        if multiple {
+            let departments = geography::group_by_department(rates, |r| r.department.clone())
+                .into_iter()
+                .map(|(department, rates)| DepartmentPositivity { department, rates })
+                .collect();
+
+            return Ok(metadata::respond_with_date(&self.pool, &["positivity_rate_per_dep_by_day"], resolved, PositivityCollection { rates: Vec::new(), departments }).await);
+        }
+
+        Ok(metadata::respond_with_date(&self.pool, &["positivity_rate_per_dep_by_day"], resolved, PositivityCollection { rates, departments: Vec::new() }).await)
     }
 
     /// Get Positivity by the department for a week.
     /// Based on a given date. We're calculating the covid case / 100k for the last 7 days
-    /// including the given day
+    /// including the given day. Multiple departments can be queried at once
     /// 
     /// # Arguments
     /// * `&self`
@@ -84,57 +166,147 @@ impl PositivityRate for PosServiceHandle {
             return Err(MaskErr::MissingDate.into());
         }
 
-        let date: CommonInput = input.date.unwrap().into();
+        let (date, resolved) = metadata::resolve_date(&self.pool, "positivity_rate_per_dep_by_day", input.date.unwrap()).await?;
         let dates = match date.get_previous_seven_date_from_day() {
             Some(d) => d,
             None => return Err(MaskErr::InvalidDate.into())
         };
+        let (departments, multiple) = geography::resolve_departments(
+            &input.department,
+            &input.departments,
+            input.all_departments
+        )?;
 
-        let res = match get_positivity_for_week(&self.pool, dates, &input.department).await {
+        let res = match get_positivity_for_week(&self.pool, dates, departments).await {
             Ok(res) => res,
-            Err(err) => {
-                error!("fetch positivity cases per week {:?}", err);
-                return Err(MaskErr::QueryError("positivity cases per week".into()).into());
-            }
+            Err(err) => return Err(MaskErr::query("positivity cases per week", err).into())
         };
 
+        if multiple {
+            let departments = geography::group_by_department(res, |r| r.department.clone())
+                .into_iter()
+                .map(|(department, rates)| DepartmentWeekPositivity {
+                    department,
+                    week_infection_rate: calculate_positivity_per_week(&rates),
+                    rates
+                })
+                .collect();
+
+            return Ok(metadata::respond_with_date(&self.pool, &["positivity_rate_per_dep_by_day"], resolved, PositivityWeekCollection {
+                rates: Vec::new(),
+                week_infection_rate: 0.0,
+                departments
+            }).await);
+        }
+
         let week_infection_rate = calculate_positivity_per_week(&res);
-        Ok(Response::new(PositivityWeekCollection {
+        Ok(metadata::respond_with_date(&self.pool, &["positivity_rate_per_dep_by_day"], resolved, PositivityWeekCollection {
             rates: res,
-            week_infection_rate
-        }))
+            week_infection_rate,
+            departments: Vec::new()
+        }).await)
+    }
+
+    /// Retrieve the positivity rate of the departments of a region, or of the whole country
+    /// if no region is given, by day. The infection rate is weighted by the population of each department
+    /// 
+    /// # Arguments
+    /// * `&self`
+    /// * `request` - Request<AreaInput>
+    async fn get_positivity_by_area_per_day(
+        &self,
+        request: Request<AreaInput>
+    ) -> Result<Response<PositivityAreaCollection>, Status> {
+        let input = request.into_inner();
+        if input.date.is_none() {
+            return Err(MaskErr::MissingDate.into());
+        }
+
+        let (date, resolved) = metadata::resolve_date(&self.pool, "positivity_rate_per_dep_by_day", input.date.unwrap()).await?;
+        let date = date.build_date_sql_like()?;
+        let departments = geography::get_department_codes(input.region)?;
+
+        match query::get_all_by_date_and_gen_field::<PositivityAreaResult, Vec<String>>(
+            &self.pool,
+            "SELECT jour,
+                SUM(pop)::bigint AS pop,
+                SUM(p)::bigint AS p,
+                COALESCE(SUM(tx_std * pop) / NULLIF(SUM(pop), 0), 0) AS tx_std
+            FROM positivity_rate_per_dep_by_day WHERE jour LIKE $1 AND dep = ANY($2)
+            GROUP BY jour ORDER BY jour",
+            &date,
+            departments
+        ).await {
+            Ok(mut rates) => {
+                rates.iter_mut().for_each(|r| r.region = input.region);
+                Ok(metadata::respond_with_date(&self.pool, &["positivity_rate_per_dep_by_day"], resolved, PositivityAreaCollection { rates }).await)
+            },
+            Err(err) => Err(MaskErr::query("positivity by area per day", err).into())
+        }
+    }
+
+    /// Rank the departments by infection rate or by number of positive pcr test for a day
+    /// or for the last 7 days including the given day
+    /// 
+    /// # Arguments
+    /// * `&self`
+    /// * `request` - Request<PositivityRankingInput>
+    async fn get_department_ranking(
+        &self,
+        request: Request<PositivityRankingInput>
+    ) -> Result<Response<DepartmentRanking>, Status> {
+        let input = request.into_inner();
+        if input.date.is_none() {
+            return Err(MaskErr::MissingDate.into());
+        }
+
+        // the infection rate of the period is the number of positive tests per 100k inhabitants. The daily
+        // rates can't be summed as they're already relative to the population
+        let value = match input.metric() {
+            Metric::InfectionRate => "SUM(p) * 100000.0 / NULLIF(MAX(pop), 0)",
+            Metric::PcrPositive => "SUM(p)"
+        };
+
+        let (date, resolved) = metadata::resolve_date(&self.pool, "positivity_rate_per_dep_by_day", input.date.unwrap()).await?;
+        let day = date.build_naive_date()?;
+        let options = input.options.unwrap_or_default();
+        let (days, previous_days) = utils::get_ranking_days(day, options.period() == Period::Week);
+
+        let sql = query::build_ranking_query("positivity_rate_per_dep_by_day", value, None, options.ascending);
+        match query::get_ranking::<RankingResult>(
+            &self.pool,
+            &sql,
+            days,
+            previous_days,
+            (options.limit > 0).then_some(options.limit as i64)
+        ).await {
+            Ok(ranks) => Ok(metadata::respond_with_date(&self.pool, &["positivity_rate_per_dep_by_day"], resolved, DepartmentRanking { ranks }).await),
+            Err(err) => Err(MaskErr::query("positivity ranking", err).into())
+        }
     }
 }
 
-/// SQL query to get the positivity per week
+/// SQL query to get the positivity per week of a list of departments
 /// 
 /// # Arguments
 /// * `pool` - &PGPool
 /// * `dates` - Vec<String>
-/// * `department` - &str
-async fn get_positivity_for_week(pool: &PGPool, dates: Vec<String>, department: &str) -> Result<Vec<PositivityDayResult>, MaskErr> {
-    let mut rates = Vec::new();
-    for date in dates.iter() {
-        // @Warning
-        // We can't query the date between 2 date as they're string...
-        // It would be nice to convert the date to a datetime on the import.py script.
-        let res: Result<PgRow, sqlx::Error> = sqlx::query("SELECT * FROM positivity_rate_per_dep_by_day WHERE jour = $1 AND dep = $2")
-            .bind(date)
-            .bind(&department)
-            .fetch_one(pool)
-            .await;
-
-        match res {
-            Ok(data) => rates.push(PositivityDayResult::try_from(data)?),
-            Err(err) => {
-                match err {
-                    sqlx::error::Error::RowNotFound => continue,
-                    _ => return Err(err.into())
-                }
-            }
-        }
-    }
-    
+/// * `departments` - Vec<String>
+async fn get_positivity_for_week(pool: &PGPool, dates: Vec<String>, departments: Vec<String>) -> Result<Vec<PositivityDayResult>, MaskErr> {
+    // @Warning
+    // We can't query the date between 2 date as they're string...
+    // It would be nice to convert the date to a datetime on the import.py script.
+    let rows = sqlx::query("SELECT * FROM positivity_rate_per_dep_by_day WHERE jour = ANY($1) AND dep = ANY($2) ORDER BY dep, jour")
+        .bind(dates)
+        .bind(departments)
+        .fetch_all(pool)
+        .await?;
+
+    let rates = rows
+        .into_iter()
+        .map(PositivityDayResult::try_from)
+        .collect::<Result<Vec<_>, _>>()?;
+
     Ok(rates)
 }
 
@@ -153,7 +325,8 @@ fn calculate_positivity_per_week(cases: &[PositivityDayResult]) -> f64 {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::positivity::common::CommonInput;
+    use crate::positivity::common::{CommonInput, RankingOptions, SmoothingOptions};
+    use chrono::NaiveDate;
 
     #[tokio::test]
     async fn expect_grpc_to_return_positivity_per_day_ok() {
@@ -165,9 +338,14 @@ mod tests {
             date: Some(CommonInput {
                 day: Some(10),
                 month: 12,
-                year: 2021
+                year: 2021,
+                latest: false
             }),
-            department: "94".to_owned()
+            department: "94".to_owned(),
+            departments: Vec::new(),
+            all_departments: false,
+            include_trend: false,
+            smoothing: None
         };
 
         let request = Request::new(input);
@@ -186,9 +364,14 @@ mod tests {
             date: Some(CommonInput {
                 day: None,
                 month: 30,
-                year: 2021
+                year: 2021,
+                latest: false
             }),
-            department: "94".to_owned()
+            department: "94".to_owned(),
+            departments: Vec::new(),
+            all_departments: false,
+            include_trend: false,
+            smoothing: None
         };
 
         let request = Request::new(input);
@@ -207,9 +390,14 @@ mod tests {
             date: Some(CommonInput {
                 day: Some(10),
                 month: 12,
-                year: 2021
+                year: 2021,
+                latest: false
             }),
-            department: "94".to_owned()
+            department: "94".to_owned(),
+            departments: Vec::new(),
+            all_departments: false,
+            include_trend: false,
+            smoothing: None
         };
 
         let request = Request::new(input);
@@ -228,9 +416,14 @@ mod tests {
             date: Some(CommonInput {
                 day: None,
                 month: 12,
-                year: 2021
+                year: 2021,
+                latest: false
             }),
-            department: "80".to_owned()
+            department: "80".to_owned(),
+            departments: Vec::new(),
+            all_departments: false,
+            include_trend: false,
+            smoothing: None
         };
 
         let request = Request::new(input);
@@ -238,4 +431,168 @@ mod tests {
 
         assert!(res.is_err());
     }
+
+    #[tokio::test]
+    async fn expect_grpc_to_return_positivity_by_region() {
+        let pool = db::connect("../config.toml").await.unwrap();
+        let pool_arc = Arc::new(pool);
+        let service = PosServiceHandle { pool: Arc::clone(&pool_arc) };
+        
+        let input = AreaInput {
+            date: Some(CommonInput {
+                day: Some(10),
+                month: 12,
+                year: 2021,
+                latest: false
+            }),
+            region: Some(11)
+        };
+
+        let request = Request::new(input);
+        let res = service.get_positivity_by_area_per_day(request).await;
+
+        assert!(res.is_ok());
+    }
+
+    #[tokio::test]
+    async fn expect_grpc_to_return_positivity_per_week_by_department() {
+        let pool = db::connect("../config.toml").await.unwrap();
+        let pool_arc = Arc::new(pool);
+        let service = PosServiceHandle { pool: Arc::clone(&pool_arc) };
+        
+        let input = PositivityInput {
+            date: Some(CommonInput {
+                day: Some(20),
+                month: 12,
+                year: 2021,
+                latest: false
+            }),
+            department: String::new(),
+            departments: vec!["94".to_owned(), "13".to_owned()],
+            all_departments: false,
+            include_trend: false,
+            smoothing: None
+        };
+
+        let request = Request::new(input);
+        let res = service.get_positivity_by_department_per_week(request).await.unwrap();
+
+        assert_eq!(res.get_ref().departments.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn expect_grpc_to_return_department_ranking() {
+        let pool = db::connect("../config.toml").await.unwrap();
+        let pool_arc = Arc::new(pool);
+        let service = PosServiceHandle { pool: Arc::clone(&pool_arc) };
+        
+        let input = PositivityRankingInput {
+            date: Some(CommonInput {
+                day: Some(10),
+                month: 12,
+                year: 2021,
+                latest: false
+            }),
+            metric: Metric::InfectionRate.into(),
+            options: Some(RankingOptions {
+                period: Period::Week.into(),
+                ascending: false,
+                limit: 10
+            })
+        };
+
+        let request = Request::new(input);
+        let res = service.get_department_ranking(request).await.unwrap();
+        let ranks = &res.get_ref().ranks;
+
+        assert!(ranks.len() <= 10);
+        assert!(ranks.windows(2).all(|w| w[0].value >= w[1].value));
+
+        // the rate of the week is the number of positive tests of the week per 100k inhabitants
+        let (days, _) = utils::get_ranking_days(NaiveDate::from_ymd_opt(2021, 12, 10).unwrap(), true);
+        let rate: f64 = sqlx::query("SELECT (SUM(p) * 100000.0 / MAX(pop))::float8 AS rate FROM positivity_rate_per_dep_by_day WHERE dep = $1 AND jour = ANY($2)")
+            .bind(&ranks[0].department)
+            .bind(days)
+            .fetch_one(pool_arc.as_ref())
+            .await
+            .unwrap()
+            .get("rate");
+
+        assert!((ranks[0].value - rate).abs() < 1e-6);
+    }
+
+    #[tokio::test]
+    async fn expect_department_ranking_to_return_error_without_day() {
+        let pool = db::connect("../config.toml").await.unwrap();
+        let pool_arc = Arc::new(pool);
+        let service = PosServiceHandle { pool: Arc::clone(&pool_arc) };
+        
+        let input = PositivityRankingInput {
+            date: Some(CommonInput {
+                day: None,
+                month: 12,
+                year: 2021,
+                latest: false
+            }),
+            metric: Metric::PcrPositive.into(),
+            options: None
+        };
+
+        let request = Request::new(input);
+        let res = service.get_department_ranking(request).await;
+
+        assert!(res.is_err());
+    }
+
+    #[tokio::test]
+    async fn expect_grpc_to_return_positivity_per_day_with_trend() {
+        let pool = db::connect("../config.toml").await.unwrap();
+        let pool_arc = Arc::new(pool);
+        let service = PosServiceHandle { pool: Arc::clone(&pool_arc) };
+        
+        let input = PositivityInput {
+            date: Some(CommonInput {
+                day: None,
+                month: 12,
+                year: 2021,
+                latest: false
+            }),
+            department: "94".to_owned(),
+            departments: Vec::new(),
+            all_departments: false,
+            include_trend: true,
+            smoothing: None
+        };
+
+        let request = Request::new(input);
+        let res = service.get_positivity_by_department_per_day(request).await.unwrap();
+
+        assert!(res.get_ref().rates.iter().all(|r| r.trend.is_some()));
+    }
+
+    #[tokio::test]
+    async fn expect_grpc_to_return_smoothed_positivity_per_day() {
+        let pool = db::connect("../config.toml").await.unwrap();
+        let pool_arc = Arc::new(pool);
+        let service = PosServiceHandle { pool: Arc::clone(&pool_arc) };
+
+        let input = PositivityInput {
+            date: Some(CommonInput {
+                day: Some(15),
+                month: 12,
+                year: 2021,
+                latest: false
+            }),
+            department: String::new(),
+            departments: vec!["94".to_owned(), "75".to_owned()],
+            all_departments: false,
+            include_trend: true,
+            smoothing: Some(SmoothingOptions::default())
+        };
+
+        let request = Request::new(input);
+        let res = service.get_positivity_by_department_per_day(request).await.unwrap();
+
+        assert!(res.get_ref().departments.iter().flat_map(|d| &d.rates).all(|r| r.smoothed.is_some()));
+    }
 }
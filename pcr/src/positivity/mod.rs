@@ -4,6 +4,4 @@ pub mod proto {
     tonic::include_proto!("pos");
 }
 
-pub mod common {
-    tonic::include_proto!("common");
-}
\ No newline at end of file
+pub use crate::common::proto_common as common;
\ No newline at end of file
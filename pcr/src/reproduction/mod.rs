@@ -0,0 +1,7 @@
+pub mod rt;
+
+pub mod proto {
+    tonic::include_proto!("reproduction");
+}
+
+pub use crate::common::proto_common as common;
@@ -0,0 +1,230 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use analytics::{err::AnalyticsErr, reproduction::{self, Config, SerialInterval}};
+use chrono::Duration;
+use db::{PGPool, query};
+use sqlx::{postgres::PgRow, Row};
+use tonic::{Request, Response, Status};
+use utils::err::MaskErr;
+use super::proto::{
+    reproduction_service_server::ReproductionService,
+    ReproductionInput,
+    ReproductionOutput,
+    ReproductionResult
+};
+
+// Number of days fetched before the start of the range. The estimation of a day
+// needs the cases of the window & of the serial interval preceding this day
+const HISTORY_DAYS: i64 = 42;
+
+pub struct ReproductionHandle {
+    pub pool: Arc<PGPool>
+}
+
+/// Number of positive pcr test of a day for all ages
+struct DailyPositive {
+    day: String,
+    positive: i64
+}
+
+impl TryFrom<PgRow> for DailyPositive {
+    type Error = sqlx::Error;
+
+    fn try_from(value: PgRow) -> Result<Self, Self::Error> {
+        let res = Self {
+            day: value.try_get("jour")?,
+            positive: value.try_get("p")?
+        };
+
+        Ok(res)
+    }
+}
+
+#[tonic::async_trait]
+impl ReproductionService for ReproductionHandle {
+    /// Estimate the effective reproduction number for each day of a date range
+    /// from the daily positive pcr test of a department, a region or the whole country
+    ///
+    /// # Arguments
+    /// * `&self`
+    /// * `request` - Request<ReproductionInput>
+    async fn get_reproduction_number(
+        &self,
+        request: Request<ReproductionInput>
+    ) -> Result<Response<ReproductionOutput>, Status> {
+        let input = request.into_inner();
+        let (start, end) = match input.range.map(|r| (r.start, r.end)) {
+            Some((Some(start), Some(end))) => (start, end),
+            _ => return Err(MaskErr::MissingDate.into())
+        };
+
+        let dataset = match (&input.department, input.region) {
+            (Some(_), _) => "pcr_test_department",
+            (None, Some(_)) => "pcr_test_region",
+            (None, None) => "pcr_country"
+        };
+        let (start, end, resolved) = metadata::resolve_range(&self.pool, dataset, start, end).await?;
+
+        let (from, to) = utils::build_date_range(&start, &end)?;
+        let config = Config {
+            serial_interval: SerialInterval {
+                mean: input.serial_interval_mean.unwrap_or(SerialInterval::default().mean),
+                sd: input.serial_interval_sd.unwrap_or(SerialInterval::default().sd)
+            },
+            window: input.window.map(|w| w as usize).unwrap_or(Config::default().window)
+        };
+
+        let history = from - Duration::days(HISTORY_DAYS);
+        let (history_str, to_str) = (history.format("%Y-%m-%d").to_string(), to.format("%Y-%m-%d").to_string());
+
+        let res = match (input.department, input.region) {
+            (Some(department), _) => {
+                geography::validate_department(&department)?;
+                query::get_all_by_date_range_and_gen_field::<DailyPositive, String>(
+                    &self.pool,
+                    "SELECT jour, p FROM pcr_test_department WHERE jour >= $1 AND jour <= $2 AND dep = $3 AND cl_age90 = 0",
+                    &history_str,
+                    &to_str,
+                    department
+                ).await
+            },
+            (None, Some(region)) => {
+                geography::validate_region(region)?;
+                query::get_all_by_date_range_and_gen_field::<DailyPositive, i32>(
+                    &self.pool,
+                    "SELECT jour, p FROM pcr_test_region WHERE jour >= $1 AND jour <= $2 AND reg = $3 AND cl_age90 = 0",
+                    &history_str,
+                    &to_str,
+                    region
+                ).await
+            },
+            (None, None) => query::get_all_by_date_range::<DailyPositive>(
+                &self.pool,
+                "SELECT jour, p FROM pcr_country WHERE jour >= $1 AND jour <= $2 AND cl_age90 = 0",
+                &history_str,
+                &to_str
+            ).await
+        };
+
+        let positives: HashMap<String, i64> = match res {
+            Ok(rows) => rows.into_iter().map(|r| (r.day, r.positive)).collect(),
+            Err(err) => return Err(MaskErr::query("daily positive pcr test", err).into())
+        };
+
+        // missing days are considered as days without any positive test
+        let days = utils::get_days_between(history, to);
+        let incidence: Vec<f64> = days
+            .iter()
+            .map(|d| positives.get(d).copied().unwrap_or_default() as f64)
+            .collect();
+
+        let estimates = reproduction::estimate(&incidence, &config)
+            .map_err(|err| match err {
+                AnalyticsErr::NotConverged => MaskErr::QueryError(err.to_string()),
+                err => MaskErr::InvalidParam(err.to_string())
+            })?;
+
+        let from = from.format("%Y-%m-%d").to_string();
+        let data = days
+            .into_iter()
+            .zip(incidence)
+            .zip(estimates)
+            .filter(|((day, _), _)| *day >= from)
+            .filter_map(|((day, positive), estimate)| estimate.map(|e| ReproductionResult {
+                day,
+                pcr_positive: positive as i64,
+                r: e.mean,
+                lower: e.lower,
+                upper: e.upper
+            }))
+            .collect();
+
+        Ok(metadata::respond_with_date(&self.pool, &[dataset], resolved, ReproductionOutput { data }).await)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::common::{CommonInput as RCommonInput, DateRange};
+
+    fn range() -> Option<DateRange> {
+        Some(DateRange {
+            start: Some(RCommonInput {
+                day: Some(1),
+                month: 12,
+                year: 2021,
+                latest: false
+            }),
+            end: Some(RCommonInput {
+                day: Some(31),
+                month: 12,
+                year: 2021,
+                latest: false
+            })
+        })
+    }
+
+    #[tokio::test]
+    async fn expect_grpc_to_return_reproduction_number_for_department() {
+        let pool = db::connect("../config.toml").await.unwrap();
+        let pool_arc = Arc::new(pool);
+        let service = ReproductionHandle { pool: Arc::clone(&pool_arc) };
+
+        let input = ReproductionInput {
+            range: range(),
+            department: Some("75".to_owned()),
+            region: None,
+            serial_interval_mean: None,
+            serial_interval_sd: None,
+            window: None
+        };
+
+        let request = Request::new(input);
+        let res = service.get_reproduction_number(request).await.unwrap();
+
+        assert!(res.get_ref().data.iter().all(|r| r.lower <= r.r && r.r <= r.upper));
+    }
+
+    #[tokio::test]
+    async fn expect_grpc_to_return_reproduction_number_for_country() {
+        let pool = db::connect("../config.toml").await.unwrap();
+        let pool_arc = Arc::new(pool);
+        let service = ReproductionHandle { pool: Arc::clone(&pool_arc) };
+
+        let input = ReproductionInput {
+            range: range(),
+            department: None,
+            region: None,
+            serial_interval_mean: Some(5.2),
+            serial_interval_sd: Some(3.0),
+            window: Some(14)
+        };
+
+        let request = Request::new(input);
+        let res = service.get_reproduction_number(request).await;
+
+        assert!(res.is_ok());
+    }
+
+    #[tokio::test]
+    async fn expect_grpc_to_return_error_for_invalid_serial_interval() {
+        let pool = db::connect("../config.toml").await.unwrap();
+        let pool_arc = Arc::new(pool);
+        let service = ReproductionHandle { pool: Arc::clone(&pool_arc) };
+
+        let input = ReproductionInput {
+            range: range(),
+            department: None,
+            region: Some(11),
+            serial_interval_mean: Some(-1.0),
+            serial_interval_sd: None,
+            window: None
+        };
+
+        let request = Request::new(input);
+        let res = service.get_reproduction_number(request).await;
+
+        assert_eq!(res.unwrap_err().code(), tonic::Code::InvalidArgument);
+    }
+}
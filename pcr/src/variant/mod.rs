@@ -0,0 +1,7 @@
+pub mod share;
+
+pub mod proto {
+    tonic::include_proto!("variant");
+}
+
+pub use crate::common::proto_common as common;
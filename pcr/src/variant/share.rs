@@ -0,0 +1,281 @@
+use std::sync::Arc;
+use db::PGPool;
+use sqlx::{postgres::PgRow, Row};
+use tonic::{Request, Response, Status};
+use utils::err::MaskErr;
+use super::proto::{
+    variant_service_server::VariantService,
+    VariantInput,
+    VariantOutput,
+    VariantResult
+};
+
+const DATASET: &str = "variants_region";
+
+// The weeks overlapping the range [$1, $2] are selected
+const REGION_QUERY: &str = "SELECT reg::integer AS reg, semaine, variant, nb_seq, nb_variant,
+        COALESCE(nb_variant * 100.0 / NULLIF(nb_seq, 0), 0)::float8 AS share
+    FROM variants_region
+    WHERE reg = $3 AND LEFT(semaine, 10) <= $2 AND RIGHT(semaine, 10) >= $1";
+
+/// Build the query of the shares of the country. The share of each region is weighted by
+/// its positive pcr tests of the week, the regions without the positives of the whole week
+/// are left out. The shares of the sequenced tests are used when no region has the positives
+fn country_query() -> String {
+    let positives = weekly_positives("pcr_test_region", "reg = v.reg AND");
+
+    format!(
+        "SELECT semaine, variant, SUM(nb_seq)::bigint AS nb_seq, SUM(nb_variant)::bigint AS nb_variant,
+            COALESCE(
+                SUM(region_positive * nb_variant::float8 / nb_seq) FILTER (WHERE nb_seq > 0)
+                    / NULLIF(SUM(region_positive) FILTER (WHERE nb_seq > 0), 0),
+                SUM(nb_variant)::float8 / NULLIF(SUM(nb_seq), 0),
+                0
+            ) * 100.0 AS share
+        FROM (
+            SELECT v.*, pcr.positive AS region_positive
+            FROM variants_region v
+            {positives}
+            WHERE LEFT(v.semaine, 10) <= $2 AND RIGHT(v.semaine, 10) >= $1
+        ) regions
+        GROUP BY semaine, variant"
+    )
+}
+
+pub struct VariantHandle {
+    pub pool: Arc<PGPool>
+}
+
+impl TryFrom<PgRow> for VariantResult {
+    type Error = sqlx::Error;
+
+    fn try_from(value: PgRow) -> Result<Self, Self::Error> {
+        let week: String = value.try_get("semaine")?;
+        let sequenced: i64 = value.try_get("nb_seq")?;
+        let count: i64 = value.try_get("nb_variant")?;
+        let pcr_positive: Option<i64> = value.try_get("positive")?;
+
+        let share: f64 = value.try_get("share")?;
+        let estimated_positive = pcr_positive.map(|p| p as f64 * share / 100.0);
+
+        let res = Self {
+            week_start: week.get(..10).unwrap_or_default().to_owned(),
+            week_end: week.get(11..).unwrap_or_default().to_owned(),
+            region: value.try_get("reg").ok(),
+            variant: value.try_get("variant")?,
+            sequenced,
+            count,
+            share,
+            pcr_positive,
+            estimated_positive
+        };
+
+        Ok(res)
+    }
+}
+
+/// Join the positive pcr tests of the week of each row. The positives are only summed
+/// when every day of the week is available
+///
+/// # Arguments
+/// * `table` - &str
+/// * `condition` - &str
+fn weekly_positives(table: &str, condition: &str) -> String {
+    format!(
+        "LEFT JOIN LATERAL (
+            SELECT CASE WHEN COUNT(DISTINCT jour) = 7 THEN SUM(p)::bigint END AS positive
+            FROM {table}
+            WHERE {condition} cl_age90 = 0 AND jour >= LEFT(v.semaine, 10) AND jour <= RIGHT(v.semaine, 10)
+        ) pcr ON true"
+    )
+}
+
+/// Build the query of the variants. The positive pcr tests of each week are joined
+/// from the pcr dataset when given
+///
+/// # Arguments
+/// * `query` - &str
+/// * `pcr` - Option<(&str, &str)>
+fn build_query(query: &str, pcr: Option<(&str, &str)>) -> String {
+    match pcr {
+        Some((table, condition)) => format!(
+            "SELECT v.*, pcr.positive FROM ({query}) v
+            {}
+            ORDER BY v.semaine, v.variant",
+            weekly_positives(table, condition)
+        ),
+        None => format!("SELECT v.*, NULL::bigint AS positive FROM ({query}) v ORDER BY v.semaine, v.variant")
+    }
+}
+
+impl VariantHandle {
+    /// Return the variant shares of the weeks overlapping the range of an input
+    ///
+    /// # Arguments
+    /// * `&self` - Self
+    /// * `input` - VariantInput
+    /// * `query` - String
+    /// * `pcr_dataset` - &str
+    async fn get_shares(&self, input: VariantInput, query: String, pcr_dataset: &str) -> Result<Response<VariantOutput>, Status> {
+        let (start, end) = match input.range.map(|r| (r.start, r.end)) {
+            Some((Some(start), Some(end))) => (start, end),
+            _ => return Err(MaskErr::MissingDate.into())
+        };
+
+        let (start, end, resolved) = metadata::resolve_range(&self.pool, DATASET, start, end).await?;
+        let (from, to) = utils::build_date_range(&start, &end)?;
+
+        let rows = sqlx::query(&query)
+            .bind(from.format("%Y-%m-%d").to_string())
+            .bind(to.format("%Y-%m-%d").to_string())
+            .bind(input.region)
+            .fetch_all(self.pool.as_ref())
+            .await;
+
+        let data = match rows {
+            Ok(rows) => rows
+                .into_iter()
+                .map(VariantResult::try_from)
+                .collect::<Result<Vec<_>, _>>(),
+            Err(err) => Err(err)
+        };
+
+        let datasets = if input.include_positives { vec![DATASET, pcr_dataset] } else { vec![DATASET] };
+        match data {
+            Ok(data) => Ok(metadata::respond_with_date(&self.pool, &datasets, resolved, VariantOutput { data }).await),
+            Err(err) => Err(MaskErr::query("variant shares", err).into())
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl VariantService for VariantHandle {
+    /// Get the weekly share of the variants among the sequenced positive tests of a region
+    ///
+    /// # Arguments
+    /// * `&self`
+    /// * `request` - Request<VariantInput>
+    async fn get_variant_share_by_region(
+        &self,
+        request: Request<VariantInput>
+    ) -> Result<Response<VariantOutput>, Status> {
+        let input = request.into_inner();
+        let region = input.region.ok_or_else(|| MaskErr::MissingParam("region".to_owned()))?;
+        geography::validate_region(region)?;
+
+        let pcr = input.include_positives.then_some(("pcr_test_region", "reg = v.reg AND"));
+        let query = build_query(REGION_QUERY, pcr);
+
+        self.get_shares(input, query, "pcr_test_region").await
+    }
+
+    /// Get the weekly share of the variants among the sequenced positive tests of the whole country
+    ///
+    /// # Arguments
+    /// * `&self`
+    /// * `request` - Request<VariantInput>
+    async fn get_variant_share_country(
+        &self,
+        request: Request<VariantInput>
+    ) -> Result<Response<VariantOutput>, Status> {
+        // the region is ignored
+        let input = request.into_inner();
+
+        let pcr = input.include_positives.then_some(("pcr_country", ""));
+        let query = build_query(&country_query(), pcr);
+
+        self.get_shares(input, query, "pcr_country").await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::common::{CommonInput as VCommonInput, DateRange};
+
+    fn input(region: Option<i32>, include_positives: bool) -> VariantInput {
+        VariantInput {
+            range: Some(DateRange {
+                start: Some(VCommonInput { day: Some(1), month: 12, year: 2021, latest: false }),
+                end: Some(VCommonInput { day: Some(31), month: 12, year: 2021, latest: false })
+            }),
+            region,
+            include_positives
+        }
+    }
+
+    async fn get_handle() -> VariantHandle {
+        let pool = db::connect("../config.toml").await.unwrap();
+
+        VariantHandle { pool: Arc::new(pool) }
+    }
+
+    #[test]
+    fn expect_to_build_query_with_positives() {
+        let query = build_query(REGION_QUERY, Some(("pcr_test_region", "reg = v.reg AND")));
+        assert!(query.contains("FROM pcr_test_region"));
+        assert!(build_query(REGION_QUERY, None).contains("NULL::bigint AS positive"));
+    }
+
+    #[tokio::test]
+    async fn expect_grpc_to_return_shares_of_region() {
+        let handle = get_handle().await;
+        let data = handle.get_variant_share_by_region(Request::new(input(Some(11), true))).await.unwrap().into_inner().data;
+
+        // the weeks overlapping december
+        assert_eq!(data.first().unwrap().week_start, "2021-11-29");
+        assert_eq!(data.last().unwrap().week_end, "2022-01-02");
+        assert!(data.iter().all(|r| r.region == Some(11)));
+
+        // the shares of a week sum to 100%
+        let first_week: f64 = data.iter().filter(|r| r.week_start == "2021-11-29").map(|r| r.share).sum();
+        assert!((first_week - 100.0).abs() < 1e-6);
+
+        let estimated = data.iter().find(|r| r.pcr_positive.is_some()).unwrap();
+        let expected = estimated.pcr_positive.unwrap() as f64 * estimated.count as f64 / estimated.sequenced as f64;
+        assert!((estimated.estimated_positive.unwrap() - expected).abs() < 1e-6);
+    }
+
+    #[tokio::test]
+    async fn expect_grpc_to_return_shares_of_country() {
+        let handle = get_handle().await;
+        let data = handle.get_variant_share_country(Request::new(input(Some(11), false))).await.unwrap().into_inner().data;
+
+        assert!(!data.is_empty());
+        assert!(data.iter().all(|r| r.region.is_none() && r.pcr_positive.is_none()));
+        assert!(data.iter().any(|r| r.variant == "Omicron BA.1" && r.share > 50.0));
+    }
+
+    #[tokio::test]
+    async fn expect_country_shares_to_be_weighted_by_regional_positives() {
+        let handle = get_handle().await;
+        let country = handle.get_variant_share_country(Request::new(input(None, false))).await.unwrap().into_inner().data;
+
+        // the regions with the positive pcr tests of the week
+        let mut regions = Vec::new();
+        for reg in [11, 93, 94] {
+            let data = handle.get_variant_share_by_region(Request::new(input(Some(reg), true))).await.unwrap().into_inner().data;
+            regions.extend(data);
+        }
+
+        let week = |r: &&VariantResult| r.week_start == "2021-12-06" && r.variant == "Omicron BA.1";
+        let (weighted, positives) = regions
+            .iter()
+            .filter(week)
+            .fold((0.0, 0.0), |(w, p), r| (w + r.share * r.pcr_positive.unwrap() as f64, p + r.pcr_positive.unwrap() as f64));
+
+        let share = country.iter().find(week).unwrap().share;
+        assert!((share - weighted / positives).abs() < 1e-6);
+    }
+
+    #[tokio::test]
+    async fn expect_grpc_to_return_error() {
+        let handle = get_handle().await;
+
+        let res = handle.get_variant_share_by_region(Request::new(input(None, false))).await;
+        assert_eq!(res.unwrap_err().code(), tonic::Code::FailedPrecondition);
+
+        let res = handle.get_variant_share_by_region(Request::new(input(Some(99), false))).await;
+        assert_eq!(res.unwrap_err().code(), tonic::Code::InvalidArgument);
+    }
+}
@@ -0,0 +1,121 @@
+import pandas as pd
+from sqlalchemy import text
+import util
+
+# Daily deltas with a z-score above this threshold are reported as outliers
+OUTLIER_ZSCORE = 4
+
+# Columns used to scan each dataset with days, taken from datasets.toml
+#   - day: column of the day
+#   - keys: columns identifying a series (e.g. a department & an age class)
+#   - metrics: columns checked for negative values & outliers
+DATASETS = {
+  name: {'day': dataset['day'], 'keys': [k for k in dataset['key'] if k != dataset['day']], 'metrics': dataset.get('metrics', [])}
+  for name, dataset in util.load_registry().items()
+  if 'day' in dataset
+}
+
+CREATE_TABLE = """
+CREATE TABLE IF NOT EXISTS data_quality (
+  dataset TEXT NOT NULL,
+  series TEXT NOT NULL,
+  jour TEXT NOT NULL,
+  kind TEXT NOT NULL,
+  metric TEXT,
+  value DOUBLE PRECISION,
+  detail TEXT NOT NULL,
+  detected_at TIMESTAMP NOT NULL DEFAULT now()
+)
+"""
+
+def series_name(keys, values) -> str:
+  if not isinstance(values, tuple):
+    values = (values,)
+
+  return ','.join(k + '=' + str(v) for k, v in zip(keys, values))
+
+def group_series(df, keys):
+  if not keys:
+    return [('', df)]
+
+  return [(series_name(keys, values), group) for values, group in df.groupby(keys)]
+
+def finding(series: str, day: str, kind: str, detail: str, metric=None, value=None):
+  return {'series': series, 'jour': day, 'kind': kind, 'metric': metric, 'value': value, 'detail': detail}
+
+def find_duplicates(df, day, keys):
+  duplicated = df[df.duplicated(subset=keys + [day], keep='first')]
+
+  return [
+    finding(series_name(keys, tuple(row[k] for k in keys)), row[day], 'duplicate', 'the day is present more than once')
+    for _, row in duplicated.iterrows()
+  ]
+
+def find_gaps(df, day, keys):
+  findings = []
+  for series, group in group_series(df, keys):
+    days = pd.to_datetime(group[day], errors='coerce').dropna()
+    if days.empty:
+      continue
+
+    expected = pd.date_range(days.min(), days.max(), freq='D')
+    for missing in expected.difference(days):
+      findings.append(finding(series, missing.strftime('%Y-%m-%d'), 'gap', 'the day is missing'))
+
+  return findings
+
+def find_negatives(df, day, keys, metrics):
+  findings = []
+  for series, group in group_series(df, keys):
+    for metric in metrics:
+      for _, row in group[group[metric] < 0].iterrows():
+        findings.append(finding(series, row[day], 'negative', 'the value is negative', metric, row[metric]))
+
+  return findings
+
+def find_outliers(df, day, keys, metrics):
+  findings = []
+  for series, group in group_series(df, keys):
+    group = group.sort_values(day)
+    for metric in metrics:
+      deltas = group[metric].diff()
+      std = deltas.std()
+      if not std or pd.isna(std):
+        continue
+
+      zscores = (deltas - deltas.mean()) / std
+      for idx in zscores[zscores.abs() > OUTLIER_ZSCORE].index:
+        detail = 'the daily change has a z-score of {:.2f}'.format(zscores[idx])
+        findings.append(finding(series, group.at[idx, day], 'outlier', detail, metric, group.at[idx, metric]))
+
+  return findings
+
+def scan(dataset: str):
+  print("\nscan "+dataset)
+  config = DATASETS[dataset]
+  day, keys, metrics = config['day'], config['keys'], config['metrics']
+
+  df = pd.read_sql_table(dataset, util.engine)
+  for metric in metrics:
+    df[metric] = pd.to_numeric(df[metric], errors='coerce')
+
+  findings = find_duplicates(df, day, keys)
+  # the other checks expect a single row per day
+  df = df.drop_duplicates(subset=keys + [day], keep='first')
+  findings += find_gaps(df, day, keys)
+  findings += find_negatives(df, day, keys, metrics)
+  findings += find_outliers(df, day, keys, metrics)
+
+  # the previous findings are replaced in a single transaction
+  with util.engine.begin() as conn:
+    conn.execute(text(CREATE_TABLE))
+    conn.execute(text("DELETE FROM data_quality WHERE dataset = :dataset"), {'dataset': dataset})
+
+    if findings:
+      report = pd.DataFrame(findings)
+      report.insert(0, 'dataset', dataset)
+      report.to_sql('data_quality', conn, if_exists="append", index=False)
+
+def scan_all():
+  for dataset in DATASETS:
+    scan(dataset)
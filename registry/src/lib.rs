@@ -0,0 +1,282 @@
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Deserializer};
+use utils::err::MaskErr;
+
+pub mod row;
+
+// The registry is shared with the import.py & quality.py scripts
+const REGISTRY: &str = include_str!("../../datasets.toml");
+
+static DATASETS: Lazy<Vec<Dataset>> = Lazy::new(|| {
+    load(REGISTRY).unwrap_or_else(|err| panic!("the datasets.toml registry is invalid: {err}"))
+});
+
+/// Type of a column. The columns are cast to this type when queried
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColumnType {
+    Int,
+    Float,
+    String
+}
+
+impl ColumnType {
+    /// Return the postgres type of the column
+    ///
+    /// # Arguments
+    /// * `&self` - Self
+    pub fn as_sql(&self) -> &'static str {
+        match self {
+            ColumnType::Int => "bigint",
+            ColumnType::Float => "double precision",
+            ColumnType::String => "text"
+        }
+    }
+
+    /// Return the name of the type used in the registry
+    ///
+    /// # Arguments
+    /// * `&self` - Self
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ColumnType::Int => "int",
+            ColumnType::Float => "float",
+            ColumnType::String => "string"
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    Csv,
+    Json
+}
+
+/// Geographic level of the rows of a dataset
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    Department,
+    Region,
+    Country
+}
+
+impl Level {
+    /// Return the name of the level used in the registry
+    ///
+    /// # Arguments
+    /// * `&self` - Self
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Level::Department => "department",
+            Level::Region => "region",
+            Level::Country => "country"
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Geography {
+    pub level: Level,
+    // column of the code of the department or the region. Not set for the country
+    pub column: Option<String>
+}
+
+#[derive(Debug, Clone)]
+pub struct Column {
+    pub name: String,
+    pub kind: ColumnType
+}
+
+/// A dataset described in datasets.toml. The name of the dataset is the name of its table
+#[derive(Debug, Clone, Deserialize)]
+pub struct Dataset {
+    pub name: String,
+    pub url: Option<String>,
+    pub file: Option<String>,
+    pub path: Option<String>,
+    pub format: Format,
+    #[serde(default)]
+    pub json_path: Vec<String>,
+    pub key: Vec<String>,
+    pub day: Option<String>,
+    pub geography: Geography,
+    #[serde(default)]
+    pub metrics: Vec<String>,
+    #[serde(deserialize_with = "deserialize_columns")]
+    pub columns: Vec<Column>
+}
+
+#[derive(Deserialize)]
+struct Registry {
+    dataset: Vec<Dataset>
+}
+
+impl Dataset {
+    /// Return a column of the dataset
+    ///
+    /// # Arguments
+    /// * `&self` - Self
+    /// * `name` - &str
+    pub fn get_column(&self, name: &str) -> Option<&Column> {
+        self.columns.iter().find(|c| c.name == name)
+    }
+
+    /// Check that the columns used by the dataset are declared
+    ///
+    /// # Arguments
+    /// * `&self` - Self
+    fn validate(&self) -> Result<(), MaskErr> {
+        let used = self.key
+            .iter()
+            .chain(self.day.iter())
+            .chain(self.geography.column.iter())
+            .chain(self.metrics.iter());
+
+        for column in used {
+            if self.get_column(column).is_none() {
+                return Err(MaskErr::InvalidParam(format!("the column {column} of {} is not declared", self.name)));
+            }
+        }
+
+        // the file of a dataset is either downloaded or read from a local path
+        match (&self.url, &self.file, &self.path) {
+            (Some(_), Some(_), None) | (None, None, Some(_)) => (),
+            _ => return Err(MaskErr::InvalidParam(format!("{} needs either a url & a file or a path", self.name)))
+        };
+
+        match (self.geography.level, &self.geography.column) {
+            (Level::Country, None) | (Level::Department | Level::Region, Some(_)) => Ok(()),
+            _ => Err(MaskErr::InvalidParam(format!("the geography column of {} does not match its level", self.name)))
+        }
+    }
+}
+
+/// Deserialize the table of the columns while keeping the order of the columns
+///
+/// # Arguments
+/// * `deserializer` - D
+fn deserialize_columns<'de, D>(deserializer: D) -> Result<Vec<Column>, D::Error>
+where
+    D: Deserializer<'de>
+{
+    let table = toml::value::Table::deserialize(deserializer)?;
+    table
+        .into_iter()
+        .map(|(name, kind)| {
+            let kind = kind.try_into().map_err(serde::de::Error::custom)?;
+            Ok(Column { name, kind })
+        })
+        .collect()
+}
+
+/// Parse & validate a registry
+///
+/// # Arguments
+/// * `content` - &str
+fn load(content: &str) -> Result<Vec<Dataset>, MaskErr> {
+    let registry: Registry = toml::from_str(content)
+        .map_err(|err| MaskErr::InvalidParam(err.to_string()))?;
+
+    for (idx, dataset) in registry.dataset.iter().enumerate() {
+        dataset.validate()?;
+        if registry.dataset[..idx].iter().any(|d| d.name == dataset.name) {
+            return Err(MaskErr::InvalidParam(format!("the dataset {} is declared twice", dataset.name)));
+        }
+    }
+
+    Ok(registry.dataset)
+}
+
+/// Return the datasets of the registry
+pub fn get_datasets() -> &'static [Dataset] {
+    &DATASETS
+}
+
+/// Return a dataset from its name
+///
+/// # Arguments
+/// * `name` - &str
+pub fn get_dataset(name: &str) -> Option<&'static Dataset> {
+    DATASETS.iter().find(|d| d.name == name)
+}
+
+/// Return a dataset from its name. Return a MaskErr if the dataset is unknown
+///
+/// # Arguments
+/// * `name` - &str
+pub fn validate_dataset(name: &str) -> Result<&'static Dataset, MaskErr> {
+    get_dataset(name).ok_or_else(|| MaskErr::InvalidParam(format!("the dataset {name} is unknown")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expect_registry_to_be_valid() {
+        // datasets queried by the services
+        let used = [
+            "hospitalization", "cases", "pcr_test_region", "pcr_test_department", "positivity_rate_per_dep_by_day",
+            "data_mix", "unvaxx", "vaxx", "hospital_dep", "pcr_country", "icu_capacity", "vaccination_department",
+            "vaccination_region", "variants_region", "deaths_department"
+        ];
+        for name in used {
+            let dataset = get_dataset(name).unwrap_or_else(|| panic!("the dataset {name} is missing"));
+            assert!(dataset.validate().is_ok());
+            assert!(!dataset.key.is_empty() && !dataset.columns.is_empty());
+        }
+
+        let hospitalization = get_dataset("hospitalization").unwrap();
+        assert_eq!(hospitalization.day.as_deref(), Some("jour"));
+        assert_eq!(hospitalization.geography.level, Level::Region);
+        assert_eq!(hospitalization.columns[0].name, "reg");
+        assert_eq!(hospitalization.get_column("hospconv").unwrap().kind, ColumnType::Float);
+
+        let icu = get_dataset("icu_capacity").unwrap();
+        assert!(icu.day.is_none());
+        assert!(icu.url.is_none());
+    }
+
+    #[test]
+    fn expect_invalid_registry_to_return_error() {
+        let undeclared = r#"
+            [[dataset]]
+            name = "cases"
+            format = "csv"
+            key = ["dep", "jour"]
+            geography = { level = "department", column = "dep" }
+
+            [dataset.columns]
+            dep = "string"
+        "#;
+        assert!(load(undeclared).is_err());
+
+        let level = r#"
+            [[dataset]]
+            name = "cases"
+            format = "csv"
+            key = ["dep"]
+            geography = { level = "country", column = "dep" }
+
+            [dataset.columns]
+            dep = "string"
+        "#;
+        assert!(load(level).is_err());
+        assert!(validate_dataset("unknown").is_err());
+
+        let source = r#"
+            [[dataset]]
+            name = "cases"
+            url = "https://www.data.gouv.fr/fr/datasets/r/406c6a23-e283-4300-9484-54e78c8ae675"
+            format = "csv"
+            key = ["dep"]
+            geography = { level = "department", column = "dep" }
+
+            [dataset.columns]
+            dep = "string"
+        "#;
+        assert!(load(source).is_err());
+    }
+}
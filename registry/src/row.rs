@@ -0,0 +1,183 @@
+use db::PGPool;
+use sqlx::{Postgres, Row, postgres::{PgArguments, PgRow}, query::Query};
+use utils::err::MaskErr;
+use super::{ColumnType, Dataset};
+
+/// Value of a column decoded with the type declared in the registry
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Int(i64),
+    Float(f64),
+    Text(String)
+}
+
+/// A row of a dataset. The values are in the order of the columns of the dataset
+#[derive(Debug, Clone, PartialEq)]
+pub struct DatasetRow {
+    pub values: Vec<Option<Value>>
+}
+
+/// Quote the name of a column (e.g. nb_pcr+)
+///
+/// # Arguments
+/// * `column` - &str
+fn quote(column: &str) -> String {
+    format!("\"{}\"", column.replace('"', "\"\""))
+}
+
+impl Dataset {
+    /// Build the query returning the rows of the dataset. The rows are filtered by day
+    /// with a LIKE pattern and by the codes of the geography when asked
+    ///
+    /// # Arguments
+    /// * `&self` - Self
+    /// * `by_day` - bool
+    /// * `by_area` - bool
+    pub fn build_query(&self, by_day: bool, by_area: bool) -> String {
+        let columns = self.columns
+            .iter()
+            .map(|c| format!("{}::{} AS {}", quote(&c.name), c.kind.as_sql(), quote(&c.name)))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let mut conditions = Vec::new();
+        if let (true, Some(day)) = (by_day, &self.day) {
+            conditions.push(format!("{} LIKE ${}", quote(day), conditions.len() + 1));
+        }
+
+        if let (true, Some(column)) = (by_area, &self.geography.column) {
+            conditions.push(format!("{}::text = ANY(${})", quote(column), conditions.len() + 1));
+        }
+
+        let mut query = format!("SELECT {columns} FROM {}", quote(&self.name));
+        if !conditions.is_empty() {
+            query = format!("{query} WHERE {}", conditions.join(" AND "));
+        }
+
+        let order = self.key.iter().map(|k| quote(k)).collect::<Vec<_>>().join(", ");
+        format!("{query} ORDER BY {order}")
+    }
+
+    /// Keep the filters which apply to the dataset. The day is ignored for the datasets
+    /// without days and the areas are ignored for the datasets of the country
+    ///
+    /// # Arguments
+    /// * `&self` - Self
+    /// * `day` - Option<&'a str>
+    /// * `areas` - Option<&'a [String]>
+    pub fn filters<'a>(&self, day: Option<&'a str>, areas: Option<&'a [String]>) -> (Option<&'a str>, Option<&'a [String]>) {
+        (
+            day.filter(|_| self.day.is_some()),
+            areas.filter(|_| self.geography.column.is_some())
+        )
+    }
+
+    /// Decode a row with the types of the columns
+    ///
+    /// # Arguments
+    /// * `&self` - Self
+    /// * `row` - &PgRow
+    pub fn decode(&self, row: &PgRow) -> Result<DatasetRow, sqlx::Error> {
+        let mut values = Vec::with_capacity(self.columns.len());
+        for column in &self.columns {
+            let name = column.name.as_str();
+            let value = match column.kind {
+                ColumnType::Int => row.try_get::<Option<i64>, _>(name)?.map(Value::Int),
+                ColumnType::Float => row.try_get::<Option<f64>, _>(name)?.map(Value::Float),
+                ColumnType::String => row.try_get::<Option<String>, _>(name)?.map(Value::Text)
+            };
+
+            values.push(value);
+        }
+
+        Ok(DatasetRow { values })
+    }
+}
+
+/// Bind the filters to a query built by `build_query`
+///
+/// # Arguments
+/// * `query` - &'q str
+/// * `day` - Option<&'q str>
+/// * `areas` - Option<&'q [String]>
+pub fn bind_filters<'q>(query: &'q str, day: Option<&'q str>, areas: Option<&'q [String]>) -> Query<'q, Postgres, PgArguments> {
+    let mut query = sqlx::query(query);
+    if let Some(day) = day {
+        query = query.bind(day);
+    }
+
+    if let Some(areas) = areas {
+        query = query.bind(areas);
+    }
+
+    query
+}
+
+/// Return the rows of a dataset. The day is ignored for the datasets without days and
+/// the areas are ignored for the datasets of the country
+///
+/// # Arguments
+/// * `pool` - &PGPool
+/// * `dataset` - &Dataset
+/// * `day` - Option<&str>
+/// * `areas` - Option<&[String]>
+pub async fn get_rows(pool: &PGPool, dataset: &Dataset, day: Option<&str>, areas: Option<&[String]>) -> Result<Vec<DatasetRow>, MaskErr> {
+    let (day, areas) = dataset.filters(day, areas);
+    let query = dataset.build_query(day.is_some(), areas.is_some());
+
+    let rows = bind_filters(&query, day, areas).fetch_all(pool).await?;
+    let rows = rows
+        .iter()
+        .map(|r| dataset.decode(r))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::get_dataset;
+
+    #[test]
+    fn expect_to_build_query() {
+        let dataset = get_dataset("cases").unwrap();
+        let query = dataset.build_query(true, true);
+
+        assert!(query.starts_with("SELECT \"dep\"::text AS \"dep\", \"jour\"::text AS \"jour\""));
+        assert!(query.ends_with("FROM \"cases\" WHERE \"jour\" LIKE $1 AND \"dep\"::text = ANY($2) ORDER BY \"dep\", \"jour\""));
+        assert!(!dataset.build_query(false, false).contains("WHERE"));
+    }
+
+    #[tokio::test]
+    async fn expect_to_get_rows_by_day_and_area() {
+        let pool = db::connect("../config.toml").await.unwrap();
+        let dataset = get_dataset("hospitalization").unwrap();
+        let areas = vec!["11".to_owned()];
+
+        let rows = get_rows(&pool, dataset, Some("2021-10-01"), Some(&areas)).await.unwrap();
+        assert!(!rows.is_empty());
+        assert!(rows.iter().all(|r| r.values[0] == Some(Value::Int(11))));
+        assert!(rows.iter().all(|r| r.values.len() == dataset.columns.len()));
+    }
+
+    #[tokio::test]
+    async fn expect_columns_to_match_tables() {
+        let pool = db::connect("../config.toml").await.unwrap();
+        for dataset in crate::get_datasets() {
+            let query = format!("{} LIMIT 1", dataset.build_query(false, false));
+            let row = sqlx::query(&query).fetch_one(&pool).await.unwrap();
+            assert!(dataset.decode(&row).is_ok(), "unable to decode {}", dataset.name);
+        }
+    }
+
+    #[tokio::test]
+    async fn expect_to_get_rows_without_day() {
+        let pool = db::connect("../config.toml").await.unwrap();
+        let dataset = get_dataset("icu_capacity").unwrap();
+
+        let rows = get_rows(&pool, dataset, Some("2021-10-01"), None).await.unwrap();
+        assert!(!rows.is_empty());
+        assert!(matches!(rows[0].values[0], Some(Value::Text(_))));
+    }
+}
@@ -3,7 +3,10 @@ import toml
 import os
 import pandas as pd
 import json
-from sqlalchemy import create_engine
+import hashlib
+import shutil
+import uuid
+from sqlalchemy import create_engine, text
 
 
 def set_engine():
@@ -27,27 +30,194 @@ def set_engine():
 # variable engine
 engine = set_engine()
 
+CREATE_IMPORT_LOG = """
+CREATE TABLE IF NOT EXISTS import_log (
+  id BIGSERIAL PRIMARY KEY,
+  dataset TEXT NOT NULL,
+  source TEXT NOT NULL,
+  file_hash TEXT NOT NULL,
+  row_count BIGINT NOT NULL,
+  max_date TEXT,
+  imported_at TIMESTAMP NOT NULL DEFAULT now()
+)
+"""
+
+# directory of the files to import. The files are downloaded when not set
+source_dir = None
+
+# columns containing the day of a row
+DAY_COLUMNS = ['jour', 'date', 'semaine']
+
+# registry of the datasets shared with the services
+REGISTRY_PATH = os.path.join(os.path.dirname(os.path.abspath(__file__)), 'datasets.toml')
+
+# column of the staging table keeping the position of the rows in the file
+POSITION_COLUMN = '_position'
+
+# pandas type of the column types of the registry
+COLUMN_TYPES = {'int': int, 'float': float, 'string': 'string'}
+# postgres type of the column types of the registry, the same as the registry crate
+SQL_TYPES = {'int': 'bigint', 'float': 'double precision', 'string': 'text'}
+
+def load_registry():
+  return {dataset['name']: dataset for dataset in toml.load(REGISTRY_PATH)['dataset']}
+
+def resolve_source(filename: str) -> str:
+  # the source directory must be within the import root (import_root variable, default to the
+  # directory of the script) as it can be requested by the clients of the admin service
+  root = os.path.realpath(os.environ.get('import_root', os.path.dirname(os.path.abspath(__file__))))
+  path = os.path.realpath(os.path.join(source_dir, filename))
+  if os.path.commonpath([root, path]) != root:
+    raise ValueError('the file {} is outside of the import root {}'.format(path, root))
+
+  return path
+
 def download_file(url: str, filename: str):
+  # the files are copied from the source directory when set (e.g. offline imports & tests)
+  if source_dir:
+    shutil.copyfile(resolve_source(filename), './' + filename)
+    return
+
   wget.download(url, './' + filename)
 
-def import_csv_to_sql(filename: str, table_name: str, dic):
+def hash_file(filename: str) -> str:
+  sha = hashlib.sha256()
+  with open(filename, 'rb') as f:
+    for chunk in iter(lambda: f.read(65536), b''):
+      sha.update(chunk)
+
+  return sha.hexdigest()
+
+def log_import(conn, table_name: str, source: str, filename: str, df):
+  # written in the transaction of the import, the log always matches the rows of the dataset
+  day = next((c for c in DAY_COLUMNS if c in df.columns), None)
+  max_date = str(df[day].max()) if day and not df.empty else None
+
+  conn.execute(text(CREATE_IMPORT_LOG))
+  conn.execute(
+    text("""
+      INSERT INTO import_log (dataset, source, file_hash, row_count, max_date)
+      VALUES (:dataset, :source, :file_hash, :row_count, :max_date)
+    """),
+    {
+      'dataset': table_name,
+      'source': source,
+      'file_hash': hash_file(filename),
+      'row_count': len(df.index),
+      'max_date': max_date
+    }
+  )
+
+def quote(column: str) -> str:
+  return '"' + column.replace('"', '""') + '"'
+
+def get_sql_types(conn, table_name: str):
+  rows = conn.execute(text("""
+    SELECT column_name, data_type FROM information_schema.columns
+    WHERE table_schema = current_schema() AND table_name = :table
+  """), {'table': table_name}).fetchall()
+
+  return {column: data_type for column, data_type in rows}
+
+def upsert(df, table_name: str, keys, types, source: str, filename: str):
+  # The rows are loaded in a staging table then merged in the table by their natural key
+  # within a single transaction along with the import log. The services never see a
+  # partially imported dataset.
+  # The staging table has a unique name as several imports may run at the same time
+  staging_name = '{}_staging_{}'.format(table_name, uuid.uuid4().hex[:12])
+  staging = quote(staging_name)
+  table = quote(table_name)
+  index = table_name + '_natural_key'
+  columns = [quote(c) for c in df.columns]
+  key_columns = [quote(k) for k in keys]
+  values = [c for c in columns if c not in key_columns]
+
+  if values:
+    on_conflict = 'DO UPDATE SET {set} WHERE ({current}) IS DISTINCT FROM ({excluded})'.format(
+      set=', '.join(c + ' = EXCLUDED.' + c for c in values),
+      current=', '.join('target.' + c for c in values),
+      excluded=', '.join('EXCLUDED.' + c for c in values)
+    )
+  else:
+    on_conflict = 'DO NOTHING'
+
+  staged = df.reset_index(drop=True)
+  staged.insert(0, POSITION_COLUMN, staged.index)
+
+  with engine.begin() as conn:
+    staged.to_sql(staging_name, conn, index=False)
+
+    # the columns declared in the registry have its type, the others keep the type inferred by pandas
+    staging_types = get_sql_types(conn, staging_name)
+    sql_types = {c: SQL_TYPES[types[c]] if c in types else staging_types[c] for c in df.columns}
+    conn.execute(text('CREATE TABLE IF NOT EXISTS {} ({})'.format(
+      table,
+      ', '.join(quote(c) + ' ' + sql_types[c] for c in df.columns)
+    )))
+
+    # the columns added to the file or to the registry since the table has been created
+    current_types = get_sql_types(conn, table_name)
+    for column, sql_type in sql_types.items():
+      if column not in current_types:
+        conn.execute(text('ALTER TABLE {} ADD COLUMN {} {}'.format(table, quote(column), sql_type)))
+      elif column in types and current_types[column] != sql_type:
+        conn.execute(text('ALTER TABLE {t} ALTER COLUMN {c} TYPE {type} USING {c}::{type}'.format(
+          t=table,
+          c=quote(column),
+          type=sql_type
+        )))
+
+    if conn.execute(text("SELECT to_regclass(:index)"), {'index': index}).scalar() is None:
+      # tables created by the former imports have the pandas index and may contain duplicates
+      conn.execute(text('ALTER TABLE {} DROP COLUMN IF EXISTS "index"'.format(table)))
+      conn.execute(text('DELETE FROM {t} a USING {t} b WHERE a.ctid < b.ctid AND {keys}'.format(
+        t=table,
+        keys=' AND '.join('a.' + k + ' = b.' + k for k in key_columns)
+      )))
+      conn.execute(text('CREATE UNIQUE INDEX {} ON {} ({})'.format(quote(index), table, ', '.join(key_columns))))
+
+    # the last row is kept when a key is present more than once in the file
+    result = conn.execute(text("""
+      INSERT INTO {t} AS target ({columns})
+      SELECT DISTINCT ON ({keys}) {casts} FROM {s}
+      ORDER BY {keys}, {position} DESC
+      ON CONFLICT ({keys}) {on_conflict}
+    """.format(
+      t=table,
+      s=staging,
+      columns=', '.join(columns),
+      casts=', '.join(quote(c) + '::' + sql_types[c] for c in df.columns),
+      keys=', '.join(key_columns),
+      position=quote(POSITION_COLUMN),
+      on_conflict=on_conflict
+    )))
+    conn.execute(text('DROP TABLE {}'.format(staging)))
+    log_import(conn, table_name, source, filename, df)
+
+  print("\n{} rows inserted or updated in {}".format(result.rowcount, table_name))
+
+def get_dtype(names, columns):
+  # the columns of the registry are lowercase while the files may not be
+  return {name: COLUMN_TYPES[columns[name.lower()]] for name in names if name.lower() in columns}
+
+def import_csv_to_sql(filename: str, table_name: str, columns, keys, source=None):
   print("\nprocess "+filename)
-  df = pd.read_csv(filename, ';', dtype=dic)
+  header = pd.read_csv(filename, ';', nrows=0).columns
+  df = pd.read_csv(filename, ';', dtype=get_dtype(header, columns))
   df.columns = [c.lower() for c in df.columns] 
-  df.to_sql(
-    table_name,
-    engine,
-    if_exists="replace"
-  )
+  upsert(df, table_name, keys, columns, source or filename, filename)
 
-def import_json_to_db(filename: str, path, table_name: str):
+def import_json_to_db(filename: str, path, table_name: str, columns, keys, source=None):
   print("\nprocessing "+filename)
   with open(filename) as f:
     data = json.loads(f.read())
 
   df = pd.json_normalize(data, path)
-  df.to_sql(
-    table_name,
-    engine,
-    if_exists="replace"
-  )
+  df = df.astype(get_dtype(df.columns, columns))
+  df.columns = [c.lower() for c in df.columns]
+  upsert(df, table_name, keys, columns, source or filename, filename)
+
+def notify_import():
+  # the hospital service evaluates the alert rules when receiving this notification
+  with engine.begin() as conn:
+    conn.execute(text("SELECT pg_notify('dataset_imported', '')"))
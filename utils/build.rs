@@ -0,0 +1,15 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    tonic_build::configure()
+        .build_server(false)
+        .build_client(false)
+        .compile(
+            &[
+                "../proto/common.proto",
+                "../proto/google/rpc/status.proto",
+                "../proto/google/rpc/error_details.proto",
+            ], 
+            &["../proto"]
+        )?;
+
+    Ok(())
+}
@@ -0,0 +1,65 @@
+use chrono::{Datelike, NaiveDate};
+use sqlx::{postgres::PgRow, Row};
+use super::Date;
+use proto_common::{CommonInput, RankingResult, Trend};
+
+/// Messages of the common.proto shared by the services
+pub mod proto_common {
+    tonic::include_proto!("common");
+}
+
+impl Date for CommonInput {
+    fn get_year(&self) -> i32 {
+        self.year
+    }
+
+    fn get_month(&self) -> i32 {
+        self.month
+    }
+
+    fn get_day(&self) -> Option<i32> {
+        self.day
+    }
+
+    fn is_latest(&self) -> bool {
+        self.latest
+    }
+}
+
+impl From<NaiveDate> for CommonInput {
+    fn from(day: NaiveDate) -> Self {
+        Self {
+            day: Some(day.day() as i32),
+            month: day.month() as i32,
+            year: day.year(),
+            latest: false
+        }
+    }
+}
+
+impl TryFrom<PgRow> for RankingResult {
+    type Error = sqlx::Error;
+
+    fn try_from(value: PgRow) -> Result<Self, Self::Error> {
+        let rank: i64 = value.try_get("rank")?;
+        let res = Self {
+            rank: rank as u32,
+            department: value.try_get("dep")?,
+            value: value.try_get("value")?,
+            week_change: value.try_get("week_change")?
+        };
+
+        Ok(res)
+    }
+}
+
+impl From<db::query::Trend> for Trend {
+    fn from(t: db::query::Trend) -> Self {
+        Trend {
+            day_change: t.day_change,
+            day_change_rate: t.day_change_rate,
+            week_change: t.week_change,
+            week_change_rate: t.week_change_rate
+        }
+    }
+}
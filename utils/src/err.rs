@@ -1,4 +1,17 @@
-use tonic::Status;
+use std::collections::HashMap;
+use prost::Message;
+use prost_types::{Any, Duration};
+use tonic::{Code, Status};
+use rpc::{BadRequest, ErrorInfo, RetryInfo, bad_request::FieldViolation};
+
+pub mod rpc {
+    tonic::include_proto!("google.rpc");
+}
+
+// Domain of the reasons of the ErrorInfo details
+const ERROR_DOMAIN: &str = "mask";
+// Delay advised to the clients before retrying a request failing on an unavailable database
+const RETRY_DELAY_SECONDS: i64 = 5;
 
 #[derive(Debug)]
 pub enum MaskErr {
@@ -6,6 +19,12 @@ pub enum MaskErr {
     InvalidDate,
     MissingDate,
     MissingParam(String),
+    InvalidParam(String),
+    // field of the input & code of the department or region
+    UnknownGeography { field: String, code: String },
+    // the date is outside of the days available in the dataset
+    DateOutOfRange { dataset: String, first_day: String, last_day: String },
+    DbUnavailable(String),
     IO(String),
 }
 
@@ -16,6 +35,10 @@ impl std::fmt::Display for MaskErr {
             MaskErr::InvalidDate => write!(f, "The date is invalid"),
             MaskErr::MissingDate => write!(f, "The date is missing"),
             MaskErr::MissingParam(key) => write!(f, "A param of name {key} is missing"),
+            MaskErr::InvalidParam(msg) => write!(f, "A param is invalid: {msg}"),
+            MaskErr::UnknownGeography { field, code } => write!(f, "The {field} {code} is unknown"),
+            MaskErr::DateOutOfRange { dataset, first_day, last_day } => write!(f, "The date is outside of the days available in {dataset} ({first_day} to {last_day})"),
+            MaskErr::DbUnavailable(msg) => write!(f, "The database is unavailable: {msg}"),
             MaskErr::IO(msg) => write!(f, "Unable to open file for reasons: {msg}",),
         }
     }
@@ -23,9 +46,133 @@ impl std::fmt::Display for MaskErr {
 
 impl std::error::Error for MaskErr {}
 
+impl MaskErr {
+    /// Build the error of a query which failed in a handler. The cause of the error is only
+    /// logged, the client receives the context (e.g. pcr by department) & the reason
+    ///
+    /// # Arguments
+    /// * `context` - &str
+    /// * `err` - E
+    pub fn query<E: Into<MaskErr>>(context: &str, err: E) -> MaskErr {
+        match err.into() {
+            MaskErr::QueryError(cause) => {
+                error!("query of {context} failed: {cause}");
+                MaskErr::QueryError(context.to_owned())
+            },
+            MaskErr::DbUnavailable(cause) => {
+                error!("query of {context} failed on an unavailable database: {cause}");
+                MaskErr::DbUnavailable(context.to_owned())
+            },
+            MaskErr::IO(cause) => {
+                error!("query of {context} failed on an IO error: {cause}");
+                MaskErr::IO(context.to_owned())
+            },
+            err => err
+        }
+    }
+
+    /// Return the stable reason of the error returned in the ErrorInfo details
+    ///
+    /// # Arguments
+    /// * `&self` - Self
+    pub fn reason(&self) -> &'static str {
+        match self {
+            MaskErr::QueryError(_) => "QUERY_FAILED",
+            MaskErr::InvalidDate => "INVALID_DATE",
+            MaskErr::MissingDate => "MISSING_DATE",
+            MaskErr::MissingParam(_) => "MISSING_PARAM",
+            MaskErr::InvalidParam(_) => "INVALID_PARAM",
+            MaskErr::UnknownGeography { .. } => "UNKNOWN_GEOGRAPHY",
+            MaskErr::DateOutOfRange { .. } => "DATE_OUT_OF_RANGE",
+            MaskErr::DbUnavailable(_) => "DB_UNAVAILABLE",
+            MaskErr::IO(_) => "IO_ERROR",
+        }
+    }
+
+    /// Return the gRPC code of the error
+    ///
+    /// # Arguments
+    /// * `&self` - Self
+    pub fn code(&self) -> Code {
+        match self {
+            MaskErr::QueryError(_) | MaskErr::IO(_) => Code::Internal,
+            MaskErr::MissingParam(_) => Code::FailedPrecondition,
+            MaskErr::InvalidDate
+            | MaskErr::MissingDate
+            | MaskErr::InvalidParam(_)
+            | MaskErr::UnknownGeography { .. } => Code::InvalidArgument,
+            MaskErr::DateOutOfRange { .. } => Code::OutOfRange,
+            MaskErr::DbUnavailable(_) => Code::Unavailable,
+        }
+    }
+
+    /// Build the google.rpc details of the error. Every error has an ErrorInfo. The errors
+    /// caused by a field of the input have a BadRequest and the transient errors have a RetryInfo
+    ///
+    /// # Arguments
+    /// * `&self` - Self
+    pub fn details(&self) -> Vec<Any> {
+        let metadata: HashMap<String, String> = match self {
+            MaskErr::MissingParam(key) => HashMap::from([("param".to_owned(), key.to_owned())]),
+            MaskErr::UnknownGeography { field, code } => HashMap::from([
+                ("field".to_owned(), field.to_owned()),
+                ("code".to_owned(), code.to_owned())
+            ]),
+            MaskErr::DateOutOfRange { dataset, first_day, last_day } => HashMap::from([
+                ("dataset".to_owned(), dataset.to_owned()),
+                ("first_available_date".to_owned(), first_day.to_owned()),
+                ("last_available_date".to_owned(), last_day.to_owned())
+            ]),
+            _ => HashMap::new()
+        };
+
+        let mut details = vec![pack("ErrorInfo", &ErrorInfo {
+            reason: self.reason().to_owned(),
+            domain: ERROR_DOMAIN.to_owned(),
+            metadata
+        })];
+
+        let violation = match self {
+            MaskErr::InvalidDate | MaskErr::MissingDate => Some("date"),
+            MaskErr::MissingParam(key) => Some(key.as_str()),
+            MaskErr::UnknownGeography { field, .. } => Some(field.as_str()),
+            _ => None
+        };
+
+        if let Some(field) = violation {
+            details.push(pack("BadRequest", &BadRequest {
+                field_violations: vec![FieldViolation { field: field.to_owned(), description: self.to_string() }]
+            }));
+        }
+
+        if let MaskErr::DbUnavailable(_) = self {
+            details.push(pack("RetryInfo", &RetryInfo {
+                retry_delay: Some(Duration { seconds: RETRY_DELAY_SECONDS, nanos: 0 })
+            }));
+        }
+
+        details
+    }
+}
+
+/// Wrap a message of the google.rpc package in an Any
+///
+/// # Arguments
+/// * `name` - &str
+/// * `message` - &M
+fn pack<M: Message>(name: &str, message: &M) -> Any {
+    Any {
+        type_url: format!("type.googleapis.com/google.rpc.{name}"),
+        value: message.encode_to_vec()
+    }
+}
+
 impl From<sqlx::Error> for MaskErr {
     fn from(err: sqlx::Error) -> Self {
-        MaskErr::QueryError(err.to_string())
+        match db::err::is_unavailable(&err) {
+            true => MaskErr::DbUnavailable(err.to_string()),
+            false => MaskErr::QueryError(err.to_string())
+        }
     }
 }
 
@@ -43,17 +190,74 @@ impl From<std::num::ParseFloatError> for MaskErr {
 
 impl From<db::err::DBError> for MaskErr {
     fn from(err: db::err::DBError) -> Self {
-        MaskErr::QueryError(err.to_string())
+        match err {
+            db::err::DBError::Unavailable(msg) => MaskErr::DbUnavailable(msg),
+            err => MaskErr::QueryError(err.to_string())
+        }
     }
 }
 
 impl From<MaskErr> for Status {
     fn from(err: MaskErr) -> Self {
-        match err {
-            MaskErr::QueryError(msg) | MaskErr::IO(msg) => Status::internal(msg),
-            MaskErr::MissingDate => Status::invalid_argument("The date is missing"),
-            MaskErr::MissingParam(msg) => Status::failed_precondition(msg),
-            MaskErr::InvalidDate => Status::invalid_argument("The date is invalid"),
-        }
+        let status = rpc::Status {
+            code: err.code() as i32,
+            message: err.to_string(),
+            details: err.details()
+        };
+
+        // the details are sent in the grpc-status-details-bin metadata
+        Status::with_details(err.code(), err.to_string(), status.encode_to_vec().into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode_details(status: &Status) -> rpc::Status {
+        rpc::Status::decode(status.details()).unwrap()
+    }
+
+    #[test]
+    fn expect_invalid_date_to_have_field_violation() {
+        let status = Status::from(MaskErr::InvalidDate);
+        assert_eq!(status.code(), Code::InvalidArgument);
+
+        let details = decode_details(&status);
+        assert_eq!(details.code, Code::InvalidArgument as i32);
+
+        let info = ErrorInfo::decode(details.details[0].value.as_slice()).unwrap();
+        assert_eq!(info.reason, "INVALID_DATE");
+        assert_eq!(info.domain, ERROR_DOMAIN);
+
+        assert_eq!(details.details[1].type_url, "type.googleapis.com/google.rpc.BadRequest");
+        let bad_request = BadRequest::decode(details.details[1].value.as_slice()).unwrap();
+        assert_eq!(bad_request.field_violations[0].field, "date");
+    }
+
+    #[test]
+    fn expect_unavailable_db_to_be_retried() {
+        let status = Status::from(MaskErr::query("pcr by department", sqlx::Error::PoolTimedOut));
+        assert_eq!(status.code(), Code::Unavailable);
+
+        let details = decode_details(&status);
+        let retry = details.details
+            .iter()
+            .find(|d| d.type_url.ends_with("RetryInfo"))
+            .map(|d| RetryInfo::decode(d.value.as_slice()).unwrap())
+            .unwrap();
+
+        assert_eq!(retry.retry_delay.unwrap().seconds, RETRY_DELAY_SECONDS);
+    }
+
+    #[test]
+    fn expect_query_error_to_not_leak_the_cause() {
+        let err = MaskErr::query("pcr by department", sqlx::Error::RowNotFound);
+        assert!(err.to_string().contains("pcr by department"));
+        assert!(!err.to_string().contains(&sqlx::Error::RowNotFound.to_string()));
+        assert_eq!(err.reason(), "QUERY_FAILED");
+
+        let status = Status::from(MaskErr::query("pcr by department", sqlx::Error::PoolTimedOut));
+        assert!(!status.message().contains(&sqlx::Error::PoolTimedOut.to_string()));
     }
 }
@@ -0,0 +1,157 @@
+use std::collections::HashSet;
+use super::err::MaskErr;
+
+/// Fields of a message requested by the field mask of an input
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct FieldMask {
+    // every field is requested when not set
+    paths: Option<HashSet<String>>
+}
+
+/// Message whose fields can be cleared when they are not requested
+pub trait Masked {
+    /// Reset a field to its default value
+    ///
+    /// # Arguments
+    /// * `&mut self` - Self
+    /// * `field` - &str
+    fn clear(&mut self, field: &str);
+}
+
+impl FieldMask {
+    /// Create a mask from the paths of a google.protobuf.FieldMask. Every field is requested when
+    /// there is no path. The path of a sub field of a message (e.g. rates.death) requests the
+    /// whole message
+    ///
+    /// # Arguments
+    /// * `paths` - Vec<String>
+    /// * `fields` - &[&str]
+    pub fn new(paths: Vec<String>, fields: &[&str]) -> Result<Self, MaskErr> {
+        if paths.is_empty() {
+            return Ok(FieldMask::default());
+        }
+
+        let mut requested = HashSet::new();
+        for path in paths {
+            let field = path.split('.').next().unwrap_or_default();
+            if !fields.contains(&field) {
+                return Err(MaskErr::InvalidParam(format!("the field {path} of the field mask is unknown")));
+            }
+
+            requested.insert(field.to_owned());
+        }
+
+        Ok(FieldMask { paths: Some(requested) })
+    }
+
+    /// Check whether a field is requested
+    ///
+    /// # Arguments
+    /// * `&self` - Self
+    /// * `field` - &str
+    pub fn contains(&self, field: &str) -> bool {
+        self.paths
+            .as_ref()
+            .map(|p| p.contains(field))
+            .unwrap_or(true)
+    }
+
+    /// Build the list of the columns to select. The columns of the requested fields are selected
+    /// along with the required columns (e.g. the keys of the rows)
+    ///
+    /// # Arguments
+    /// * `&self` - Self
+    /// * `columns` - &[(&str, &[&str])]
+    /// * `required` - &[&str]
+    pub fn select(&self, columns: &[(&str, &[&str])], required: &[&str]) -> String {
+        let mut selected: Vec<&str> = required.to_vec();
+        let requested = columns
+            .iter()
+            .filter(|(field, _)| self.contains(field))
+            .flat_map(|(_, columns)| columns.iter().copied());
+
+        for column in requested {
+            if !selected.contains(&column) {
+                selected.push(column);
+            }
+        }
+
+        selected
+            .iter()
+            .map(|c| format!("\"{c}\""))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Clear the fields of the rows which are not requested
+    ///
+    /// # Arguments
+    /// * `&self` - Self
+    /// * `rows` - &mut [T]
+    /// * `fields` - &[&str]
+    pub fn apply<T: Masked>(&self, rows: &mut [T], fields: &[&str]) {
+        let cleared: Vec<&str> = fields
+            .iter()
+            .copied()
+            .filter(|f| !self.contains(f))
+            .collect();
+
+        if cleared.is_empty() {
+            return;
+        }
+
+        for row in rows.iter_mut() {
+            cleared.iter().for_each(|f| row.clear(f));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIELDS: [&str; 3] = ["day", "positive", "rates"];
+
+    #[derive(Debug, PartialEq)]
+    struct Row {
+        day: String,
+        positive: i64
+    }
+
+    impl Masked for Row {
+        fn clear(&mut self, field: &str) {
+            match field {
+                "day" => self.day = String::new(),
+                "positive" => self.positive = 0,
+                _ => {}
+            }
+        }
+    }
+
+    #[test]
+    fn expect_mask_to_be_validated() {
+        let mask = FieldMask::new(vec!["positive".to_owned(), "rates.death".to_owned()], &FIELDS).unwrap();
+        assert!(mask.contains("rates"));
+        assert!(!mask.contains("day"));
+
+        assert!(FieldMask::new(Vec::new(), &FIELDS).unwrap().contains("day"));
+        assert!(FieldMask::new(vec!["unknown".to_owned()], &FIELDS).is_err());
+    }
+
+    #[test]
+    fn expect_to_select_requested_columns() {
+        let mask = FieldMask::new(vec!["positive".to_owned()], &FIELDS).unwrap();
+        let columns: [(&str, &[&str]); 2] = [("day", &["jour"]), ("positive", &["p", "jour"])];
+
+        assert_eq!(mask.select(&columns, &["dep"]), "\"dep\", \"p\", \"jour\"");
+    }
+
+    #[test]
+    fn expect_unrequested_fields_to_be_cleared() {
+        let mask = FieldMask::new(vec!["positive".to_owned()], &FIELDS).unwrap();
+        let mut rows = vec![Row { day: "2021-12-01".to_owned(), positive: 10 }];
+        mask.apply(&mut rows, &FIELDS);
+
+        assert_eq!(rows[0], Row { day: String::new(), positive: 10 });
+    }
+}
@@ -1,7 +1,18 @@
 use color_eyre::Result;
 use chrono::{NaiveDate, Datelike, Duration};
 
+#[macro_use]
+extern crate log;
+
 pub mod err;
+pub mod common;
+pub mod smoothing;
+pub mod page;
+pub mod field_mask;
+
+// Maximum number of days of a date range. Can be changed with the environment variable
+const ENV_MAX_RANGE_DAYS: &str = "max_range_days";
+const DEFAULT_MAX_RANGE_DAYS: i64 = 366;
 
 /// Setup the library and the address to use based on the environment variable
 /// for each gRPC microservices
@@ -36,6 +47,90 @@ pub fn get_server_addr(port: i32) -> String {
     }
 }
 
+/// Return the maximum number of days of a date range
+fn get_max_range_days() -> i64 {
+    std::env::var(ENV_MAX_RANGE_DAYS)
+        .ok()
+        .and_then(|v| v.parse::<i64>().ok())
+        .filter(|v| *v > 0)
+        .unwrap_or(DEFAULT_MAX_RANGE_DAYS)
+}
+
+/// Build the boundaries of a date range. When the day is omitted the start of the range
+/// falls on the first day of the month and the end of the range on the last day of the month.
+/// A range longer than the maximum number of days is rejected
+///
+/// # Arguments
+/// * `start` - &S
+/// * `end` - &E
+pub fn build_date_range<S: Date, E: Date>(start: &S, end: &E) -> Result<(NaiveDate, NaiveDate), err::MaskErr> {
+    let from = NaiveDate::from_ymd_opt(start.get_year(), start.get_month() as u32, start.get_day().unwrap_or(1) as u32)
+        .ok_or(err::MaskErr::InvalidDate)?;
+
+    let to = match end.get_day() {
+        Some(day) => NaiveDate::from_ymd_opt(end.get_year(), end.get_month() as u32, day as u32),
+        // last day of the month is the day before the first day of the next month
+        None => NaiveDate::from_ymd_opt(end.get_year(), end.get_month() as u32, 1)
+            .and_then(|d| d.checked_add_signed(Duration::days(31)))
+            .and_then(|d| NaiveDate::from_ymd_opt(d.year(), d.month(), 1))
+            .and_then(|d| d.pred_opt())
+    }
+    .ok_or(err::MaskErr::InvalidDate)?;
+
+    if from > to {
+        return Err(err::MaskErr::InvalidDate);
+    }
+
+    let max_days = get_max_range_days();
+    if (to - from).num_days() + 1 > max_days {
+        return Err(err::MaskErr::InvalidParam(format!("the date range must be at most {max_days} days")));
+    }
+
+    Ok((from, to))
+}
+
+/// Return every day between two dates (both included) formatted as YYYY-MM-DD
+///
+/// # Arguments
+/// * `from` - NaiveDate
+/// * `to` - NaiveDate
+pub fn get_days_between(from: NaiveDate, to: NaiveDate) -> Vec<String> {
+    let mut days = Vec::new();
+    let mut day = Some(from);
+    while let Some(d) = day.filter(|d| *d <= to) {
+        days.push(d.format("%Y-%m-%d").to_string());
+        day = d.succ_opt();
+    }
+
+    days
+}
+
+/// Return the list of days of a period ending at the given day (included) formatted as YYYY-MM-DD
+/// For example with a length of 7 and the day 2021-12-23, the method returns the days
+/// between 2021-12-17 -> 2021-12-23
+///
+/// # Arguments
+/// * `day` - NaiveDate
+/// * `length` - i64
+pub fn get_days_ending_at(day: NaiveDate, length: i64) -> Vec<String> {
+    get_days_between(day - Duration::days(length - 1), day)
+}
+
+/// Return the days of a ranking period ending at the given day and the days of the same period
+/// the previous week. The period is either the day or the last 7 days
+///
+/// # Arguments
+/// * `day` - NaiveDate
+/// * `is_week` - bool
+pub fn get_ranking_days(day: NaiveDate, is_week: bool) -> (Vec<String>, Vec<String>) {
+    let length = if is_week { 7 } else { 1 };
+
+    (
+        get_days_ending_at(day, length),
+        get_days_ending_at(day - Duration::days(7), length)
+    )
+}
+
 pub trait Date {
     /// Return the year
     /// 
@@ -53,6 +148,13 @@ pub trait Date {
     /// # Arguments
     /// * `&self` - &Date
     fn get_day(&self) -> Option<i32>;
+    /// Return whether the last day available in the dataset is requested instead of the date
+    ///
+    /// # Arguments
+    /// * `&self` - &Date
+    fn is_latest(&self) -> bool {
+        false
+    }
     /// Build Date based day, month, year from the struct. 
     /// Return an option if the date is valid.
     /// The date can be in either this format:
@@ -84,6 +186,18 @@ pub trait Date {
         }
     }
 
+    /// Build a chrono date from a date which requires a day
+    ///
+    /// # Arguments
+    /// * `&self` - Self
+    fn build_naive_date(&self) -> Result<NaiveDate, err::MaskErr> {
+        let day = self.get_day()
+            .ok_or_else(|| err::MaskErr::MissingParam("day".to_owned()))?;
+
+        NaiveDate::from_ymd_opt(self.get_year(), self.get_month() as u32, day as u32)
+            .ok_or(err::MaskErr::InvalidDate)
+    }
+
     /// Build a date and append a '%' for LIKE queries
     ///
     /// # Arguments
@@ -128,4 +242,39 @@ pub trait Date {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Day(i32, i32, Option<i32>);
 
+    impl Date for Day {
+        fn get_year(&self) -> i32 {
+            self.0
+        }
+
+        fn get_month(&self) -> i32 {
+            self.1
+        }
+
+        fn get_day(&self) -> Option<i32> {
+            self.2
+        }
+    }
+
+    #[test]
+    fn expect_range_to_be_built() {
+        let (from, to) = build_date_range(&Day(2021, 2, None), &Day(2021, 3, None)).unwrap();
+        assert_eq!(from, NaiveDate::from_ymd_opt(2021, 2, 1).unwrap());
+        assert_eq!(to, NaiveDate::from_ymd_opt(2021, 3, 31).unwrap());
+
+        assert!(build_date_range(&Day(2021, 3, Some(2)), &Day(2021, 3, Some(1))).is_err());
+    }
+
+    #[test]
+    fn expect_long_range_to_be_rejected() {
+        assert!(build_date_range(&Day(2021, 1, Some(1)), &Day(2021, 12, Some(31))).is_ok());
+        let res = build_date_range(&Day(2020, 1, Some(1)), &Day(2021, 12, Some(31)));
+        assert!(matches!(res, Err(err::MaskErr::InvalidParam(_))));
+    }
+}
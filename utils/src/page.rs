@@ -0,0 +1,156 @@
+use super::err::MaskErr;
+
+// Number of rows of a page when the size is not set
+const DEFAULT_PAGE_SIZE: u32 = 1000;
+const MAX_PAGE_SIZE: u32 = 10_000;
+const TOKEN_SEPARATOR: char = '|';
+
+/// Key of a row used to paginate: the day, the area & the age (or the sex) of the row
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageKey {
+    pub day: String,
+    pub area: String,
+    pub age: i64
+}
+
+/// Row of a paginated response
+pub trait Paginated {
+    /// Return the key of the row. The rows of a page are ordered by this key
+    ///
+    /// # Arguments
+    /// * `&self` - Self
+    fn page_key(&self) -> PageKey;
+}
+
+/// Page requested by an input
+#[derive(Debug, Clone, PartialEq)]
+pub struct Page {
+    pub size: u32,
+    // key of the last row of the previous page
+    pub after: Option<PageKey>
+}
+
+impl PageKey {
+    /// Encode the key as an opaque token
+    ///
+    /// # Arguments
+    /// * `&self` - Self
+    pub fn to_token(&self) -> String {
+        let key = format!("{}{TOKEN_SEPARATOR}{}{TOKEN_SEPARATOR}{}", self.day, self.area, self.age);
+        base64::encode_config(key, base64::URL_SAFE_NO_PAD)
+    }
+
+    /// Decode the key of a token
+    ///
+    /// # Arguments
+    /// * `token` - &str
+    pub fn from_token(token: &str) -> Result<Self, MaskErr> {
+        let invalid = || MaskErr::InvalidParam(format!("the page token {token} is invalid"));
+        let key = base64::decode_config(token, base64::URL_SAFE_NO_PAD)
+            .ok()
+            .and_then(|k| String::from_utf8(k).ok())
+            .ok_or_else(invalid)?;
+
+        let mut parts = key.splitn(3, TOKEN_SEPARATOR);
+        match (parts.next(), parts.next(), parts.next().and_then(|a| a.parse::<i64>().ok())) {
+            (Some(day), Some(area), Some(age)) => Ok(PageKey { day: day.to_owned(), area: area.to_owned(), age }),
+            _ => Err(invalid())
+        }
+    }
+}
+
+impl Page {
+    /// Create the page of an input. The first page is requested when the token is empty
+    ///
+    /// # Arguments
+    /// * `size` - u32
+    /// * `token` - &str
+    pub fn new(size: u32, token: &str) -> Result<Self, MaskErr> {
+        let size = match size {
+            0 => DEFAULT_PAGE_SIZE,
+            s if s > MAX_PAGE_SIZE => return Err(MaskErr::InvalidParam(format!("the page size must be at most {MAX_PAGE_SIZE}"))),
+            s => s
+        };
+
+        let after = match token.is_empty() {
+            true => None,
+            false => Some(PageKey::from_token(token)?)
+        };
+
+        Ok(Page { size, after })
+    }
+
+    /// Return the number of rows to fetch. One more row than the size of the page is
+    /// fetched in order to know if there is a next page
+    ///
+    /// # Arguments
+    /// * `&self` - Self
+    pub fn limit(&self) -> i64 {
+        self.size as i64 + 1
+    }
+
+    /// Return the key of the last row of the previous page as the parameters of a query
+    ///
+    /// # Arguments
+    /// * `&self` - Self
+    pub fn after(&self) -> Option<(&str, &str, i64)> {
+        self.after.as_ref().map(|k| (k.day.as_str(), k.area.as_str(), k.age))
+    }
+
+    /// Remove the extra row fetched with the limit of the page and return the token of
+    /// the next page. The token is empty on the last page
+    ///
+    /// # Arguments
+    /// * `&self` - Self
+    /// * `rows` - &mut Vec<T>
+    pub fn next_token<T: Paginated>(&self, rows: &mut Vec<T>) -> String {
+        if rows.len() <= self.size as usize {
+            return String::new();
+        }
+
+        rows.truncate(self.size as usize);
+        rows.last()
+            .map(|r| r.page_key().to_token())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Row(i64);
+
+    impl Paginated for Row {
+        fn page_key(&self) -> PageKey {
+            PageKey { day: "2021-12-01".to_owned(), area: "75".to_owned(), age: self.0 }
+        }
+    }
+
+    #[test]
+    fn expect_token_to_be_decoded() {
+        let key = PageKey { day: "2021-12-01".to_owned(), area: "2A".to_owned(), age: 89 };
+        assert_eq!(PageKey::from_token(&key.to_token()).unwrap(), key);
+        assert!(PageKey::from_token("not a token").is_err());
+    }
+
+    #[test]
+    fn expect_page_to_be_validated() {
+        assert_eq!(Page::new(0, "").unwrap().size, DEFAULT_PAGE_SIZE);
+        assert!(Page::new(MAX_PAGE_SIZE + 1, "").is_err());
+        assert!(Page::new(10, "?").is_err());
+    }
+
+    #[test]
+    fn expect_next_token_to_be_the_last_row() {
+        let page = Page::new(2, "").unwrap();
+
+        let mut rows = vec![Row(0), Row(9), Row(19)];
+        let token = page.next_token(&mut rows);
+        assert_eq!(rows.len(), 2);
+        assert_eq!(PageKey::from_token(&token).unwrap().age, 9);
+
+        let mut rows = vec![Row(0), Row(9)];
+        assert!(page.next_token(&mut rows).is_empty());
+    }
+}
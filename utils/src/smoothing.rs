@@ -0,0 +1,302 @@
+use std::collections::HashMap;
+use chrono::{NaiveDate, Duration};
+use super::{Date, err::MaskErr, common::proto_common::{SmoothingOptions, smoothing_options}};
+
+// Number of days of the rolling mean when the window is not set
+const DEFAULT_WINDOW: u32 = 7;
+const MAX_WINDOW: u32 = 31;
+
+/// Position of the day within the window of the rolling mean
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Method {
+    // the window ends on the day
+    Trailing,
+    // the window is centered on the day
+    Centered
+}
+
+/// Rolling mean applied on daily series
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Smoothing {
+    pub method: Method,
+    pub window: usize
+}
+
+/// Row of a daily series which can be smoothed
+pub trait Smoothable {
+    /// Return the key of the series of the row (e.g. department, age...)
+    ///
+    /// # Arguments
+    /// * `&self` - Self
+    fn series(&self) -> String;
+    /// Return the day of the row formatted as YYYY-MM-DD
+    ///
+    /// # Arguments
+    /// * `&self` - Self
+    fn day(&self) -> &str;
+    /// Return the raw values to smooth
+    ///
+    /// # Arguments
+    /// * `&self` - Self
+    fn values(&self) -> Vec<f64>;
+    /// Set the smoothed values. The values are in the same order as the raw values
+    ///
+    /// # Arguments
+    /// * `&mut self` - Self
+    /// * `values` - Vec<f64>
+    fn set_smoothed(&mut self, values: Vec<f64>);
+}
+
+impl Smoothing {
+    /// Create a new smoothing. A window of 0 fallback to a 7 days window
+    /// A centered window needs an odd number of days
+    ///
+    /// # Arguments
+    /// * `method` - Method
+    /// * `window` - u32
+    pub fn new(method: Method, window: u32) -> Result<Self, MaskErr> {
+        let window = if window == 0 { DEFAULT_WINDOW } else { window };
+        if window > MAX_WINDOW {
+            return Err(MaskErr::InvalidParam(format!("the smoothing window can't exceed {MAX_WINDOW} days")));
+        }
+
+        if method == Method::Centered && window % 2 == 0 {
+            return Err(MaskErr::InvalidParam("a centered smoothing window needs an odd number of days".to_owned()));
+        }
+
+        Ok(Smoothing { method, window: window as usize })
+    }
+
+    /// Return the number of days before & after a day which are needed to smooth the day
+    ///
+    /// # Arguments
+    /// * `&self` - Self
+    fn bounds(&self) -> (usize, usize) {
+        match self.method {
+            Method::Trailing => (self.window - 1, 0),
+            Method::Centered => (self.window / 2, self.window / 2)
+        }
+    }
+
+    /// Extend a date range with the days needed to smooth the first & the last day of the range
+    ///
+    /// # Arguments
+    /// * `&self` - Self
+    /// * `from` - NaiveDate
+    /// * `to` - NaiveDate
+    pub fn extend_range(&self, from: NaiveDate, to: NaiveDate) -> (NaiveDate, NaiveDate) {
+        let (before, after) = self.bounds();
+
+        (from - Duration::days(before as i64), to + Duration::days(after as i64))
+    }
+
+    /// Return the range of days to query in order to smooth the rows of a date. The date
+    /// is either a day or a month
+    ///
+    /// # Arguments
+    /// * `&self` - Self
+    /// * `date` - &D
+    pub fn history_range<D: Date>(&self, date: &D) -> Result<(NaiveDate, NaiveDate), MaskErr> {
+        let (from, to) = super::build_date_range(date, date)?;
+
+        Ok(self.extend_range(from, to))
+    }
+
+    /// Return the range of days to query in order to smooth the given rows, which is the
+    /// range of their days extended by the window. None when there is no row to smooth
+    ///
+    /// # Arguments
+    /// * `&self` - Self
+    /// * `rows` - &[T]
+    pub fn rows_range<T: Smoothable>(&self, rows: &[T]) -> Option<(NaiveDate, NaiveDate)> {
+        let days: Vec<NaiveDate> = rows
+            .iter()
+            .filter_map(|r| NaiveDate::parse_from_str(r.day(), "%Y-%m-%d").ok())
+            .collect();
+
+        let (from, to) = (days.iter().min()?, days.iter().max()?);
+
+        Some(self.extend_range(*from, *to))
+    }
+
+    /// Compute the rolling mean of a daily series. A day is only smoothed when
+    /// every day of its window has a value
+    ///
+    /// # Arguments
+    /// * `&self` - Self
+    /// * `values` - &[Option<f64>]
+    pub fn rolling_mean(&self, values: &[Option<f64>]) -> Vec<Option<f64>> {
+        let (before, after) = self.bounds();
+
+        (0..values.len())
+            .map(|idx| {
+                if idx < before || idx + after >= values.len() {
+                    return None;
+                }
+
+                values[idx - before..=idx + after]
+                    .iter()
+                    .copied()
+                    .sum::<Option<f64>>()
+                    .map(|sum| sum / self.window as f64)
+            })
+            .collect()
+    }
+
+    /// Smooth the rows with the history of their series. The history should contain
+    /// the rows between the days returned by `history_range` or `rows_range`
+    ///
+    /// # Arguments
+    /// * `&self` - Self
+    /// * `rows` - &mut [T]
+    /// * `history` - &[T]
+    /// * `from` - NaiveDate
+    /// * `to` - NaiveDate
+    pub fn apply<T: Smoothable>(&self, rows: &mut [T], history: &[T], from: NaiveDate, to: NaiveDate) {
+        let days = super::get_days_between(from, to);
+        let mut series: HashMap<String, HashMap<&str, Vec<f64>>> = HashMap::new();
+        for row in history {
+            series
+                .entry(row.series())
+                .or_default()
+                .insert(row.day(), row.values());
+        }
+
+        let mut smoothed: HashMap<(String, String), Vec<f64>> = HashMap::new();
+        for (key, points) in series {
+            let size = points.values().map(|v| v.len()).max().unwrap_or_default();
+            let means: Vec<Vec<Option<f64>>> = (0..size)
+                .map(|idx| {
+                    let values: Vec<Option<f64>> = days
+                        .iter()
+                        .map(|d| points.get(d.as_str()).and_then(|v| v.get(idx).copied()))
+                        .collect();
+
+                    self.rolling_mean(&values)
+                })
+                .collect();
+
+            for (idx, day) in days.iter().enumerate() {
+                let values: Option<Vec<f64>> = means.iter().map(|m| m[idx]).collect();
+                if let Some(values) = values {
+                    smoothed.insert((key.clone(), day.to_owned()), values);
+                }
+            }
+        }
+
+        for row in rows.iter_mut() {
+            if let Some(values) = smoothed.remove(&(row.series(), row.day().to_owned())) {
+                row.set_smoothed(values);
+            }
+        }
+    }
+}
+
+impl TryFrom<SmoothingOptions> for Smoothing {
+    type Error = MaskErr;
+
+    fn try_from(options: SmoothingOptions) -> Result<Self, Self::Error> {
+        let method = match options.method() {
+            smoothing_options::Method::Trailing => Method::Trailing,
+            smoothing_options::Method::Centered => Method::Centered
+        };
+
+        Smoothing::new(method, options.window)
+    }
+}
+
+/// Build the smoothing requested by an input. Return None if the input does not ask for smoothing
+///
+/// # Arguments
+/// * `options` - Option<SmoothingOptions>
+pub fn from_options(options: Option<SmoothingOptions>) -> Result<Option<Smoothing>, MaskErr> {
+    options.map(Smoothing::try_from).transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Row {
+        series: &'static str,
+        day: String,
+        value: f64,
+        smoothed: Option<f64>
+    }
+
+    impl Smoothable for Row {
+        fn series(&self) -> String {
+            self.series.to_owned()
+        }
+
+        fn day(&self) -> &str {
+            &self.day
+        }
+
+        fn values(&self) -> Vec<f64> {
+            vec![self.value]
+        }
+
+        fn set_smoothed(&mut self, values: Vec<f64>) {
+            self.smoothed = values.first().copied();
+        }
+    }
+
+    #[test]
+    fn expect_to_compute_rolling_mean() {
+        let values = vec![Some(1.0), Some(2.0), Some(3.0), Some(4.0), None, Some(6.0)];
+
+        let trailing = Smoothing::new(Method::Trailing, 3).unwrap();
+        assert_eq!(trailing.rolling_mean(&values), vec![None, None, Some(2.0), Some(3.0), None, None]);
+
+        let centered = Smoothing::new(Method::Centered, 3).unwrap();
+        assert_eq!(centered.rolling_mean(&values), vec![None, Some(2.0), Some(3.0), None, None, None]);
+    }
+
+    #[test]
+    fn expect_options_to_be_converted() {
+        let options = SmoothingOptions { method: smoothing_options::Method::Centered as i32, window: 4 };
+        assert!(from_options(Some(options)).is_err());
+
+        let options = SmoothingOptions { method: smoothing_options::Method::Centered as i32, window: 5 };
+        assert_eq!(from_options(Some(options)).unwrap(), Some(Smoothing { method: Method::Centered, window: 5 }));
+        assert_eq!(from_options(None).unwrap(), None);
+    }
+
+    #[test]
+    fn expect_invalid_window_to_return_error() {
+        assert_eq!(Smoothing::new(Method::Trailing, 0).unwrap().window, 7);
+        assert!(Smoothing::new(Method::Centered, 4).is_err());
+        assert!(Smoothing::new(Method::Trailing, 60).is_err());
+    }
+
+    #[test]
+    fn expect_range_to_cover_the_rows() {
+        let smoothing = Smoothing::new(Method::Centered, 5).unwrap();
+        let row = |day: &str| Row { series: "75", day: day.to_owned(), value: 1.0, smoothed: None };
+
+        let (from, to) = smoothing.rows_range(&[row("2021-12-10"), row("2021-12-04")]).unwrap();
+        assert_eq!(from, NaiveDate::from_ymd_opt(2021, 12, 2).unwrap());
+        assert_eq!(to, NaiveDate::from_ymd_opt(2021, 12, 12).unwrap());
+        assert_eq!(smoothing.rows_range::<Row>(&[]), None);
+    }
+
+    #[test]
+    fn expect_to_smooth_each_series() {
+        let smoothing = Smoothing::new(Method::Trailing, 2).unwrap();
+        let day = NaiveDate::from_ymd_opt(2021, 12, 2).unwrap();
+        let (from, to) = smoothing.extend_range(day, day);
+
+        let row = |series, day: &str, value| Row { series, day: day.to_owned(), value, smoothed: None };
+        let history = vec![
+            row("75", "2021-12-01", 2.0),
+            row("75", "2021-12-02", 4.0),
+            row("13", "2021-12-02", 8.0)
+        ];
+        let mut rows = vec![row("75", "2021-12-02", 4.0), row("13", "2021-12-02", 8.0)];
+        smoothing.apply(&mut rows, &history, from, to);
+
+        assert_eq!(rows[0].smoothed, Some(3.0));
+        assert_eq!(rows[1].smoothed, None);
+    }
+}